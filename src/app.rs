@@ -1,211 +1,3362 @@
-use crate::file_system::{get_file_list, FileEntry};
-use crate::git_ops::{create_commit, stage_all_modified, update_right_pane};
+use crate::bisect::{Bisect, BisectProgress};
+use crate::clipboard;
+use crate::config::{Config, MAX_CONTEXT_LINES};
+use crate::file_system::{
+    get_file_list, is_expanded, FileEntry, FileListOptions, SortOrder, StatusFilter, StatusSummary,
+};
+use crate::git_ops::{
+    amend_commit, apply_hunk_lines, blame_file, branch_ahead_behind, checkout_branch,
+    cherry_pick_commit, commit_template, create_branch, create_commit, create_tag, delete_branch,
+    head_status, last_commit_message, list_local_branches, list_stashes, list_submodules,
+    load_commits, load_reflog, merge_branch, rename_branch, reset_to_commit, resolve_commit,
+    revert_commit, show_commit, split_rows, stage_file, stage_hunk, staged_diff, staged_files,
+    stash_apply, stash_drop, stash_save, strip_comments, tags_by_commit, take_conflict_side,
+    unmerged_commit_count, unstage_all, unstage_file, unstage_hunk, update_right_pane, BranchInfo,
+    BranchUpstream, CommitInfo, DiffLine, DiffRequest, DiffSettings, HeadStatus, Hunk, LineKind,
+    MergeOutcome, ReflogEntry, SplitRow, StagedFile, StashInfo, SubmoduleInfo, Whitespace,
+};
+use crate::keymap::{Action, KeyMap};
+use crate::rebase::{todo_list, Rebase, RebaseProgress, TodoAction, TodoItem};
+use crate::worker::{FileList, GitResult, GitTask, StatusRequest, Worker};
 use crossterm::event::Event;
-use crossterm::event::{KeyCode, KeyEvent};
-use git2::Repository;
-use std::collections::HashMap;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use git2::{Oid, Repository, ResetType, Status};
+use ratatui::layout::Rect;
+use ratatui::widgets::ListState;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
 
 pub type AppResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+#[derive(Default)]
 pub struct Modal {
     pub content: String,
     pub is_visible: bool,
+    pub error: Option<String>,
+    /// Byte offset of the text cursor in `content`, always on a character boundary.
+    pub cursor: usize,
+}
+
+impl Modal {
+    pub fn set_content(&mut self, content: String) {
+        self.cursor = content.len();
+        self.content = content;
+    }
+
+    pub fn clear(&mut self) {
+        self.content.clear();
+        self.cursor = 0;
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.content.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+        self.error = None;
+    }
+
+    /// Applies a text-editing key at the cursor, clearing any stale error message. Up and
+    /// Down move between lines, keeping the column where the line is long enough.
+    pub fn handle_text_input(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char(c) => self.insert(c),
+            KeyCode::Backspace => {
+                if let Some(c) = self.content[..self.cursor].chars().next_back() {
+                    self.cursor -= c.len_utf8();
+                    self.content.remove(self.cursor);
+                    self.error = None;
+                }
+            }
+            KeyCode::Delete if self.cursor < self.content.len() => {
+                self.content.remove(self.cursor);
+                self.error = None;
+            }
+            KeyCode::Left => {
+                if let Some(c) = self.content[..self.cursor].chars().next_back() {
+                    self.cursor -= c.len_utf8();
+                }
+            }
+            KeyCode::Right => {
+                if let Some(c) = self.content[self.cursor..].chars().next() {
+                    self.cursor += c.len_utf8();
+                }
+            }
+            KeyCode::Home => self.cursor = self.line_start(self.cursor),
+            KeyCode::End => self.cursor = self.line_end(self.cursor),
+            KeyCode::Up => {
+                let start = self.line_start(self.cursor);
+                if start > 0 {
+                    let column = self.content[start..self.cursor].chars().count();
+                    self.cursor = self.offset_in_line(self.line_start(start - 1), column);
+                }
+            }
+            KeyCode::Down => {
+                let end = self.line_end(self.cursor);
+                if end < self.content.len() {
+                    let column = self.content[self.line_start(self.cursor)..self.cursor]
+                        .chars()
+                        .count();
+                    self.cursor = self.offset_in_line(end + 1, column);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The cursor as (line, column), counting characters.
+    pub fn cursor_position(&self) -> (usize, usize) {
+        let before = &self.content[..self.cursor];
+        let line = before.matches('\n').count();
+        let column = before[self.line_start(self.cursor)..].chars().count();
+        (line, column)
+    }
+
+    fn line_start(&self, offset: usize) -> usize {
+        self.content[..offset]
+            .rfind('\n')
+            .map_or(0, |index| index + 1)
+    }
+
+    fn line_end(&self, offset: usize) -> usize {
+        self.content[offset..]
+            .find('\n')
+            .map_or(self.content.len(), |index| offset + index)
+    }
+
+    /// Offset of `column` characters into the line starting at `start`, or the line's end if
+    /// it is shorter.
+    fn offset_in_line(&self, start: usize, column: usize) -> usize {
+        let end = self.line_end(start);
+        self.content[start..end]
+            .char_indices()
+            .nth(column)
+            .map_or(end, |(index, _)| start + index)
+    }
+}
+
+/// What a one-line prompt does with its text when Enter is pressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputAction {
+    CreateBranch,
+    /// Holds the name of the local branch being renamed.
+    RenameBranch(String),
+    CherryPick,
+    ShowCommit,
+    /// Holds "name [message]" for a new tag on the given commit, or HEAD for `None`.
+    CreateTag(Option<Oid>),
+}
+
+impl InputAction {
+    fn title(&self, checkout_new_branch: bool) -> &'static str {
+        match self {
+            InputAction::CreateBranch if checkout_new_branch => {
+                "New Branch (Tab: switch to it after creating: yes)"
+            }
+            InputAction::CreateBranch => "New Branch (Tab: switch to it after creating: no)",
+            InputAction::RenameBranch(_) => "Rename Branch",
+            InputAction::CherryPick => "Cherry-pick (commit id, branch or tag)",
+            InputAction::ShowCommit => "Show Commit (commit id, branch, tag or HEAD~2)",
+            InputAction::CreateTag(Some(_)) => {
+                "Tag Selected Commit (name, then a message for an annotated tag)"
+            }
+            InputAction::CreateTag(None) => "Tag HEAD (name, then a message for an annotated tag)",
+        }
+    }
+}
+
+/// A one-line text prompt. The prompts share the editing keys of `Modal` and are drawn the
+/// same way; only the title and what Enter does differ. A failed action leaves the prompt
+/// open with the error below the text.
+pub struct InputModal {
+    pub title: String,
+    pub field: Modal,
+    pub action: InputAction,
+}
+
+/// Incremental search over the file list. The query stays set after the prompt is closed
+/// with Enter so that matches remain highlighted and can be cycled through.
+pub struct Search {
+    pub prompt: Modal,
+    /// Selection when the prompt was opened, restored if the search is cancelled.
+    origin: usize,
+}
+
+impl Search {
+    pub fn query(&self) -> &str {
+        &self.prompt.content
+    }
+
+    /// Case-insensitive substring match against the current query; nothing matches an empty
+    /// query.
+    pub fn matches(&self, name: &str) -> bool {
+        !self.query().is_empty() && name.to_lowercase().contains(&self.query().to_lowercase())
+    }
+}
+
+/// An action that waits for the user to confirm it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PendingAction {
+    Revert(Oid),
+    AbortRebase,
+    HardReset(Oid),
+    /// Deleting a local branch that has commits HEAD doesn't.
+    DeleteBranch(String),
+    Quit,
+}
+
+/// A yes/no question asked before doing something that is hard to undo.
+pub struct Confirmation {
+    pub prompt: String,
+    pub action: PendingAction,
+}
+
+pub struct BranchList {
+    pub branches: Vec<BranchInfo>,
+    pub selected: usize,
+    pub is_visible: bool,
+    pub error: Option<String>,
+}
+
+/// Asks how to reset the current branch to a commit from the history or the reflog.
+pub struct ResetMenu {
+    pub target: Oid,
+    /// The commit's short id and summary.
+    pub label: String,
+}
+
+/// The todo list of an interactive rebase, being edited or, once the rebase has stopped on
+/// conflicts, what is left of it.
+pub struct RebaseTodo {
+    pub items: Vec<TodoItem>,
+    pub selected: usize,
+    pub is_visible: bool,
+    pub error: Option<String>,
+}
+
+pub struct StashList {
+    pub stashes: Vec<StashInfo>,
+    pub selected: usize,
+    pub is_visible: bool,
+    pub error: Option<String>,
+}
+
+pub struct ReflogList {
+    pub entries: Vec<ReflogEntry>,
+    pub selected: usize,
+    pub is_visible: bool,
+    pub error: Option<String>,
+}
+
+pub struct SubmoduleList {
+    pub submodules: Vec<SubmoduleInfo>,
+    pub selected: usize,
+    pub is_visible: bool,
+    pub error: Option<String>,
+}
+
+/// Identifies a file's diff as it was computed. A change to the file, the index or the
+/// diff settings gives a different key, so stale entries are never looked up again.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct DiffCacheKey {
+    path: String,
+    status: Status,
+    modified: Option<SystemTime>,
+    index_modified: Option<SystemTime>,
+    settings: DiffSettings,
+    full: bool,
+}
+
+/// Lines of a hunk picked for staging on their own, as the fixed and the moving end of the
+/// range, both indices into `right_pane_lines`. The hunk is kept to tell whether the
+/// selection still applies to what the Details pane shows.
+struct LineSelection {
+    hunk: Hunk,
+    anchor: usize,
+    cursor: usize,
+}
+
+/// Where each pane was drawn in the last frame, for mapping mouse events to panes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PaneAreas {
+    pub file_list: Rect,
+    pub details: Rect,
+    /// `None` while the debug pane is hidden.
+    pub debug: Option<Rect>,
+}
+
+/// Lines or entries moved per mouse wheel notch.
+const WHEEL_STEP: usize = 3;
+
+/// Longest gap between two clicks on the same entry that still counts as a double click.
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Number of commits loaded into the history view at a time.
+const HISTORY_PAGE_SIZE: usize = 200;
+
+/// Branches either side of the selection whose ahead/behind counts are worked out.
+const BRANCH_COUNT_WINDOW: usize = 30;
+
+/// Columns moved per ←/→ press when the Details pane scrolls sideways.
+const HSCROLL_STEP: usize = 8;
+
+/// Percentage points the file list grows or shrinks by per `<`/`>` press.
+const PANE_WIDTH_STEP: u16 = 5;
+
+/// The narrowest any pane can be made, as a percentage of the terminal.
+const MIN_PANE_WIDTH: u16 = 10;
+
+pub struct History {
+    pub commits: Vec<CommitInfo>,
+    pub selected: usize,
+    pub is_visible: bool,
+    /// Set once the revwalk has run out of commits, so no further pages are requested.
+    pub exhausted: bool,
+    pub list_state: ListState,
+    /// Tag names by the commit they point at, read when the history is opened.
+    pub tags: HashMap<Oid, Vec<String>>,
+    /// Whether commit dates are shown as timestamps rather than "3 hours ago".
+    pub absolute_dates: bool,
 }
 
 pub struct App {
     pub files: Vec<FileEntry>,
+    pub status_summary: StatusSummary,
+    pub head_status: HeadStatus,
     pub expanded_dirs: HashMap<String, bool>,
     pub selected_index: usize,
-    pub right_pane_content: String,
-    pub debug_content: String,
+    pub right_pane_lines: Vec<DiffLine>,
+    /// The newest lines of the debug log, at most `debug_log_lines` from the config.
+    pub debug_lines: VecDeque<String>,
+    /// How many older lines were dropped to stay within the limit.
+    pub debug_trimmed: usize,
     pub commit_modal: Modal,
+    /// What the commit will take, listed in the commit modal.
+    pub commit_files: Vec<StagedFile>,
+    pub amend_mode: bool,
+    /// Set when the commit message should be written in the external editor, which the
+    /// main loop starts since it has to hand over the terminal.
+    editor_requested: bool,
     pub help_modal: Modal,
+    pub help_scroll: usize,
+    /// Largest useful `help_scroll`, worked out when the help is drawn.
+    pub help_max_scroll: usize,
+    pub branch_list: BranchList,
+    pub stash_list: StashList,
+    pub submodule_list: SubmoduleList,
+    pub reflog: ReflogList,
+    pub rebase_todo: RebaseTodo,
+    /// The new message of the todo item being reworded.
+    pub reword_modal: Modal,
+    /// A rebase that stopped on conflicts.
+    pub rebase: Option<Rebase>,
+    pub bisect: Option<Bisect>,
+    pub input: Option<InputModal>,
+    pub checkout_new_branch: bool,
+    pub search: Search,
+    pub history: History,
+    pub confirmation: Option<Confirmation>,
+    /// Set once the user has asked to quit and, if needed, confirmed it.
+    pub should_quit: bool,
+    pub reset_menu: Option<ResetMenu>,
+    pub worker: Worker,
     pub debug_mode: bool,
+    /// Rows the debug pane is scrolled down by, or `None` to follow the newest messages.
+    pub debug_scroll: Option<usize>,
+    /// Largest useful `debug_scroll`, worked out when the debug pane is drawn.
+    pub debug_max_scroll: usize,
     pub focused_pane: FocusedPane,
     pub details_scroll: usize,
+    /// Largest useful `details_scroll`, worked out from the wrapped content and pane height
+    /// when the Details pane is drawn.
+    pub details_max_scroll: usize,
+    /// Columns the Details pane is scrolled to the right by when lines don't wrap.
+    pub details_hscroll: usize,
+    /// Largest useful `details_hscroll`, from the longest line and the pane width.
+    pub details_max_hscroll: usize,
+    /// Whether diffs are shown in two columns, old on the left and new on the right.
+    pub side_by_side: bool,
+    /// Whether long lines in the Details pane wrap; otherwise they run off its right edge.
+    pub wrap_lines: bool,
+    /// Whether the last frame actually drew two columns; narrow panes stay unified.
+    pub details_split_shown: bool,
+    pub diff_hunks: Vec<Hunk>,
+    line_selection: Option<LineSelection>,
+    /// Details pane contents of files shown before, so that going back to one is instant.
+    diff_cache: HashMap<DiffCacheKey, (Vec<DiffLine>, Vec<Hunk>)>,
+    /// The diff the worker is reading for the Details pane, with where to cache it.
+    pending_diff: Option<(DiffRequest, Option<DiffCacheKey>)>,
+    /// The file list the worker is reading. Reading it here as well makes that one stale.
+    pending_status: Option<StatusRequest>,
+    pub selected_hunk: usize,
+    pub keymap: KeyMap,
+    pub config: Config,
+    pub list_options: FileListOptions,
+    pub diff_settings: DiffSettings,
+    /// The file whose diff is shown in full, however long.
+    pub full_diff: Option<String>,
+    /// Width of the file list as a percentage of the terminal, starting from the config and
+    /// changed with `<`/`>` or by dragging its border.
+    pub file_list_width: u16,
+    pub debug_pane_width: u16,
+    /// Set while the border between the file list and the Details pane is being dragged.
+    resizing: bool,
+    pub pane_areas: PaneAreas,
+    pub file_list_state: ListState,
+    last_click: Option<(Instant, usize)>,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum FocusedPane {
     FileList,
     Details,
+    /// Only while the debug pane is shown.
+    Debug,
 }
 
 impl App {
-    pub fn new(repo: &Repository) -> Self {
-        let files = get_file_list(repo);
-        Self {
+    pub fn new(repo: &Repository, keymap: KeyMap, config: Config) -> AppResult<Self> {
+        let list_options = FileListOptions {
+            show_untracked: config.show_untracked,
+            show_ignored: false,
+            sort: SortOrder::Name,
+            show_file_info: false,
+            status_filter: None,
+        };
+        let mut expanded_dirs = HashMap::new();
+        let (files, status_summary) = get_file_list(repo, list_options, &mut expanded_dirs)?;
+        let mut app = Self {
             files,
-            expanded_dirs: HashMap::new(),
+            status_summary,
+            head_status: head_status(repo),
+            expanded_dirs,
             selected_index: 0,
-            right_pane_content: String::new(),
-            debug_content: String::new(), // Add this line
-            commit_modal: Modal {
-                content: String::new(),
+            right_pane_lines: Vec::new(),
+            debug_lines: VecDeque::new(),
+            debug_trimmed: 0,
+            commit_modal: Modal::default(),
+            commit_files: Vec::new(),
+            amend_mode: false,
+            editor_requested: false,
+            help_modal: Modal {
+                content: help_content(&keymap),
+                ..Modal::default()
+            },
+            help_scroll: 0,
+            help_max_scroll: 0,
+            branch_list: BranchList {
+                branches: Vec::new(),
+                selected: 0,
                 is_visible: false,
+                error: None,
             },
-            help_modal: Modal {
-                content: get_help_content(),
+            stash_list: StashList {
+                stashes: Vec::new(),
+                selected: 0,
+                is_visible: false,
+                error: None,
+            },
+            submodule_list: SubmoduleList {
+                submodules: Vec::new(),
+                selected: 0,
                 is_visible: false,
+                error: None,
             },
-            debug_mode: false,
+            reflog: ReflogList {
+                entries: Vec::new(),
+                selected: 0,
+                is_visible: false,
+                error: None,
+            },
+            rebase_todo: RebaseTodo {
+                items: Vec::new(),
+                selected: 0,
+                is_visible: false,
+                error: None,
+            },
+            reword_modal: Modal::default(),
+            rebase: None,
+            bisect: None,
+            input: None,
+            checkout_new_branch: true,
+            search: Search {
+                prompt: Modal::default(),
+                origin: 0,
+            },
+            history: History {
+                commits: Vec::new(),
+                selected: 0,
+                is_visible: false,
+                exhausted: false,
+                list_state: ListState::default(),
+                tags: HashMap::new(),
+                absolute_dates: false,
+            },
+            confirmation: None,
+            should_quit: false,
+            reset_menu: None,
+            worker: Worker::spawn(repo.path()),
+            debug_mode: config.debug_mode,
+            debug_scroll: None,
+            debug_max_scroll: 0,
             focused_pane: FocusedPane::FileList,
             details_scroll: 0,
+            details_max_scroll: 0,
+            details_hscroll: 0,
+            details_max_hscroll: 0,
+            side_by_side: false,
+            wrap_lines: true,
+            details_split_shown: false,
+            diff_hunks: Vec::new(),
+            line_selection: None,
+            diff_cache: HashMap::new(),
+            pending_diff: None,
+            pending_status: None,
+            selected_hunk: 0,
+            keymap,
+            list_options,
+            diff_settings: DiffSettings {
+                context_lines: config.context_lines,
+                whitespace: Whitespace::Show,
+            },
+            full_diff: None,
+            file_list_width: config.file_list_width,
+            debug_pane_width: config.debug_pane_width,
+            resizing: false,
+            config,
+            pane_areas: PaneAreas::default(),
+            file_list_state: ListState::default(),
+            last_click: None,
+        };
+        if let Some(hint) = sync_hint(&app.head_status, &app.keymap) {
+            app.debug_log(&hint);
         }
+        Ok(app)
     }
 
     pub fn handle_event(&mut self, event: Event, repo: &Repository) -> AppResult<()> {
-        if let Event::Key(key) = event {
-            self.handle_key_event(key, repo)?
+        match event {
+            Event::Key(key) => self.handle_key_event(key, repo)?,
+            Event::Mouse(mouse) if !self.has_open_modal() => {
+                self.handle_mouse_event(mouse, repo)?
+            }
+            _ => {}
         }
         Ok(())
     }
 
-    pub fn handle_key_event(&mut self, key: KeyEvent, repo: &Repository) -> AppResult<()> {
-        if self.commit_modal.is_visible {
-            match key.code {
-                KeyCode::Enter => self.perform_commit(repo)?,
-                KeyCode::Esc => self.close_modals(),
-                KeyCode::Char(c) => self.commit_modal.content.push(c),
-                KeyCode::Backspace => {
-                    self.commit_modal.content.pop();
+    /// Left click focuses the pane under the cursor, and in the file list selects the entry
+    /// clicked; double-clicking a directory expands or collapses it. The wheel scrolls the
+    /// Details pane or moves the selection in the file list, whichever is under the cursor.
+    pub fn handle_mouse_event(&mut self, mouse: MouseEvent, repo: &Repository) -> AppResult<()> {
+        if self.resizing {
+            match mouse.kind {
+                MouseEventKind::Drag(MouseButton::Left) => self.drag_divider(mouse.column),
+                MouseEventKind::Up(MouseButton::Left) => {
+                    self.resizing = false;
+                    self.log_file_list_width();
                 }
                 _ => {}
             }
-        } else {
-            match (self.focused_pane, key.code) {
-                (FocusedPane::FileList, KeyCode::Up) => self.move_selection_up(1),
-                (FocusedPane::FileList, KeyCode::Down) => self.move_selection_down(1),
-                (FocusedPane::FileList, KeyCode::PageUp) => self.move_selection_up(10),
-                (FocusedPane::FileList, KeyCode::PageDown) => self.move_selection_down(10),
-                (FocusedPane::Details, KeyCode::Up) => self.scroll_details_up(1),
-                (FocusedPane::Details, KeyCode::PageUp) => self.scroll_details_up(10),
-                (FocusedPane::Details, KeyCode::Down) => self.scroll_details_down(1),
-                (FocusedPane::Details, KeyCode::PageDown) => self.scroll_details_down(10),
-                (_, KeyCode::Left) => self.set_focused_pane(FocusedPane::FileList),
-                (_, KeyCode::Right) => self.set_focused_pane(FocusedPane::Details),
-                (_, KeyCode::Enter) => self.show_details(repo)?,
-                (_, KeyCode::Char('c')) => self.start_commit(repo)?,
-                (_, KeyCode::Char('?')) => self.toggle_help(),
-                (_, KeyCode::Char('d')) => self.toggle_debug_mode(), // Add this line
-                (_, KeyCode::Esc) => self.close_modals(),
+            return Ok(());
+        }
+        let over_debug = self
+            .pane_areas
+            .debug
+            .is_some_and(|area| contains(area, mouse.column, mouse.row));
+        if over_debug {
+            match mouse.kind {
+                MouseEventKind::Down(MouseButton::Left) => {
+                    self.set_focused_pane(FocusedPane::Debug)
+                }
+                MouseEventKind::ScrollUp => self.scroll_debug_up(WHEEL_STEP),
+                MouseEventKind::ScrollDown => self.scroll_debug_down(WHEEL_STEP),
                 _ => {}
             }
+            return Ok(());
+        }
+        let over_file_list = contains(self.pane_areas.file_list, mouse.column, mouse.row);
+        let over_details = contains(self.pane_areas.details, mouse.column, mouse.row);
+        let file_list = self.pane_areas.file_list;
+        let on_divider = over_file_list && mouse.column + 1 == file_list.x + file_list.width;
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) if on_divider => self.resizing = true,
+            MouseEventKind::Down(MouseButton::Left) if over_file_list => {
+                self.set_focused_pane(FocusedPane::FileList);
+                self.click_list_row(mouse.row, repo)?;
+            }
+            MouseEventKind::Down(MouseButton::Left) if over_details => {
+                self.set_focused_pane(FocusedPane::Details)
+            }
+            MouseEventKind::ScrollUp if over_details => self.scroll_details_up(WHEEL_STEP),
+            MouseEventKind::ScrollDown if over_details => self.scroll_details_down(WHEEL_STEP),
+            MouseEventKind::ScrollUp if over_file_list => {
+                if self.history.is_visible {
+                    self.move_history_selection_up(WHEEL_STEP)
+                } else {
+                    self.move_selection_up(WHEEL_STEP)
+                }
+            }
+            MouseEventKind::ScrollDown if over_file_list => {
+                if self.history.is_visible {
+                    self.move_history_selection_down(WHEEL_STEP, repo)
+                } else {
+                    self.move_selection_down(WHEEL_STEP)
+                }
+            }
+            _ => {}
         }
         Ok(())
     }
 
-    fn scroll_details_up(&mut self, step: usize) {
-        if self.details_scroll > 0 {
-            // Check that this won't overflow
-            if self.details_scroll >= step {
-                self.details_scroll -= step;
-            } else {
-                self.details_scroll = 0;
-            }
+    /// Makes the file list as wide as the divider dragged to `column`.
+    fn drag_divider(&mut self, column: u16) {
+        let areas = &self.pane_areas;
+        let total =
+            areas.file_list.width + areas.details.width + areas.debug.map_or(0, |area| area.width);
+        if total == 0 {
+            return;
         }
+        let width = column.saturating_sub(areas.file_list.x) + 1;
+        self.set_file_list_width((u32::from(width) * 100 / u32::from(total)) as u16);
     }
 
-    fn scroll_details_down(&mut self, step: usize) {
-        // Check that this won't overflow
-        if self.details_scroll + step < self.right_pane_content.lines().count() {
-            self.details_scroll += step;
+    /// Sets the file list width, keeping every pane at least `MIN_PANE_WIDTH` wide.
+    fn set_file_list_width(&mut self, width: u16) {
+        self.file_list_width = width.clamp(MIN_PANE_WIDTH, self.max_file_list_width());
+    }
+
+    fn max_file_list_width(&self) -> u16 {
+        let debug = if self.debug_mode {
+            self.debug_pane_width
         } else {
-            self.details_scroll = self.right_pane_content.lines().count() - 1;
-        }
+            0
+        };
+        100 - MIN_PANE_WIDTH - debug
     }
 
-    fn move_selection_up(&mut self, step: usize) {
-        if !self.files.is_empty() && self.selected_index > 0 {
-            // Check that this won't overflow
-            if self.selected_index >= step {
-                self.selected_index -= step;
+    fn log_file_list_width(&mut self) {
+        let message = format!("File list width: {}%", self.file_list_width);
+        self.debug_log(&message);
+    }
+
+    /// Entries visible at once in the file list or history, for paging by a screenful.
+    fn list_page_size(&self) -> usize {
+        usize::from(self.pane_areas.file_list.height.saturating_sub(2)).max(1)
+    }
+
+    fn click_list_row(&mut self, row: u16, repo: &Repository) -> AppResult<()> {
+        // Rows start below the block's top border.
+        let Some(row) = row
+            .checked_sub(self.pane_areas.file_list.y + 1)
+            .filter(|&row| row + 2 < self.pane_areas.file_list.height)
+        else {
+            return Ok(());
+        };
+        let offset = if self.history.is_visible {
+            self.history.list_state.offset()
+        } else {
+            self.file_list_state.offset()
+        };
+        let index = offset + usize::from(row);
+        let double_click = self.last_click.is_some_and(|(time, clicked)| {
+            clicked == index && time.elapsed() <= DOUBLE_CLICK_INTERVAL
+        });
+        self.last_click = Some((Instant::now(), index));
+
+        if self.history.is_visible {
+            if index < self.history.commits.len() {
+                self.history.selected = index;
+                self.show_selected_commit(repo);
+            }
+        } else if index < self.files.len() {
+            self.selected_index = index;
+            if double_click && self.files[index].is_dir {
+                self.select_entry(repo)?;
             } else {
-                self.selected_index = 0;
+                self.request_details(repo);
             }
         }
+        Ok(())
     }
 
-    fn move_selection_down(&mut self, step: usize) {
-        if !self.files.is_empty() && self.selected_index < self.files.len() - 1 {
-            // Check that this won't overflow
-            if self.selected_index + step < self.files.len() {
-                self.selected_index += step;
-            } else {
-                self.selected_index = self.files.len() - 1;
+    pub fn handle_key_event(&mut self, key: KeyEvent, repo: &Repository) -> AppResult<()> {
+        if self.confirmation.is_some() {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Enter => self.perform_pending_action(repo)?,
+                KeyCode::Char('n') | KeyCode::Esc => self.confirmation = None,
+                _ => {}
+            }
+        } else if let Some(menu) = &self.reset_menu {
+            let target = menu.target;
+            match key.code {
+                KeyCode::Char('s') => self.reset_to(repo, target, ResetType::Soft)?,
+                KeyCode::Char('m') | KeyCode::Enter => {
+                    self.reset_to(repo, target, ResetType::Mixed)?
+                }
+                KeyCode::Char('h') => self.confirm_hard_reset(),
+                KeyCode::Esc => self.reset_menu = None,
+                _ => {}
+            }
+        } else if self.commit_modal.is_visible {
+            match key.code {
+                KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.toggle_amend_mode(repo)?
+                }
+                KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.perform_commit(repo, true)?
+                }
+                KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.perform_commit(repo, false)?
+                }
+                KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.cycle_commit_prefix()
+                }
+                KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.preview_commit(repo)
+                }
+                KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.commit_modal.is_visible = false;
+                    self.editor_requested = true;
+                }
+                KeyCode::Enter => self.commit_modal.insert('\n'),
+                KeyCode::Esc => self.close_modals(),
+                _ => self.commit_modal.handle_text_input(key),
+            }
+        } else if self.reword_modal.is_visible {
+            match key.code {
+                KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.save_reword()
+                }
+                KeyCode::Enter => self.reword_modal.insert('\n'),
+                KeyCode::Esc => self.reword_modal.is_visible = false,
+                _ => self.reword_modal.handle_text_input(key),
+            }
+        } else if self.rebase_todo.is_visible {
+            let editable = self.rebase.is_none();
+            match (key.code, self.keymap.action_for(&key)) {
+                (KeyCode::Char('K'), _) if editable => self.move_todo_item(false),
+                (KeyCode::Char('J'), _) if editable => self.move_todo_item(true),
+                (KeyCode::Char('p'), _) if editable => self.set_todo_action(TodoAction::Pick),
+                (KeyCode::Char('r'), _) if editable => self.open_reword(),
+                (KeyCode::Char('s'), _) if editable => self.set_todo_action(TodoAction::Squash),
+                (KeyCode::Char('a'), _) if !editable => self.confirm_abort_rebase(),
+                (_, Some(Action::Up)) => {
+                    self.rebase_todo.selected = self.rebase_todo.selected.saturating_sub(1)
+                }
+                (_, Some(Action::Down))
+                    if self.rebase_todo.selected + 1 < self.rebase_todo.items.len() =>
+                {
+                    self.rebase_todo.selected += 1
+                }
+                (_, Some(Action::Select)) => self.run_rebase(repo)?,
+                (_, Some(Action::Close)) | (_, Some(Action::Rebase)) => self.close_modals(),
+                _ => {}
+            }
+        } else if let Some(input) = &mut self.input {
+            match key.code {
+                KeyCode::Enter => self.submit_input(repo),
+                KeyCode::Tab if input.action == InputAction::CreateBranch => {
+                    self.checkout_new_branch = !self.checkout_new_branch;
+                    input.title = input.action.title(self.checkout_new_branch).to_string();
+                }
+                KeyCode::Esc => self.close_modals(),
+                _ => input.field.handle_text_input(key),
+            }
+        } else if self.search.prompt.is_visible {
+            match key.code {
+                KeyCode::Enter => self.search.prompt.is_visible = false,
+                KeyCode::Esc => self.cancel_search(),
+                _ => {
+                    self.search.prompt.handle_text_input(key);
+                    self.jump_to_match(self.search.origin, true);
+                }
+            }
+        } else if self.branch_list.is_visible {
+            match (key.code, self.keymap.action_for(&key)) {
+                (_, Some(Action::Up)) => {
+                    self.branch_list.selected = self.branch_list.selected.saturating_sub(1);
+                    self.load_branch_counts(repo);
+                }
+                (_, Some(Action::Down))
+                    if self.branch_list.selected + 1 < self.branch_list.branches.len() =>
+                {
+                    self.branch_list.selected += 1;
+                    self.load_branch_counts(repo);
+                }
+                (_, Some(Action::Select)) => self.checkout_selected_branch(repo),
+                (KeyCode::Char('m'), _) => self.merge_selected_branch(repo)?,
+                (KeyCode::Char('x'), _) | (KeyCode::Delete, _) => {
+                    self.delete_selected_branch(repo, false)
+                }
+                (KeyCode::Char('D'), _) => self.delete_selected_branch(repo, true),
+                (KeyCode::Char('r'), _) => self.rename_selected_branch(),
+                (_, Some(Action::Close)) | (_, Some(Action::Branches)) => self.close_modals(),
+                _ => {}
+            }
+        } else if self.stash_list.is_visible {
+            match (key.code, self.keymap.action_for(&key)) {
+                (_, Some(Action::Up)) => {
+                    self.stash_list.selected = self.stash_list.selected.saturating_sub(1)
+                }
+                (_, Some(Action::Down))
+                    if self.stash_list.selected + 1 < self.stash_list.stashes.len() =>
+                {
+                    self.stash_list.selected += 1
+                }
+                (_, Some(Action::Select)) => self.apply_selected_stash(repo, true),
+                (KeyCode::Char('a'), _) => self.apply_selected_stash(repo, false),
+                (KeyCode::Char('x'), _) | (KeyCode::Delete, _) => self.drop_selected_stash(repo),
+                (_, Some(Action::Close)) | (_, Some(Action::StashList)) => self.close_modals(),
+                _ => {}
+            }
+        } else if self.reflog.is_visible {
+            match self.keymap.action_for(&key) {
+                Some(Action::Up) => self.reflog.selected = self.reflog.selected.saturating_sub(1),
+                Some(Action::Down) if self.reflog.selected + 1 < self.reflog.entries.len() => {
+                    self.reflog.selected += 1
+                }
+                Some(Action::PageUp) => {
+                    self.reflog.selected =
+                        self.reflog.selected.saturating_sub(self.list_page_size())
+                }
+                Some(Action::PageDown) => {
+                    self.reflog.selected = (self.reflog.selected + self.list_page_size())
+                        .min(self.reflog.entries.len().saturating_sub(1))
+                }
+                Some(Action::Select) => self.reset_to_reflog_entry(),
+                Some(Action::Close) | Some(Action::Reflog) => self.close_modals(),
+                _ => {}
+            }
+        } else if self.submodule_list.is_visible {
+            match self.keymap.action_for(&key) {
+                Some(Action::Up) => {
+                    self.submodule_list.selected = self.submodule_list.selected.saturating_sub(1)
+                }
+                Some(Action::Down)
+                    if self.submodule_list.selected + 1 < self.submodule_list.submodules.len() =>
+                {
+                    self.submodule_list.selected += 1
+                }
+                Some(Action::Select) => {
+                    if let Some(submodule) = self
+                        .submodule_list
+                        .submodules
+                        .get(self.submodule_list.selected)
+                    {
+                        self.worker
+                            .run(GitTask::UpdateSubmodule(submodule.name.clone()));
+                    }
+                }
+                Some(Action::Close) | Some(Action::Submodules) => self.close_modals(),
+                _ => {}
+            }
+        } else if self.help_modal.is_visible {
+            match self.keymap.action_for(&key) {
+                Some(Action::Up) => self.help_scroll = self.help_scroll.saturating_sub(1),
+                Some(Action::Down) => {
+                    self.help_scroll = (self.help_scroll + 1).min(self.help_max_scroll)
+                }
+                Some(Action::PageUp) => self.help_scroll = self.help_scroll.saturating_sub(10),
+                Some(Action::PageDown) => {
+                    self.help_scroll = (self.help_scroll + 10).min(self.help_max_scroll)
+                }
+                Some(Action::Help) | Some(Action::Close) => self.close_modals(),
+                _ => {}
+            }
+        } else if self.history.is_visible && self.handle_history_key(key, repo)? {
+            // Handled by the history view.
+        } else if let Some(action) = self.keymap.action_for(&key) {
+            match (self.focused_pane, action) {
+                (FocusedPane::FileList, Action::Up) => self.move_selection_up(1),
+                (FocusedPane::FileList, Action::Down) => self.move_selection_down(1),
+                (FocusedPane::FileList, Action::PageUp) => {
+                    self.move_selection_up(self.list_page_size())
+                }
+                (FocusedPane::FileList, Action::PageDown) => {
+                    self.move_selection_down(self.list_page_size())
+                }
+                (FocusedPane::FileList, Action::NextChange) if !self.history.is_visible => {
+                    self.jump_to_change(repo, true)?
+                }
+                (FocusedPane::FileList, Action::PreviousChange) if !self.history.is_visible => {
+                    self.jump_to_change(repo, false)?
+                }
+                (FocusedPane::FileList, Action::Search) if !self.history.is_visible => {
+                    self.open_search()
+                }
+                (FocusedPane::FileList, Action::NextMatch) if !self.history.is_visible => {
+                    self.jump_to_match(self.selected_index + 1, true)
+                }
+                (FocusedPane::FileList, Action::PreviousMatch) if !self.history.is_visible => self
+                    .jump_to_match(
+                        (self.selected_index + self.files.len()).saturating_sub(1),
+                        false,
+                    ),
+                (FocusedPane::FileList, Action::Close) if !self.search.query().is_empty() => {
+                    self.search.prompt.clear()
+                }
+                (FocusedPane::Details, Action::Up) if self.selected_lines().is_some() => {
+                    self.move_line_cursor(false)
+                }
+                (FocusedPane::Details, Action::Down) if self.selected_lines().is_some() => {
+                    self.move_line_cursor(true)
+                }
+                (FocusedPane::Details, Action::ToggleHunk) if self.selected_lines().is_some() => {
+                    self.toggle_selected_lines(repo)?
+                }
+                (FocusedPane::Details, Action::SelectLines) => self.toggle_line_selection(),
+                (FocusedPane::Details, Action::Close) if self.selected_lines().is_some() => {
+                    self.line_selection = None
+                }
+                (FocusedPane::Debug, Action::Up) => self.scroll_debug_up(1),
+                (FocusedPane::Debug, Action::PageUp) => self.scroll_debug_up(10),
+                (FocusedPane::Debug, Action::Down) => self.scroll_debug_down(1),
+                (FocusedPane::Debug, Action::PageDown) => self.scroll_debug_down(10),
+                (FocusedPane::Details, Action::Up) => self.scroll_details_up(1),
+                (FocusedPane::Details, Action::PageUp) => self.scroll_details_up(10),
+                (FocusedPane::Details, Action::Down) => self.scroll_details_down(1),
+                (FocusedPane::Details, Action::PageDown) => self.scroll_details_down(10),
+                (FocusedPane::Details, Action::NextHunk) => self.select_next_hunk(),
+                (FocusedPane::Details, Action::PreviousHunk) => self.select_previous_hunk(),
+                (FocusedPane::Details, Action::ToggleHunk) => self.toggle_selected_hunk(repo)?,
+                (FocusedPane::Details, Action::FocusFiles) if self.details_hscroll > 0 => {
+                    self.details_hscroll = self.details_hscroll.saturating_sub(HSCROLL_STEP)
+                }
+                (FocusedPane::Details, Action::FocusDetails) => {
+                    self.details_hscroll =
+                        (self.details_hscroll + HSCROLL_STEP).min(self.details_max_hscroll)
+                }
+                (_, Action::FocusFiles) => self.set_focused_pane(FocusedPane::FileList),
+                (_, Action::FocusDetails) => self.set_focused_pane(FocusedPane::Details),
+                (_, Action::CycleFocus) => self.cycle_focus(),
+                (_, Action::NarrowFileList) => {
+                    self.set_file_list_width(self.file_list_width.saturating_sub(PANE_WIDTH_STEP));
+                    self.log_file_list_width();
+                }
+                (_, Action::WidenFileList) => {
+                    self.set_file_list_width(self.file_list_width + PANE_WIDTH_STEP);
+                    self.log_file_list_width();
+                }
+                (_, Action::Select) => self.select_entry(repo)?,
+                (_, Action::Commit) => self.start_commit(repo),
+                (_, Action::Stage) => self.stage_selected(repo)?,
+                (_, Action::Unstage) => self.unstage_selected(repo)?,
+                (_, Action::UnstageAll) => self.unstage_everything(repo)?,
+                (_, Action::Push) => self.worker.run(GitTask::Push),
+                (_, Action::Fetch) => self.worker.run(GitTask::Fetch),
+                (_, Action::Pull) => self.worker.run(GitTask::Pull),
+                (_, Action::Branches) => self.open_branch_list(repo),
+                (_, Action::Stash) => self.stash_changes(repo),
+                (_, Action::StashList) => self.open_stash_list(repo),
+                (_, Action::Submodules) => self.open_submodule_list(repo),
+                (_, Action::Reflog) => self.open_reflog(repo),
+                (_, Action::History) => self.open_history(repo),
+                (_, Action::NewBranch) => self.open_input(InputAction::CreateBranch),
+                (_, Action::CherryPick) => self.open_input(InputAction::CherryPick),
+                (_, Action::ShowCommit) => self.open_input(InputAction::ShowCommit),
+                (_, Action::NewTag) => self.open_input(InputAction::CreateTag(None)),
+                (_, Action::Rebase) if self.rebase.is_some() => self.open_stopped_rebase(),
+                (_, Action::BisectGood) => self.bisect_mark(repo, true)?,
+                (_, Action::BisectBad) => self.bisect_mark(repo, false)?,
+                (_, Action::BisectReset) => self.bisect_reset(repo)?,
+                (FocusedPane::FileList, Action::TakeOurs) if !self.history.is_visible => {
+                    self.take_conflict_side(repo, true)?
+                }
+                (FocusedPane::FileList, Action::TakeTheirs) if !self.history.is_visible => {
+                    self.take_conflict_side(repo, false)?
+                }
+                (FocusedPane::FileList, Action::Blame) if !self.history.is_visible => {
+                    self.show_blame(repo)
+                }
+                (_, Action::CycleSort) if !self.history.is_visible => self.cycle_sort(repo),
+                (_, Action::ToggleFileInfo) if !self.history.is_visible => {
+                    self.list_options.show_file_info = !self.list_options.show_file_info;
+                    self.refresh_file_list(repo);
+                }
+                (_, Action::ToggleIgnored) if !self.history.is_visible => self.toggle_ignored(repo),
+                (_, Action::CycleFilter) if !self.history.is_visible => {
+                    self.cycle_status_filter(repo)
+                }
+                (_, Action::MoreContext) => {
+                    self.set_context_lines(repo, self.diff_settings.context_lines + 1)?
+                }
+                (_, Action::LessContext) => self
+                    .set_context_lines(repo, self.diff_settings.context_lines.saturating_sub(1))?,
+                (_, Action::CycleWhitespace) => self.cycle_whitespace(repo)?,
+                (_, Action::ToggleSplitView) => self.toggle_split_view(),
+                (_, Action::ToggleWrap) => {
+                    self.wrap_lines = !self.wrap_lines;
+                    self.details_hscroll = 0;
+                }
+                (_, Action::FullDiff) if !self.history.is_visible => {
+                    if let Some(file) = self.files.get(self.selected_index) {
+                        self.full_diff = Some(file.name.clone());
+                        self.request_details(repo);
+                    }
+                }
+                (_, Action::CopyDetails) => self.copy_details(),
+                (_, Action::CopyPath) => self.copy_selected_path(),
+                (_, Action::Refresh) => self.refresh(repo)?,
+                (_, Action::Help) => self.toggle_help(),
+                (_, Action::ToggleDebug) => self.toggle_debug_mode(),
+                (_, Action::Close) => self.close_modals(),
+                _ => {}
             }
         }
+        Ok(())
     }
 
-    fn show_details(&mut self, repo: &Repository) -> AppResult<()> {
-        if !self.files.is_empty() {
-            if self.selected_index >= self.files.len() {
-                self.selected_index = self.files.len() - 1;
+    /// Keys specific to the history view. Returns `false` for keys it leaves to the
+    /// regular bindings.
+    fn handle_history_key(&mut self, key: KeyEvent, repo: &Repository) -> AppResult<bool> {
+        let Some(action) = self.keymap.action_for(&key) else {
+            return Ok(false);
+        };
+        match (self.focused_pane, action) {
+            (FocusedPane::FileList, Action::Up) => self.move_history_selection_up(1),
+            (FocusedPane::FileList, Action::Down) => self.move_history_selection_down(1, repo),
+            (FocusedPane::FileList, Action::PageUp) => {
+                self.move_history_selection_up(self.list_page_size())
             }
-            let selected_file = &self.files[self.selected_index];
-            if !selected_file.is_dir {
-                update_right_pane(repo, self)?;
+            (FocusedPane::FileList, Action::PageDown) => {
+                self.move_history_selection_down(self.list_page_size(), repo)
+            }
+            (_, Action::Select) => self.show_selected_commit(repo),
+            (_, Action::Revert) => self.confirm_revert(),
+            (_, Action::Rebase) if self.rebase.is_none() => self.open_rebase_todo(repo),
+            (_, Action::Reset) => {
+                if let Some(commit) = self.history.commits.get(self.history.selected) {
+                    self.reset_menu = Some(ResetMenu {
+                        target: commit.id,
+                        label: format!("{} \"{}\"", commit.short_id, commit.summary),
+                    });
+                }
+            }
+            (_, Action::NewTag) => {
+                let target = self.history.commits.get(self.history.selected);
+                self.open_input(InputAction::CreateTag(target.map(|commit| commit.id)))
             }
+            (_, Action::ToggleDates) => self.history.absolute_dates = !self.history.absolute_dates,
+            (_, Action::BisectGood) => self.bisect_mark(repo, true)?,
+            (_, Action::BisectBad) => self.bisect_mark(repo, false)?,
+            (_, Action::History) | (_, Action::Close) => self.close_history(),
+            _ => return Ok(false),
         }
-        Ok(())
+        Ok(true)
     }
 
-    fn start_commit(&mut self, repo: &Repository) -> AppResult<()> {
-        stage_all_modified(repo)?;
-        self.commit_modal.is_visible = true;
-        Ok(())
+    fn open_history(&mut self, repo: &Repository) {
+        self.history.commits = match load_commits(repo, 0, HISTORY_PAGE_SIZE) {
+            Ok(commits) => commits,
+            Err(e) => {
+                self.debug_log(&format!("Failed to load the history: {}", e.message()));
+                return;
+            }
+        };
+        self.history.exhausted = self.history.commits.len() < HISTORY_PAGE_SIZE;
+        self.history.selected = 0;
+        self.history.list_state = ListState::default();
+        // The history is still worth showing without its tags.
+        self.history.tags = tags_by_commit(repo).unwrap_or_else(|e| {
+            self.debug_log(&format!("Failed to read the tags: {}", e.message()));
+            HashMap::new()
+        });
+        self.history.is_visible = true;
+        self.right_pane_lines.clear();
+        self.diff_hunks.clear();
+        self.details_scroll = 0;
+        self.details_hscroll = 0;
     }
 
-    fn toggle_help(&mut self) {
-        self.help_modal.is_visible = !self.help_modal.is_visible;
+    fn close_history(&mut self) {
+        self.history.is_visible = false;
+        self.history.commits.clear();
+        self.right_pane_lines.clear();
+        self.details_scroll = 0;
+        self.details_hscroll = 0;
     }
 
-    fn close_modals(&mut self) {
-        self.commit_modal.is_visible = false;
-        self.help_modal.is_visible = false;
+    fn move_history_selection_up(&mut self, step: usize) {
+        self.history.selected = self.history.selected.saturating_sub(step);
     }
 
-    fn perform_commit(&mut self, repo: &Repository) -> AppResult<()> {
-        create_commit(repo, &self.commit_modal.content)?;
-        self.commit_modal.is_visible = false;
-        self.commit_modal.content.clear();
-        self.files = get_file_list(repo);
-        self.expanded_dirs.clear();
-        self.right_pane_content.clear();
-        Ok(())
+    fn move_history_selection_down(&mut self, step: usize, repo: &Repository) {
+        let target = self.history.selected + step;
+        // Load the next page before the selection reaches the end of what is loaded.
+        if target + 10 >= self.history.commits.len() && !self.history.exhausted {
+            match load_commits(repo, self.history.commits.len(), HISTORY_PAGE_SIZE) {
+                Ok(page) => {
+                    self.history.exhausted = page.len() < HISTORY_PAGE_SIZE;
+                    self.history.commits.extend(page);
+                }
+                Err(e) => self.debug_log(&format!("Failed to load more history: {}", e.message())),
+            }
+        }
+        self.history.selected = target.min(self.history.commits.len().saturating_sub(1));
     }
 
-    pub fn debug_log(&mut self, message: &str) {
-        self.debug_content.push_str(message);
-        self.debug_content.push('\n');
+    fn show_selected_commit(&mut self, repo: &Repository) {
+        let Some(commit) = self.history.commits.get(self.history.selected) else {
+            return;
+        };
+        match show_commit(repo, commit.id) {
+            Ok(lines) => {
+                self.right_pane_lines = lines;
+                self.diff_hunks.clear();
+                self.details_scroll = 0;
+                self.details_hscroll = 0;
+            }
+            Err(e) => {
+                let message = format!("Failed to show {}: {}", commit.short_id, e.message());
+                self.debug_log(&message);
+            }
+        }
     }
 
-    pub fn refresh_file_list(&mut self, repo: &Repository) {
-        self.files = get_file_list(repo);
+    fn confirm_revert(&mut self) {
+        if let Some(commit) = self.history.commits.get(self.history.selected) {
+            self.confirmation = Some(Confirmation {
+                prompt: format!(
+                    "Revert {} \"{}\" with a new commit?",
+                    commit.short_id, commit.summary
+                ),
+                action: PendingAction::Revert(commit.id),
+            });
+        }
     }
 
-    fn toggle_debug_mode(&mut self) {
-        self.debug_mode = !self.debug_mode;
+    fn perform_pending_action(&mut self, repo: &Repository) -> AppResult<()> {
+        let Some(confirmation) = self.confirmation.take() else {
+            return Ok(());
+        };
+        match confirmation.action {
+            PendingAction::Revert(oid) => self.revert(repo, oid)?,
+            PendingAction::AbortRebase => self.abort_rebase(repo),
+            PendingAction::HardReset(oid) => self.reset_to(repo, oid, ResetType::Hard)?,
+            PendingAction::DeleteBranch(name) => self.delete_branch(repo, &name),
+            PendingAction::Quit => self.should_quit = true,
+        }
+        Ok(())
     }
 
-    fn set_focused_pane(&mut self, pane: FocusedPane) {
+    fn revert(&mut self, repo: &Repository, oid: Oid) -> AppResult<()> {
+        let short_id = oid.to_string()[..7].to_string();
+        match revert_commit(repo, oid, self.config.require_signing) {
+            Ok(conflicts) if conflicts.is_empty() => {
+                self.debug_log(&format!("Reverted {}", short_id));
+                // Show the new revert commit at the top.
+                self.open_history(repo);
+            }
+            Ok(conflicts) => {
+                self.debug_log(&format!(
+                    "Reverting {} conflicts in {}; resolve, stage and commit them",
+                    short_id,
+                    conflicts.join(", ")
+                ));
+                // Have git's revert message ready for when the conflicts are committed,
+                // without the "#Conflicts:" comment that git would strip.
+                if let Ok(message) = repo.message() {
+                    self.commit_modal.set_content(strip_comments(&message));
+                }
+            }
+            Err(e) => self.debug_log(&format!("Failed to revert {}: {}", short_id, e.message())),
+        }
+        self.refresh_file_list(repo);
+        Ok(())
+    }
+
+    fn scroll_details_up(&mut self, step: usize) {
+        if self.details_scroll > 0 {
+            // Check that this won't overflow
+            if self.details_scroll >= step {
+                self.details_scroll -= step;
+            } else {
+                self.details_scroll = 0;
+            }
+        }
+    }
+
+    fn scroll_details_down(&mut self, step: usize) {
+        self.details_scroll = (self.details_scroll + step).min(self.details_max_scroll);
+    }
+
+    /// Scrolling up stops the debug pane from following new messages.
+    fn scroll_debug_up(&mut self, step: usize) {
+        let scroll = self.debug_scroll.unwrap_or(self.debug_max_scroll);
+        self.debug_scroll = Some(scroll.saturating_sub(step));
+    }
+
+    /// Scrolling back down to the end follows new messages again.
+    fn scroll_debug_down(&mut self, step: usize) {
+        self.debug_scroll = self
+            .debug_scroll
+            .map(|scroll| scroll + step)
+            .filter(|&scroll| scroll < self.debug_max_scroll);
+    }
+
+    fn select_next_hunk(&mut self) {
+        self.line_selection = None;
+        if self.selected_hunk + 1 < self.diff_hunks.len() {
+            self.selected_hunk += 1;
+        }
+        self.scroll_to_selected_hunk();
+    }
+
+    fn select_previous_hunk(&mut self) {
+        self.line_selection = None;
+        self.selected_hunk = self.selected_hunk.saturating_sub(1);
+        self.scroll_to_selected_hunk();
+    }
+
+    fn scroll_to_selected_hunk(&mut self) {
+        if let Some(hunk) = self.diff_hunks.get(self.selected_hunk) {
+            self.details_scroll = self.row_of_line(hunk.start_line);
+        }
+    }
+
+    /// The Details pane row showing `right_pane_lines[index]`. Paired lines share a row in
+    /// the side-by-side view, so rows there come sooner.
+    fn row_of_line(&self, index: usize) -> usize {
+        if !self.details_split_shown {
+            return index;
+        }
+        split_rows(&self.right_pane_lines)
+            .iter()
+            .position(|row| match *row {
+                SplitRow::Full(line) => line >= index,
+                SplitRow::Pair(old, new) => old.max(new).is_some_and(|line| line >= index),
+            })
+            .unwrap_or(0)
+    }
+
+    /// Starts picking lines of the selected hunk at its first added or removed line, or
+    /// drops the picked lines.
+    fn toggle_line_selection(&mut self) {
+        if self.selected_lines().is_some() {
+            self.line_selection = None;
+            return;
+        }
+        let Some(hunk) = self.diff_hunks.get(self.selected_hunk) else {
+            return;
+        };
+        let first_change = (hunk.start_line + 1..hunk.start_line + hunk.line_count).find(|&i| {
+            matches!(
+                self.right_pane_lines[i].kind,
+                LineKind::Addition | LineKind::Deletion
+            )
+        });
+        if let Some(index) = first_change {
+            let start_line = hunk.start_line;
+            self.line_selection = Some(LineSelection {
+                hunk: hunk.clone(),
+                anchor: index,
+                cursor: index,
+            });
+            self.details_scroll = self.row_of_line(start_line);
+        }
+    }
+
+    /// The picked lines, as long as they belong to the hunk selected in the Details pane.
+    pub fn selected_lines(&self) -> Option<Range<usize>> {
+        let selection = self.line_selection.as_ref()?;
+        if self.diff_hunks.get(self.selected_hunk) != Some(&selection.hunk) {
+            return None;
+        }
+        let (anchor, cursor) = (selection.anchor, selection.cursor);
+        Some(anchor.min(cursor)..anchor.max(cursor) + 1)
+    }
+
+    /// Moves the loose end of the line selection, staying inside the selected hunk.
+    fn move_line_cursor(&mut self, down: bool) {
+        let Some(selection) = self.line_selection.as_mut() else {
+            return;
+        };
+        let hunk = &selection.hunk;
+        let cursor = if down {
+            (selection.cursor + 1).min(hunk.start_line + hunk.line_count - 1)
+        } else {
+            selection.cursor.saturating_sub(1).max(hunk.start_line + 1)
+        };
+        selection.cursor = cursor;
+
+        let row = self.row_of_line(cursor);
+        let height = usize::from(self.pane_areas.details.height.saturating_sub(2)).max(1);
+        if row < self.details_scroll {
+            self.details_scroll = row;
+        } else if row >= self.details_scroll + height {
+            self.details_scroll = row + 1 - height;
+        }
+    }
+
+    fn toggle_selected_lines(&mut self, repo: &Repository) -> AppResult<()> {
+        let (Some(selected), Some(hunk)) = (
+            self.selected_lines(),
+            self.diff_hunks.get(self.selected_hunk).cloned(),
+        ) else {
+            return Ok(());
+        };
+        if !self.can_stage_hunks() {
+            return Ok(());
+        }
+        let name = self.files[self.selected_index].name.clone();
+        match apply_hunk_lines(repo, &name, &hunk, &self.right_pane_lines, selected) {
+            Ok(()) => {
+                let action = if hunk.staged { "Unstaged" } else { "Staged" };
+                self.debug_log(&format!("{} selected lines of {}", action, name))
+            }
+            Err(e) => self.debug_log(&format!("Failed to apply lines: {}", e.message())),
+        }
+        self.refresh_file_list(repo);
+        self.show_details(repo)?;
+        self.scroll_to_selected_hunk();
+        Ok(())
+    }
+
+    /// Rows mean different things in the two layouts, so the view starts again at the top.
+    fn toggle_split_view(&mut self) {
+        self.side_by_side = !self.side_by_side;
+        self.details_scroll = 0;
+    }
+
+    fn toggle_selected_hunk(&mut self, repo: &Repository) -> AppResult<()> {
+        let Some(hunk) = self.diff_hunks.get(self.selected_hunk).cloned() else {
+            return Ok(());
+        };
+        if !self.can_stage_hunks() {
+            return Ok(());
+        }
+        let name = self.files[self.selected_index].name.clone();
+        let result = if hunk.staged {
+            unstage_hunk(repo, &name, &hunk, &self.diff_settings)
+        } else {
+            stage_hunk(repo, &name, &hunk, &self.diff_settings)
+        };
+        match result {
+            Ok(()) => {
+                let action = if hunk.staged { "Unstaged" } else { "Staged" };
+                self.debug_log(&format!("{} hunk {} in {}", action, hunk.header, name))
+            }
+            Err(e) => self.debug_log(&format!("Failed to apply hunk: {}", e.message())),
+        }
+        self.refresh_file_list(repo);
+        self.show_details(repo)?;
+        self.scroll_to_selected_hunk();
+        Ok(())
+    }
+
+    fn move_selection_up(&mut self, step: usize) {
+        if !self.files.is_empty() && self.selected_index > 0 {
+            // Check that this won't overflow
+            if self.selected_index >= step {
+                self.selected_index -= step;
+            } else {
+                self.selected_index = 0;
+            }
+        }
+    }
+
+    fn move_selection_down(&mut self, step: usize) {
+        if !self.files.is_empty() && self.selected_index < self.files.len() - 1 {
+            // Check that this won't overflow
+            if self.selected_index + step < self.files.len() {
+                self.selected_index += step;
+            } else {
+                self.selected_index = self.files.len() - 1;
+            }
+        }
+    }
+
+    fn open_search(&mut self) {
+        self.search.origin = self.selected_index;
+        self.search.prompt.clear();
+        self.search.prompt.is_visible = true;
+    }
+
+    fn cancel_search(&mut self) {
+        self.selected_index = self.search.origin;
+        self.search.prompt.clear();
+        self.search.prompt.is_visible = false;
+    }
+
+    /// Selects the first file matching the search, starting at `start` and wrapping around
+    /// the list in the given direction. The selection is left alone if nothing matches.
+    fn jump_to_match(&mut self, start: usize, forward: bool) {
+        let len = self.files.len();
+        if len == 0 {
+            return;
+        }
+        let found = (0..len)
+            .map(|offset| {
+                if forward {
+                    (start + offset) % len
+                } else {
+                    (start + len - offset) % len
+                }
+            })
+            .find(|&index| self.search.matches(&self.files[index].name));
+        if let Some(index) = found {
+            self.selected_index = index;
+        }
+    }
+
+    /// Selects the next or previous file with changes, wrapping around the list, and shows
+    /// its diff. Directories, clean files and ignored ones are skipped.
+    fn jump_to_change(&mut self, repo: &Repository, forward: bool) -> AppResult<()> {
+        let len = self.files.len();
+        let found = (1..len)
+            .map(|offset| {
+                if forward {
+                    (self.selected_index + offset) % len
+                } else {
+                    (self.selected_index + len - offset) % len
+                }
+            })
+            .find(|&index| {
+                let file = &self.files[index];
+                !file.is_dir && !file.status.is_empty() && file.status != Status::IGNORED
+            });
+        match found {
+            Some(index) => {
+                self.selected_index = index;
+                self.request_details(repo);
+                Ok(())
+            }
+            None => {
+                self.debug_log("No other changed files");
+                Ok(())
+            }
+        }
+    }
+
+    /// Enter on a directory expands or collapses it and shows a summary of its changes; on
+    /// a file it shows the diff.
+    fn select_entry(&mut self, repo: &Repository) -> AppResult<()> {
+        if let Some(entry) = self
+            .files
+            .get(self.selected_index)
+            .filter(|entry| entry.is_dir)
+        {
+            let name = entry.name.clone();
+            self.toggle_directory(repo, name);
+        }
+        self.request_details(repo);
+        Ok(())
+    }
+
+    /// The children of a directory are listed right after it, so the directory keeps its
+    /// index and the selection doesn't move.
+    fn toggle_directory(&mut self, repo: &Repository, name: String) {
+        let expanded = is_expanded(&self.expanded_dirs, &name);
+        self.expanded_dirs.insert(name, !expanded);
+        self.refresh_file_list(repo);
+    }
+
+    /// Shows the selected file in the Details pane straight away, as is needed after
+    /// changing the repository, when the hunks on show have to match the file.
+    fn show_details(&mut self, repo: &Repository) -> AppResult<()> {
+        if !self.files.is_empty() {
+            if self.selected_index >= self.files.len() {
+                self.selected_index = self.files.len() - 1;
+            }
+            // Whatever the worker is reading was asked for before this.
+            self.pending_diff = None;
+            let key = self.diff_cache_key(repo);
+            if !self.show_cached_details(key.as_ref()) {
+                update_right_pane(repo, self)?;
+                if let Some(key) = key {
+                    self.diff_cache.insert(
+                        key,
+                        (self.right_pane_lines.clone(), self.diff_hunks.clone()),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `show_details`, but a diff that isn't cached is read by the worker, so that a
+    /// big one doesn't hold up the UI. Until then the Details pane keeps what it showed,
+    /// without hunks to stage.
+    fn request_details(&mut self, repo: &Repository) {
+        if self.files.is_empty() {
+            return;
+        }
+        if self.selected_index >= self.files.len() {
+            self.selected_index = self.files.len() - 1;
+        }
+        let key = self.diff_cache_key(repo);
+        if self.show_cached_details(key.as_ref()) {
+            self.pending_diff = None;
+            return;
+        }
+        let Some(request) = DiffRequest::for_selection(self) else {
+            return;
+        };
+        self.diff_hunks.clear();
+        self.pending_diff = Some((request.clone(), key));
+        self.worker.run(GitTask::Diff(request));
+    }
+
+    fn show_cached_details(&mut self, key: Option<&DiffCacheKey>) -> bool {
+        let Some((lines, hunks)) = key.and_then(|key| self.diff_cache.get(key)) else {
+            return false;
+        };
+        self.right_pane_lines = lines.clone();
+        self.diff_hunks = hunks.clone();
+        self.selected_hunk = self
+            .selected_hunk
+            .min(self.diff_hunks.len().saturating_sub(1));
+        true
+    }
+
+    /// Shows a diff read by the worker, unless another has been asked for or shown since.
+    fn finish_details(
+        &mut self,
+        request: DiffRequest,
+        details: Result<(Vec<DiffLine>, Vec<Hunk>), git2::Error>,
+    ) {
+        if self.pending_diff.as_ref().map(|(pending, _)| pending) != Some(&request) {
+            return;
+        }
+        let Some((_, key)) = self.pending_diff.take() else {
+            return;
+        };
+        match details {
+            Ok((lines, hunks)) => {
+                if let Some(key) = key {
+                    self.diff_cache.insert(key, (lines.clone(), hunks.clone()));
+                }
+                self.right_pane_lines = lines;
+                self.diff_hunks = hunks;
+                self.selected_hunk = self
+                    .selected_hunk
+                    .min(self.diff_hunks.len().saturating_sub(1));
+            }
+            Err(e) => self.debug_log(&format!("Failed to show {}: {}", request.name, e.message())),
+        }
+    }
+
+    /// `None` for directories, whose own modification time says nothing about the files in
+    /// them.
+    fn diff_cache_key(&self, repo: &Repository) -> Option<DiffCacheKey> {
+        let file = &self.files[self.selected_index];
+        if file.is_dir {
+            return None;
+        }
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        Some(DiffCacheKey {
+            path: file.name.clone(),
+            status: file.status,
+            modified: repo
+                .workdir()
+                .and_then(|workdir| modified(&workdir.join(&file.name))),
+            index_modified: modified(&repo.path().join("index")),
+            settings: self.diff_settings,
+            full: self.full_diff.as_ref() == Some(&file.name),
+        })
+    }
+
+    fn set_context_lines(&mut self, repo: &Repository, context_lines: u32) -> AppResult<()> {
+        let context_lines = context_lines.min(MAX_CONTEXT_LINES);
+        if context_lines == self.diff_settings.context_lines {
+            return Ok(());
+        }
+        self.diff_settings.context_lines = context_lines;
+        self.debug_log(&format!("Showing {} context lines", context_lines));
+        // Commit diffs in the history view are shown with git's default context.
+        if !self.history.is_visible && !self.right_pane_lines.is_empty() {
+            self.request_details(repo);
+        }
+        Ok(())
+    }
+
+    fn cycle_whitespace(&mut self, repo: &Repository) -> AppResult<()> {
+        let whitespace = self.diff_settings.whitespace.next();
+        self.diff_settings.whitespace = whitespace;
+        match whitespace.label() {
+            Some(label) => self.debug_log(&format!(
+                "Diffs are {}; hunks can be staged again once whitespace is shown",
+                label
+            )),
+            None => self.debug_log("Diffs show whitespace changes"),
+        }
+        if !self.history.is_visible && !self.right_pane_lines.is_empty() {
+            self.request_details(repo);
+        }
+        Ok(())
+    }
+
+    /// A diff that leaves whitespace changes out doesn't apply to the file as it is, so its
+    /// hunks are only for reading.
+    fn can_stage_hunks(&mut self) -> bool {
+        let showing = self.diff_settings.whitespace == Whitespace::Show;
+        if !showing {
+            let keys = self.keymap.labels_for(Action::CycleWhitespace).join("/");
+            self.debug_log(&format!(
+                "Hunks can't be staged while whitespace changes are hidden; press {} to show them",
+                keys
+            ));
+        }
+        showing
+    }
+
+    /// Replaces the diff in the Details pane with the blame of the selected file; Enter
+    /// brings the diff back.
+    fn show_blame(&mut self, repo: &Repository) {
+        let Some(file) = self.files.get(self.selected_index) else {
+            return;
+        };
+        if file.is_dir {
+            self.debug_log("Blame needs a file rather than a directory");
+            return;
+        }
+        let name = file.name.clone();
+        match blame_file(repo, &name) {
+            Ok(lines) => {
+                self.right_pane_lines = lines;
+                self.diff_hunks.clear();
+                self.details_scroll = 0;
+                self.details_hscroll = 0;
+            }
+            Err(e) => self.debug_log(&format!("Failed to blame {}: {}", name, e.message())),
+        }
+    }
+
+    /// Copies the Details pane as plain text, with the `+`/`-` markers of diff lines.
+    fn copy_details(&mut self) {
+        if self.right_pane_lines.is_empty() {
+            self.debug_log("Nothing to copy; open a file or commit first");
+            return;
+        }
+        let text: Vec<String> = self
+            .right_pane_lines
+            .iter()
+            .map(|line| match line.kind {
+                LineKind::Addition => format!("+{}", line.content),
+                LineKind::Deletion => format!("-{}", line.content),
+                LineKind::Context => format!(" {}", line.content),
+                LineKind::Blame => format!(
+                    "{} {}",
+                    line.annotation.as_deref().unwrap_or_default(),
+                    line.content
+                ),
+                _ => line.content.clone(),
+            })
+            .collect();
+        match clipboard::copy(&(text.join("\n") + "\n")) {
+            Ok(()) => self.debug_log(&format!("Copied {} lines", text.len())),
+            Err(e) => self.debug_log(&format!("Failed to copy: {}", e)),
+        }
+    }
+
+    fn copy_selected_path(&mut self) {
+        let Some(file) = self.files.get(self.selected_index) else {
+            return;
+        };
+        let name = file.name.clone();
+        match clipboard::copy(&name) {
+            Ok(()) => self.debug_log(&format!("Copied {}", name)),
+            Err(e) => self.debug_log(&format!("Failed to copy: {}", e)),
+        }
+    }
+
+    fn stage_selected(&mut self, repo: &Repository) -> AppResult<()> {
+        if let Some(selected_file) = self.files.get(self.selected_index) {
+            let name = selected_file.name.clone();
+            match stage_file(repo, &name) {
+                Ok(()) => self.debug_log(&format!("Staged {}", name)),
+                Err(e) => self.debug_log(&format!("Failed to stage {}: {}", name, e.message())),
+            }
+            self.refresh_file_list(repo);
+            if !self.right_pane_lines.is_empty() {
+                self.show_details(repo)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn unstage_selected(&mut self, repo: &Repository) -> AppResult<()> {
+        if let Some(selected_file) = self.files.get(self.selected_index) {
+            let name = selected_file.name.clone();
+            match unstage_file(repo, &name) {
+                Ok(true) => self.debug_log(&format!("Unstaged {}", name)),
+                Ok(false) => self.debug_log(&format!("{} has no staged changes", name)),
+                Err(e) => self.debug_log(&format!("Failed to unstage {}: {}", name, e.message())),
+            }
+            self.refresh_file_list(repo);
+            if !self.right_pane_lines.is_empty() {
+                self.show_details(repo)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn unstage_everything(&mut self, repo: &Repository) -> AppResult<()> {
+        match unstage_all(repo) {
+            Ok(0) => self.debug_log("Nothing is staged"),
+            Ok(count) => self.debug_log(&format!("Unstaged {} paths", count)),
+            Err(e) => self.debug_log(&format!("Failed to unstage: {}", e.message())),
+        }
+        self.refresh_file_list(repo);
+        if !self.right_pane_lines.is_empty() {
+            self.show_details(repo)?;
+        }
+        Ok(())
+    }
+
+    /// Picks up tasks the worker has finished. They may have moved HEAD or the working
+    /// tree, so the file list is read again.
+    pub fn handle_worker_results(&mut self, repo: &Repository) {
+        let mut finished = false;
+        while let Some(result) = self.worker.try_result() {
+            match result {
+                GitResult::Status(request, listed) => self.finish_file_list(repo, request, listed),
+                GitResult::Diff(request, details) => self.finish_details(request, details),
+                GitResult::Succeeded(GitTask::Pull) => {
+                    finished = true;
+                    self.right_pane_lines.clear();
+                }
+                GitResult::Succeeded(_) | GitResult::Failed(_) => finished = true,
+            }
+        }
+        if finished {
+            self.refresh_file_list(repo);
+            if self.submodule_list.is_visible {
+                self.open_submodule_list(repo);
+            }
+        }
+    }
+
+    /// Opens the commit modal on what is staged already, which is all the commit takes.
+    fn start_commit(&mut self, repo: &Repository) {
+        if self.head_status.detached {
+            self.debug_log(&format!(
+                "HEAD is {}; the commit won't be on any branch unless you create one for it",
+                self.head_status.branch.trim_matches(['(', ')'])
+            ));
+        }
+        // Start from the team's template, unless there's a draft already.
+        if self.commit_modal.content.is_empty() {
+            if let Some(template) = commit_template(repo) {
+                self.commit_modal.set_content(template);
+            }
+        }
+        if self.config.commit_in_editor {
+            self.editor_requested = true;
+        } else {
+            self.open_commit_modal(repo);
+        }
+    }
+
+    /// Shows everything the commit will take in the Details pane. The modal is hidden
+    /// meanwhile, keeping the message for when the commit key brings it back.
+    fn preview_commit(&mut self, repo: &Repository) {
+        let lines = staged_diff(
+            repo,
+            self.amend_mode,
+            &self.diff_settings,
+            self.config.max_diff_lines,
+        );
+        match lines {
+            Ok(lines) => {
+                self.right_pane_lines = lines;
+                self.diff_hunks.clear();
+                self.details_scroll = 0;
+                self.details_hscroll = 0;
+                self.commit_modal.is_visible = false;
+                self.commit_modal.error = None;
+                self.set_focused_pane(FocusedPane::Details);
+                let keys = self.keymap.labels_for(Action::Commit).join("/");
+                self.debug_log(&format!(
+                    "Previewing the commit; press {} to go back to the message",
+                    keys
+                ));
+            }
+            Err(e) => {
+                self.commit_modal.error = Some(format!("Failed to diff the staged changes: {}", e))
+            }
+        }
+    }
+
+    /// Shows the commit modal along with the files that are staged right now.
+    fn open_commit_modal(&mut self, repo: &Repository) {
+        self.commit_files = match staged_files(repo) {
+            Ok(files) => files,
+            Err(e) => {
+                self.debug_log(&format!("Failed to list the staged files: {}", e.message()));
+                Vec::new()
+            }
+        };
+        self.commit_modal.is_visible = true;
+    }
+
+    /// Puts the next of the configured prefixes, like `feat: `, in front of the commit
+    /// message, replacing the one already there. After the last one the prefix is removed.
+    fn cycle_commit_prefix(&mut self) {
+        let prefixes = &self.config.commit_prefixes;
+        if prefixes.is_empty() {
+            self.commit_modal.error = Some("No commit prefixes are configured".to_string());
+            return;
+        }
+        let content = &self.commit_modal.content;
+        let current = prefixes
+            .iter()
+            .position(|prefix| content.starts_with(&format!("{}: ", prefix)));
+        let rest = match current {
+            Some(index) => &content[prefixes[index].len() + 2..],
+            None => content.as_str(),
+        };
+        let next = match current {
+            Some(index) => prefixes.get(index + 1),
+            None => prefixes.first(),
+        };
+        let content = match next {
+            Some(prefix) => format!("{}: {}", prefix, rest),
+            None => rest.to_string(),
+        };
+        self.commit_modal.error = None;
+        self.commit_modal.set_content(content);
+    }
+
+    fn toggle_amend_mode(&mut self, repo: &Repository) -> AppResult<()> {
+        self.commit_modal.error = None;
+        if self.amend_mode {
+            self.amend_mode = false;
+            self.commit_modal.clear();
+            return Ok(());
+        }
+        match last_commit_message(repo)? {
+            Some(message) => {
+                self.amend_mode = true;
+                self.commit_modal
+                    .set_content(message.trim_end().to_string());
+            }
+            None => self.commit_modal.error = Some("There is no commit to amend yet".to_string()),
+        }
+        Ok(())
+    }
+
+    fn open_branch_list(&mut self, repo: &Repository) {
+        self.branch_list.error = None;
+        match list_local_branches(repo) {
+            Ok(branches) => {
+                self.branch_list.selected = branches.iter().position(|b| b.is_head).unwrap_or(0);
+                self.branch_list.branches = branches;
+                self.load_branch_counts(repo);
+            }
+            Err(e) => {
+                self.branch_list.branches.clear();
+                self.branch_list.error = Some(format!("Failed to list branches: {}", e.message()));
+            }
+        }
+        self.branch_list.is_visible = true;
+    }
+
+    /// Works out how far the branches around the selection are ahead of and behind their
+    /// upstreams, a screenful either way, so that the rest of a long list costs nothing.
+    fn load_branch_counts(&mut self, repo: &Repository) {
+        let selected = self.branch_list.selected;
+        let end = (selected + BRANCH_COUNT_WINDOW).min(self.branch_list.branches.len());
+        for branch in
+            &mut self.branch_list.branches[selected.saturating_sub(BRANCH_COUNT_WINDOW)..end]
+        {
+            if matches!(branch.upstream, BranchUpstream::Tracking(_))
+                && branch.ahead_behind.is_none()
+            {
+                branch.ahead_behind = branch_ahead_behind(repo, &branch.name).ok();
+            }
+        }
+    }
+
+    fn checkout_selected_branch(&mut self, repo: &Repository) {
+        let Some(branch) = self.branch_list.branches.get(self.branch_list.selected) else {
+            return;
+        };
+        if branch.is_head {
+            self.branch_list.is_visible = false;
+            return;
+        }
+        let name = branch.name.clone();
+        match checkout_branch(repo, &name) {
+            Ok(()) => {
+                self.debug_log(&format!("Switched to branch {}", name));
+                self.branch_list.is_visible = false;
+                self.selected_index = 0;
+                self.expanded_dirs.clear();
+                self.right_pane_lines.clear();
+                self.diff_hunks.clear();
+                self.refresh_file_list(repo);
+            }
+            Err(e) => self.branch_list.error = Some(e.message().to_string()),
+        }
+    }
+
+    /// Deletes the selected branch if HEAD has all of its commits. Otherwise `force` asks
+    /// whether to delete it anyway, as `git branch -D` would.
+    fn delete_selected_branch(&mut self, repo: &Repository, force: bool) {
+        let Some(branch) = self.branch_list.branches.get(self.branch_list.selected) else {
+            return;
+        };
+        if branch.is_head {
+            self.branch_list.error = Some("Can't delete the branch you are on".to_string());
+            return;
+        }
+        let name = branch.name.clone();
+        match unmerged_commit_count(repo, &name) {
+            Ok(0) => self.delete_branch(repo, &name),
+            Ok(count) => {
+                let commits = if count == 1 { "commit" } else { "commits" };
+                if force {
+                    self.confirmation = Some(Confirmation {
+                        prompt: format!(
+                            "{} has {} {} not merged into HEAD. Delete it anyway?",
+                            name, count, commits
+                        ),
+                        action: PendingAction::DeleteBranch(name),
+                    });
+                } else {
+                    self.branch_list.error = Some(format!(
+                        "{} has {} {} not merged into HEAD; press D to delete it anyway",
+                        name, count, commits
+                    ));
+                }
+            }
+            Err(e) => self.branch_list.error = Some(e.message().to_string()),
+        }
+    }
+
+    /// Swaps the branch list for a prompt holding the selected branch's name to edit.
+    fn rename_selected_branch(&mut self) {
+        let Some(branch) = self.branch_list.branches.get(self.branch_list.selected) else {
+            return;
+        };
+        let name = branch.name.clone();
+        self.branch_list.is_visible = false;
+        self.open_input(InputAction::RenameBranch(name.clone()));
+        if let Some(input) = &mut self.input {
+            input.field.set_content(name);
+        }
+    }
+
+    fn delete_branch(&mut self, repo: &Repository, name: &str) {
+        if let Err(e) = delete_branch(repo, name) {
+            self.debug_log(&format!(
+                "Failed to delete branch {}: {}",
+                name,
+                e.message()
+            ));
+            self.branch_list.error = Some(e.message().to_string());
+            return;
+        }
+        self.debug_log(&format!("Deleted branch {}", name));
+        let selected = self.branch_list.selected;
+        self.open_branch_list(repo);
+        if !self.branch_list.branches.is_empty() {
+            self.branch_list.selected = selected.min(self.branch_list.branches.len() - 1);
+            self.load_branch_counts(repo);
+        }
+    }
+
+    fn merge_selected_branch(&mut self, repo: &Repository) -> AppResult<()> {
+        let Some(branch) = self.branch_list.branches.get(self.branch_list.selected) else {
+            return Ok(());
+        };
+        if branch.is_head {
+            self.branch_list.error = Some("Can't merge a branch into itself".to_string());
+            return Ok(());
+        }
+        let name = branch.name.clone();
+        let outcome = match merge_branch(repo, &name, self.config.require_signing) {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                self.branch_list.error = Some(e.message().to_string());
+                return Ok(());
+            }
+        };
+        match outcome {
+            MergeOutcome::UpToDate => self.debug_log(&format!("Already up to date with {}", name)),
+            MergeOutcome::FastForwarded => self.debug_log(&format!("Fast-forwarded to {}", name)),
+            MergeOutcome::Merged => self.debug_log(&format!("Merged {}", name)),
+            MergeOutcome::Conflicts(conflicts) => {
+                self.debug_log(&format!(
+                    "Merging {} conflicts in {}; resolve, stage and commit them",
+                    name,
+                    conflicts.join(", ")
+                ));
+                if let Ok(message) = repo.message() {
+                    self.commit_modal.set_content(strip_comments(&message));
+                }
+            }
+        }
+        self.branch_list.is_visible = false;
+        self.right_pane_lines.clear();
+        self.diff_hunks.clear();
+        self.refresh_file_list(repo);
+        Ok(())
+    }
+
+    /// Resolves the selected conflicted file with our or their version of it.
+    fn take_conflict_side(&mut self, repo: &Repository, ours: bool) -> AppResult<()> {
+        let Some(file) = self.files.get(self.selected_index) else {
+            return Ok(());
+        };
+        if file.is_dir || !file.status.contains(Status::CONFLICTED) {
+            self.debug_log(&format!("{} has no conflicts to resolve", file.name));
+            return Ok(());
+        }
+        let name = file.name.clone();
+        let side = if ours { "our" } else { "their" };
+        match take_conflict_side(repo, &name, ours) {
+            Ok(()) => self.debug_log(&format!("Resolved {} with {} version", name, side)),
+            Err(e) => self.debug_log(&format!("Failed to resolve {}: {}", name, e.message())),
+        }
+        self.refresh_file_list(repo);
+        self.show_details(repo)
+    }
+
+    /// Opens the todo list for rebasing the commits from the one selected in the history.
+    fn open_rebase_todo(&mut self, repo: &Repository) {
+        let Some(commit) = self.history.commits.get(self.history.selected) else {
+            return;
+        };
+        match todo_list(repo, commit.id) {
+            Ok(items) => {
+                self.rebase_todo.items = items;
+                self.rebase_todo.selected = 0;
+                self.rebase_todo.error = None;
+                self.rebase_todo.is_visible = true;
+            }
+            Err(e) => self.debug_log(&format!("Can't rebase: {}", e.message())),
+        }
+    }
+
+    /// Shows what is left of the stopped rebase, to continue or abort it.
+    fn open_stopped_rebase(&mut self) {
+        let Some(rebase) = &self.rebase else {
+            return;
+        };
+        self.rebase_todo.items = rebase.remaining().cloned().collect();
+        self.rebase_todo.selected = 0;
+        self.rebase_todo.error = None;
+        self.rebase_todo.is_visible = true;
+    }
+
+    fn set_todo_action(&mut self, action: TodoAction) {
+        if let Some(item) = self.rebase_todo.items.get_mut(self.rebase_todo.selected) {
+            item.action = action;
+        }
+    }
+
+    /// Moves the selected todo item one place down the list, or up, taking the selection
+    /// with it.
+    fn move_todo_item(&mut self, down: bool) {
+        let selected = self.rebase_todo.selected;
+        let target = if down {
+            selected + 1
+        } else {
+            selected.wrapping_sub(1)
+        };
+        if target < self.rebase_todo.items.len() {
+            self.rebase_todo.items.swap(selected, target);
+            self.rebase_todo.selected = target;
+        }
+    }
+
+    fn open_reword(&mut self) {
+        if let Some(item) = self.rebase_todo.items.get(self.rebase_todo.selected) {
+            self.reword_modal
+                .set_content(item.message.trim_end().to_string());
+            self.reword_modal.error = None;
+            self.reword_modal.is_visible = true;
+        }
+    }
+
+    fn save_reword(&mut self) {
+        let message = strip_comments(&self.reword_modal.content);
+        if message.trim().is_empty() {
+            self.reword_modal.error = Some("The commit message is empty".to_string());
+            return;
+        }
+        if let Some(item) = self.rebase_todo.items.get_mut(self.rebase_todo.selected) {
+            item.summary = message.lines().next().unwrap_or_default().to_string();
+            item.message = message;
+            item.action = TodoAction::Reword;
+        }
+        self.reword_modal.is_visible = false;
+    }
+
+    /// Starts the rebase from the todo list, or continues the stopped one.
+    fn run_rebase(&mut self, repo: &Repository) -> AppResult<()> {
+        let progress = match self.rebase.as_mut() {
+            Some(rebase) => rebase.resume(repo),
+            None => Rebase::start(
+                repo,
+                self.rebase_todo.items.clone(),
+                self.config.require_signing,
+            )
+            .map(|(rebase, progress)| {
+                self.rebase = Some(rebase);
+                progress
+            }),
+        };
+        match progress {
+            Ok(RebaseProgress::Finished) => {
+                self.rebase = None;
+                self.rebase_todo.is_visible = false;
+                self.debug_log("Rebase finished");
+                self.open_history(repo);
+            }
+            Ok(RebaseProgress::Conflicts(conflicts)) => {
+                let (step, total) = self.rebase.as_ref().map_or((0, 0), Rebase::position);
+                self.debug_log(&format!(
+                    "Rebase stopped at step {}/{} with conflicts in {}; resolve and stage them, then press i to continue",
+                    step,
+                    total,
+                    conflicts.join(", ")
+                ));
+                self.rebase_todo.is_visible = false;
+                // Show the conflicted files.
+                self.close_history();
+            }
+            Err(e) => self.rebase_todo.error = Some(e.message().to_string()),
+        }
+        self.refresh_file_list(repo);
+        Ok(())
+    }
+
+    /// Quits, first asking for confirmation when there are uncommitted changes to tracked
+    /// files, unless that is turned off.
+    pub fn request_quit(&mut self) {
+        if self.config.confirm_quit && self.status_summary.is_dirty() {
+            self.confirmation = Some(Confirmation {
+                prompt: "You have uncommitted changes, quit anyway?".to_string(),
+                action: PendingAction::Quit,
+            });
+        } else {
+            self.should_quit = true;
+        }
+    }
+
+    fn confirm_hard_reset(&mut self) {
+        let Some(menu) = self.reset_menu.take() else {
+            return;
+        };
+        self.confirmation = Some(Confirmation {
+            prompt: format!(
+                "Reset hard to {}? Uncommitted changes to tracked files are lost.",
+                menu.label
+            ),
+            action: PendingAction::HardReset(menu.target),
+        });
+    }
+
+    fn reset_to(&mut self, repo: &Repository, oid: Oid, kind: ResetType) -> AppResult<()> {
+        self.reset_menu = None;
+        match reset_to_commit(repo, oid, kind) {
+            Ok(()) => {
+                let summary = repo
+                    .find_commit(oid)
+                    .map(|commit| commit.summary().unwrap_or_default().to_string())
+                    .unwrap_or_default();
+                self.debug_log(&format!(
+                    "HEAD is now at {} {}",
+                    &oid.to_string()[..7],
+                    summary
+                ));
+                // The history above the new HEAD is gone from the branch.
+                self.open_history(repo);
+            }
+            Err(e) => self.debug_log(&format!("Reset failed: {}", e.message())),
+        }
+        self.refresh_file_list(repo);
+        Ok(())
+    }
+
+    fn confirm_abort_rebase(&mut self) {
+        self.confirmation = Some(Confirmation {
+            prompt: "Abort the rebase and put the branch back as it was?".to_string(),
+            action: PendingAction::AbortRebase,
+        });
+    }
+
+    fn abort_rebase(&mut self, repo: &Repository) {
+        let Some(rebase) = self.rebase.take() else {
+            return;
+        };
+        match rebase.abort(repo) {
+            Ok(()) => self.debug_log("Rebase aborted"),
+            Err(e) => self.debug_log(&format!("Failed to abort the rebase: {}", e.message())),
+        }
+        self.rebase_todo.is_visible = false;
+        self.right_pane_lines.clear();
+        self.diff_hunks.clear();
+        self.refresh_file_list(repo);
+    }
+
+    /// Marks the selected commit of the history, or HEAD outside of it, as good or bad,
+    /// starting a bisect if none is in progress.
+    fn bisect_mark(&mut self, repo: &Repository, good: bool) -> AppResult<()> {
+        let target = if self.history.is_visible {
+            self.history
+                .commits
+                .get(self.history.selected)
+                .map(|commit| commit.id)
+        } else {
+            repo.head().ok().and_then(|head| head.target())
+        };
+        let Some(oid) = target else {
+            self.debug_log("Bisect: there is no commit to mark");
+            return Ok(());
+        };
+        let fresh = self.bisect.is_none();
+        let mut bisect = match self.bisect.take() {
+            Some(bisect) => bisect,
+            None => match Bisect::start(repo) {
+                Ok(bisect) => bisect,
+                Err(e) => {
+                    self.debug_log(&format!("Can't start a bisect: {}", e.message()));
+                    return Ok(());
+                }
+            },
+        };
+        let progress = bisect.mark(repo, oid, good);
+        let describe = |oid: Oid| match repo.find_commit(oid) {
+            Ok(commit) => format!("{:.7} \"{}\"", oid, commit.summary().unwrap_or_default()),
+            Err(_) => format!("{:.7}", oid),
+        };
+        let keys = |action| self.keymap.labels_for(action).join("/");
+        let message = match progress {
+            Ok(BisectProgress::NeedsGood) => format!(
+                "Bisect: marked {} bad; now mark a good commit with {}",
+                describe(oid),
+                keys(Action::BisectGood)
+            ),
+            Ok(BisectProgress::NeedsBad) => format!(
+                "Bisect: marked {} good; now mark a bad commit with {}",
+                describe(oid),
+                keys(Action::BisectBad)
+            ),
+            Ok(BisectProgress::Testing { commit, steps }) => format!(
+                "Bisect: testing {} (~{} {} remaining); mark it with {} or {}",
+                describe(commit),
+                steps,
+                if steps == 1 { "step" } else { "steps" },
+                keys(Action::BisectGood),
+                keys(Action::BisectBad)
+            ),
+            Ok(BisectProgress::Found(commit)) => format!(
+                "Bisect: {} is the first bad commit; press {} to end the bisect",
+                describe(commit),
+                keys(Action::BisectReset)
+            ),
+            Err(ref e) => format!("Bisect: {}", e.message()),
+        };
+        self.debug_log(&message);
+        // A failed first mark leaves no bisect behind.
+        if progress.is_ok() || !fresh {
+            self.bisect = Some(bisect);
+        }
+        match progress {
+            Ok(BisectProgress::Testing { .. }) => self.after_bisect_checkout(repo),
+            Ok(BisectProgress::Found(commit)) => {
+                if let Ok(lines) = show_commit(repo, commit) {
+                    self.right_pane_lines = lines;
+                    self.diff_hunks.clear();
+                    self.details_scroll = 0;
+                    self.details_hscroll = 0;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Ends the bisect, going back to the branch or commit HEAD was on when it started.
+    fn bisect_reset(&mut self, repo: &Repository) -> AppResult<()> {
+        let Some(bisect) = self.bisect.take() else {
+            self.debug_log("No bisect in progress");
+            return Ok(());
+        };
+        if let Err(e) = bisect.reset(repo) {
+            self.debug_log(&format!("Failed to end the bisect: {}", e.message()));
+            return Ok(());
+        }
+        self.after_bisect_checkout(repo);
+        self.debug_log(&format!(
+            "Bisect ended; HEAD is on {}",
+            self.head_status.branch
+        ));
+        Ok(())
+    }
+
+    fn after_bisect_checkout(&mut self, repo: &Repository) {
+        self.right_pane_lines.clear();
+        self.diff_hunks.clear();
+        self.refresh_file_list(repo);
+        if self.history.is_visible {
+            self.open_history(repo);
+        }
+    }
+
+    fn stash_changes(&mut self, repo: &Repository) {
+        match stash_save(repo, None) {
+            Ok(()) => {
+                self.debug_log("Stashed local changes");
+                self.right_pane_lines.clear();
+                self.diff_hunks.clear();
+            }
+            Err(e) => self.debug_log(&format!("Stash failed: {}", e.message())),
+        }
+        self.refresh_file_list(repo);
+    }
+
+    fn open_stash_list(&mut self, repo: &Repository) {
+        self.stash_list.error = None;
+        self.stash_list.selected = 0;
+        match list_stashes(repo) {
+            Ok(stashes) => self.stash_list.stashes = stashes,
+            Err(e) => {
+                self.stash_list.stashes.clear();
+                self.stash_list.error = Some(format!("Failed to list stashes: {}", e.message()));
+            }
+        }
+        self.stash_list.is_visible = true;
+    }
+
+    fn open_reflog(&mut self, repo: &Repository) {
+        self.reflog.error = None;
+        self.reflog.selected = 0;
+        match load_reflog(repo) {
+            Ok(entries) => self.reflog.entries = entries,
+            Err(e) => {
+                self.reflog.entries.clear();
+                self.reflog.error = Some(format!("Failed to read the reflog: {}", e.message()));
+            }
+        }
+        self.reflog.is_visible = true;
+    }
+
+    /// Swaps the reflog for the reset menu, to put the branch back where HEAD was at the
+    /// selected entry.
+    fn reset_to_reflog_entry(&mut self) {
+        let Some(entry) = self.reflog.entries.get(self.reflog.selected) else {
+            return;
+        };
+        self.reset_menu = Some(ResetMenu {
+            target: entry.id,
+            label: format!(
+                "HEAD@{{{}}} {:.7} \"{}\"",
+                self.reflog.selected, entry.id, entry.message
+            ),
+        });
+        self.reflog.is_visible = false;
+    }
+
+    /// Lists the submodules, keeping the selection when the list is read again after an
+    /// update.
+    fn open_submodule_list(&mut self, repo: &Repository) {
+        self.submodule_list.error = None;
+        match list_submodules(repo) {
+            Ok(submodules) => self.submodule_list.submodules = submodules,
+            Err(e) => {
+                self.submodule_list.submodules.clear();
+                self.submodule_list.error =
+                    Some(format!("Failed to list submodules: {}", e.message()));
+            }
+        }
+        if !self.submodule_list.is_visible {
+            self.submodule_list.selected = 0;
+        }
+        self.submodule_list.selected = self
+            .submodule_list
+            .selected
+            .min(self.submodule_list.submodules.len().saturating_sub(1));
+        self.submodule_list.is_visible = true;
+    }
+
+    /// Applies the selected stash, also dropping it when `pop` is set. The list stays open
+    /// on failure so the error can be read.
+    fn apply_selected_stash(&mut self, repo: &Repository, pop: bool) {
+        let Some(stash) = self.stash_list.stashes.get(self.stash_list.selected) else {
+            return;
+        };
+        let index = stash.index;
+        match stash_apply(repo, index, pop) {
+            Ok(()) => {
+                let action = if pop { "Popped" } else { "Applied" };
+                self.debug_log(&format!("{} stash@{{{}}}", action, index));
+                self.stash_list.is_visible = false;
+                self.right_pane_lines.clear();
+                self.diff_hunks.clear();
+                self.refresh_file_list(repo);
+            }
+            Err(e) => self.stash_list.error = Some(e.message().to_string()),
+        }
+    }
+
+    fn drop_selected_stash(&mut self, repo: &Repository) {
+        let Some(stash) = self.stash_list.stashes.get(self.stash_list.selected) else {
+            return;
+        };
+        let index = stash.index;
+        match stash_drop(repo, index) {
+            Ok(()) => {
+                self.debug_log(&format!("Dropped stash@{{{}}}", index));
+                self.open_stash_list(repo);
+                self.stash_list.selected =
+                    index.min(self.stash_list.stashes.len().saturating_sub(1));
+            }
+            Err(e) => self.stash_list.error = Some(e.message().to_string()),
+        }
+    }
+
+    fn open_input(&mut self, action: InputAction) {
+        self.input = Some(InputModal {
+            title: action.title(self.checkout_new_branch).to_string(),
+            field: Modal::default(),
+            action,
+        });
+    }
+
+    /// Runs the action of the open prompt with its text, closing the prompt if it succeeds.
+    fn submit_input(&mut self, repo: &Repository) {
+        let Some(input) = &self.input else {
+            return;
+        };
+        let text = input.field.content.trim().to_string();
+        let result = match input.action.clone() {
+            InputAction::CreateBranch => self.perform_create_branch(repo, &text),
+            InputAction::RenameBranch(old) => self.perform_rename_branch(repo, &old, &text),
+            InputAction::CherryPick => self.perform_cherry_pick(repo, &text),
+            InputAction::ShowCommit => self.perform_show_commit(repo, &text),
+            InputAction::CreateTag(target) => self.perform_create_tag(repo, &text, target),
+        };
+        match result {
+            Ok(()) => self.input = None,
+            Err(e) => {
+                if let Some(input) = &mut self.input {
+                    input.field.error = Some(e.message().to_string());
+                }
+            }
+        }
+    }
+
+    fn perform_create_branch(&mut self, repo: &Repository, name: &str) -> Result<(), git2::Error> {
+        create_branch(repo, name, self.checkout_new_branch)?;
+        self.debug_log(&format!("Created branch {}", name));
+        Ok(())
+    }
+
+    fn perform_rename_branch(
+        &mut self,
+        repo: &Repository,
+        old: &str,
+        new: &str,
+    ) -> Result<(), git2::Error> {
+        if old == new {
+            return Ok(());
+        }
+        rename_branch(repo, old, new)?;
+        self.debug_log(&format!("Renamed branch {} to {}", old, new));
+        self.head_status = head_status(repo);
+        Ok(())
+    }
+
+    fn perform_cherry_pick(&mut self, repo: &Repository, spec: &str) -> Result<(), git2::Error> {
+        let oid = resolve_commit(repo, spec)?;
+        let commit = repo.find_commit(oid)?;
+        let conflicts = cherry_pick_commit(repo, oid)?;
+
+        let message = commit.message().unwrap_or_default().trim_end().to_string();
+        self.commit_modal.set_content(message);
+        if conflicts.is_empty() {
+            self.debug_log(&format!("Cherry-picked {}; commit to finish", spec));
+            self.open_commit_modal(repo);
+        } else {
+            self.debug_log(&format!(
+                "Cherry-picking {} conflicts in {}; resolve, stage and commit them",
+                spec,
+                conflicts.join(", ")
+            ));
+        }
+        self.refresh_file_list(repo);
+        Ok(())
+    }
+
+    fn perform_show_commit(&mut self, repo: &Repository, spec: &str) -> Result<(), git2::Error> {
+        self.right_pane_lines = show_commit(repo, resolve_commit(repo, spec)?)?;
+        self.diff_hunks.clear();
+        self.details_scroll = 0;
+        self.details_hscroll = 0;
+        Ok(())
+    }
+
+    /// The first word of `text` is the tag's name; anything after it becomes the message of
+    /// an annotated tag.
+    fn perform_create_tag(
+        &mut self,
+        repo: &Repository,
+        text: &str,
+        target: Option<Oid>,
+    ) -> Result<(), git2::Error> {
+        let (name, message) = match text.split_once(char::is_whitespace) {
+            Some((name, message)) => (name, Some(message.trim())),
+            None => (text, None),
+        };
+        create_tag(repo, name, message, target)?;
+        self.debug_log(&format!("Created tag {}", name));
+        self.head_status = head_status(repo);
+        if self.history.is_visible {
+            match tags_by_commit(repo) {
+                Ok(tags) => self.history.tags = tags,
+                Err(e) => self.debug_log(&format!("Failed to read tags: {}", e.message())),
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether any modal is capturing input, in which case global keys like quit are ignored.
+    pub fn has_open_modal(&self) -> bool {
+        self.commit_modal.is_visible
+            || self.help_modal.is_visible
+            || self.branch_list.is_visible
+            || self.stash_list.is_visible
+            || self.submodule_list.is_visible
+            || self.reflog.is_visible
+            || self.rebase_todo.is_visible
+            || self.reword_modal.is_visible
+            || self.input.is_some()
+            || self.search.prompt.is_visible
+            || self.confirmation.is_some()
+            || self.reset_menu.is_some()
+    }
+
+    fn toggle_help(&mut self) {
+        self.help_modal.is_visible = !self.help_modal.is_visible;
+        self.help_scroll = 0;
+    }
+
+    fn close_modals(&mut self) {
+        if self.amend_mode {
+            // Don't leave the old commit message behind as the next commit's draft.
+            self.amend_mode = false;
+            self.commit_modal.clear();
+        }
+        self.commit_modal.is_visible = false;
+        self.commit_modal.error = None;
+        self.help_modal.is_visible = false;
+        self.branch_list.is_visible = false;
+        self.stash_list.is_visible = false;
+        self.submodule_list.is_visible = false;
+        self.reflog.is_visible = false;
+        self.rebase_todo.is_visible = false;
+        self.reword_modal.is_visible = false;
+        self.input = None;
+        self.confirmation = None;
+        self.reset_menu = None;
+    }
+
+    /// The draft to open in the external editor, once per request.
+    pub fn take_editor_request(&mut self) -> Option<String> {
+        if !std::mem::take(&mut self.editor_requested) {
+            return None;
+        }
+        Some(self.commit_modal.content.clone())
+    }
+
+    /// Commits with the message written in the external editor. An empty message aborts the
+    /// commit, as with `git commit`; if the editor couldn't be run, the commit modal opens
+    /// with the draft instead.
+    pub fn finish_editor_commit(
+        &mut self,
+        repo: &Repository,
+        message: Result<String, String>,
+    ) -> AppResult<()> {
+        match message {
+            Ok(message) if message.trim().is_empty() => {
+                self.debug_log("Aborting commit due to empty commit message");
+                self.close_modals();
+                // Unlike a cancelled modal, the draft was deliberately emptied.
+                self.commit_modal.clear();
+            }
+            Ok(message) => {
+                self.commit_modal.set_content(message);
+                self.perform_commit(repo, false)?;
+                // Show why the commit failed, with the message kept in the modal.
+                if self.commit_modal.error.is_some() {
+                    self.open_commit_modal(repo);
+                }
+            }
+            Err(e) => {
+                self.debug_log(&format!("Failed to run the editor: {}", e));
+                self.open_commit_modal(repo);
+            }
+        }
+        Ok(())
+    }
+
+    /// Commits the staged changes. A blank message is refused unless `allow_empty_message`
+    /// is set, since it's almost always a slip of the Enter key.
+    fn perform_commit(&mut self, repo: &Repository, allow_empty_message: bool) -> AppResult<()> {
+        let message = strip_comments(&self.commit_modal.content);
+        if !allow_empty_message && message.trim().is_empty() {
+            self.commit_modal.error =
+                Some("The commit message is empty; press Ctrl+E to commit anyway".to_string());
+            return Ok(());
+        }
+        let result = if self.amend_mode {
+            amend_commit(repo, &message, self.config.require_signing)
+        } else {
+            create_commit(repo, &message, self.config.require_signing)
+        };
+        if let Err(e) = result {
+            // Keep the modal open so the message isn't lost.
+            self.commit_modal.error = Some(format!("Commit failed: {}", e.message()));
+            return Ok(());
+        }
+        self.amend_mode = false;
+        self.commit_modal.is_visible = false;
+        self.commit_modal.error = None;
+        self.commit_modal.clear();
+        self.refresh_file_list(repo);
+        self.right_pane_lines.clear();
+        Ok(())
+    }
+
+    pub fn debug_log(&mut self, message: &str) {
+        for line in message.split('\n') {
+            if self.debug_lines.len() >= self.config.debug_log_lines {
+                self.debug_lines.pop_front();
+                self.debug_trimmed += 1;
+            }
+            self.debug_lines.push_back(line.to_string());
+        }
+    }
+
+    /// Re-reads the file list after gitui changed the repository, dropping cached diffs since
+    /// HEAD may have moved as well.
+    pub fn refresh_file_list(&mut self, repo: &Repository) {
+        self.diff_cache.clear();
+        self.poll_file_list(repo);
+    }
+
+    /// Re-reads the file list and the state of HEAD for the status bar. On failure the
+    /// previous list is kept and the error logged. Cached diffs are kept, as their keys
+    /// already account for changes to the files and the index.
+    pub fn poll_file_list(&mut self, repo: &Repository) {
+        // Whatever the worker is reading was asked for before this.
+        self.pending_status = None;
+        let listed = get_file_list(repo, self.list_options, &mut self.expanded_dirs);
+        self.set_file_list(repo, listed);
+    }
+
+    /// Has the worker read the file list again, for changes made outside gitui, which
+    /// shouldn't hold up the UI in a big working tree.
+    pub fn request_file_list(&mut self) {
+        let request = StatusRequest {
+            options: self.list_options,
+            expanded_dirs: self.expanded_dirs.clone(),
+        };
+        self.pending_status = Some(request.clone());
+        self.worker.run(GitTask::Status(request));
+    }
+
+    /// Shows a file list read by the worker, unless it was read for other options or
+    /// directories, or the list has been read here since.
+    fn finish_file_list(
+        &mut self,
+        repo: &Repository,
+        request: StatusRequest,
+        listed: Result<FileList, git2::Error>,
+    ) {
+        if self.pending_status.as_ref() != Some(&request) {
+            return;
+        }
+        let listed = listed.map(|(files, summary, expanded_dirs)| {
+            self.expanded_dirs = expanded_dirs;
+            (files, summary)
+        });
+        self.set_file_list(repo, listed);
+    }
+
+    fn set_file_list(
+        &mut self,
+        repo: &Repository,
+        listed: Result<(Vec<FileEntry>, StatusSummary), git2::Error>,
+    ) {
+        self.head_status = head_status(repo);
+        let previous: Vec<String> = self.files.iter().map(|file| file.name.clone()).collect();
+        match listed {
+            Ok((files, summary)) => {
+                // Whatever the Details pane showed was about a file that is no longer listed;
+                // clearing it lets the pane say that there is nothing left to commit.
+                if files.is_empty() && !self.files.is_empty() && !self.history.is_visible {
+                    self.right_pane_lines.clear();
+                    self.diff_hunks.clear();
+                }
+                self.files = files;
+                self.status_summary = summary;
+            }
+            Err(e) => self.debug_log(&format!("Failed to read status: {}", e.message())),
+        }
+        // Stay on the same path when it is still listed, even if entries above it came or went,
+        // so that staging one file after another doesn't lose the place.
+        let reselected = reselect(&previous, self.selected_index, &self.files);
+        self.selected_index = reselected
+            .unwrap_or(self.selected_index)
+            .min(self.files.len().saturating_sub(1));
+    }
+
+    /// Reads the file list and the selected file's diff again, for changes the watcher
+    /// didn't pick up. The selection and the expanded directories are kept.
+    fn refresh(&mut self, repo: &Repository) -> AppResult<()> {
+        self.refresh_file_list(repo);
+        if !self.history.is_visible && !self.right_pane_lines.is_empty() {
+            self.show_details(repo)?;
+        }
+        self.debug_log("Refreshed");
+        Ok(())
+    }
+
+    fn cycle_status_filter(&mut self, repo: &Repository) {
+        self.list_options.status_filter = StatusFilter::next(self.list_options.status_filter);
+        self.refresh_file_list(repo);
+        self.right_pane_lines.clear();
+        self.diff_hunks.clear();
+        self.details_scroll = 0;
+        self.details_hscroll = 0;
+    }
+
+    fn cycle_sort(&mut self, repo: &Repository) {
+        self.list_options.sort = self.list_options.sort.next();
+        self.refresh_file_list(repo);
+        self.debug_log(&format!(
+            "Sorting files by {}",
+            self.list_options.sort.name()
+        ));
+    }
+
+    fn toggle_ignored(&mut self, repo: &Repository) {
+        self.list_options.show_ignored = !self.list_options.show_ignored;
+        self.refresh_file_list(repo);
+        self.debug_log(if self.list_options.show_ignored {
+            "Showing ignored files"
+        } else {
+            "Hiding ignored files"
+        });
+    }
+
+    fn toggle_debug_mode(&mut self) {
+        self.debug_mode = !self.debug_mode;
+        // The file list may have been widened into the room the debug pane takes.
+        self.set_file_list_width(self.file_list_width);
+        if !self.debug_mode && matches!(self.focused_pane, FocusedPane::Debug) {
+            self.set_focused_pane(FocusedPane::FileList);
+        }
+    }
+
+    /// Moves the focus from the file list to the Details pane to the debug pane, when it is
+    /// shown, and back around.
+    fn cycle_focus(&mut self) {
+        let next = match self.focused_pane {
+            FocusedPane::FileList => FocusedPane::Details,
+            FocusedPane::Details if self.debug_mode => FocusedPane::Debug,
+            FocusedPane::Details | FocusedPane::Debug => FocusedPane::FileList,
+        };
+        self.set_focused_pane(next);
+    }
+
+    fn set_focused_pane(&mut self, pane: FocusedPane) {
         self.focused_pane = pane;
     }
 }
 
-fn get_help_content() -> String {
-    "
-    Key Bindings:
-    ↑/↓: Navigate file list
-    Enter: Expand/collapse directory or view file details/diff
-    c: Stage all modified files and open commit dialog
-    ?: Toggle this help menu
-    q: Quit the application
+/// A nudge to pull or push when the branch and its upstream differ, as of the last fetch.
+/// The key to press is left out when the action isn't bound to one.
+fn sync_hint(head_status: &HeadStatus, keymap: &KeyMap) -> Option<String> {
+    let upstream = head_status.upstream.as_deref()?;
+    let commits = |count: usize| {
+        if count == 1 {
+            "1 commit".to_string()
+        } else {
+            format!("{} commits", count)
+        }
+    };
+    let press = |action, verb: &str| {
+        let keys = keymap.labels_for(action);
+        if keys.is_empty() {
+            String::new()
+        } else {
+            format!("; press {} to {}", keys.join("/"), verb)
+        }
+    };
+    match head_status.ahead_behind? {
+        (0, 0) => None,
+        (0, behind) => Some(format!(
+            "{} behind {}{}",
+            commits(behind),
+            upstream,
+            press(Action::Pull, "pull")
+        )),
+        (ahead, 0) => Some(format!(
+            "{} ahead of {}{}",
+            commits(ahead),
+            upstream,
+            press(Action::Push, "push")
+        )),
+        (ahead, behind) => Some(format!(
+            "{} ahead of and {} behind {}; the branches have diverged",
+            commits(ahead),
+            behind,
+            upstream
+        )),
+    }
+}
+
+/// The help for the actions of the key map, by topic.
+const HELP_SECTIONS: &[(&str, &[(Action, &str)])] = &[
+    (
+        "Navigation",
+        &[
+            (
+                Action::Up,
+                "Move up, or scroll the Details or debug pane up",
+            ),
+            (
+                Action::Down,
+                "Move down, or scroll the Details or debug pane down",
+            ),
+            (Action::PageUp, "Move or scroll up a page"),
+            (Action::PageDown, "Move or scroll down a page"),
+            (
+                Action::FocusFiles,
+                "Focus the file list (scrolls left first when the Details pane doesn't wrap)",
+            ),
+            (
+                Action::FocusDetails,
+                "Focus the Details pane (then scrolls right when it doesn't wrap)",
+            ),
+            (
+                Action::CycleFocus,
+                "Focus the next pane: file list, Details, then debug when it is shown",
+            ),
+            (
+                Action::NarrowFileList,
+                "Make the file list narrower (or drag its right border)",
+            ),
+            (Action::WidenFileList, "Make the file list wider"),
+            (
+                Action::Select,
+                "Expand/collapse the directory or show the file's diff",
+            ),
+            (Action::NextChange, "Jump to the next changed file"),
+            (Action::PreviousChange, "Jump to the previous changed file"),
+            (Action::Search, "Search the file list"),
+            (
+                Action::NextMatch,
+                "Jump to the next search match (Esc clears the search)",
+            ),
+            (Action::PreviousMatch, "Jump to the previous search match"),
+            (
+                Action::CycleFilter,
+                "Cycle the file list filter (modified, untracked, staged, all)",
+            ),
+            (
+                Action::CycleSort,
+                "Cycle the file list order (name, status, last modified)",
+            ),
+            (Action::ToggleIgnored, "Show or hide ignored files"),
+            (
+                Action::ToggleFileInfo,
+                "Show or hide the size and modification time of each file",
+            ),
+            (
+                Action::Refresh,
+                "Re-read the file list and the selected diff, for changes made out of sight",
+            ),
+        ],
+    ),
+    (
+        "Staging",
+        &[
+            (Action::Stage, "Stage the selected file"),
+            (Action::Unstage, "Unstage the selected file"),
+            (Action::UnstageAll, "Unstage everything"),
+            (Action::NextHunk, "Select the next hunk (Details pane)"),
+            (
+                Action::PreviousHunk,
+                "Select the previous hunk (Details pane)",
+            ),
+            (
+                Action::ToggleHunk,
+                "Stage/unstage the selected hunk (Details pane)",
+            ),
+            (
+                Action::SelectLines,
+                "Pick lines of the selected hunk, then stage/unstage just those",
+            ),
+            (
+                Action::TakeOurs,
+                "Resolve the selected conflicted file with our version",
+            ),
+            (
+                Action::TakeTheirs,
+                "Resolve the selected conflicted file with their version",
+            ),
+            (
+                Action::Stash,
+                "Stash all changes, including untracked files",
+            ),
+            (Action::StashList, "Open the stash list"),
+        ],
+    ),
+    (
+        "Details pane",
+        &[
+            (Action::ToggleSplitView, "Toggle the side-by-side diff view"),
+            (Action::ToggleWrap, "Toggle wrapping of long lines"),
+            (Action::FullDiff, "Show all of a diff that was cut short"),
+            (
+                Action::MoreContext,
+                "Show more context lines around changes",
+            ),
+            (
+                Action::LessContext,
+                "Show fewer context lines around changes",
+            ),
+            (
+                Action::CycleWhitespace,
+                "Cycle hiding whitespace changes: at line ends, in amount, all of them",
+            ),
+            (
+                Action::Blame,
+                "Blame the committed version of the selected file (Enter shows the diff again)",
+            ),
+            (
+                Action::ShowCommit,
+                "Show a commit given by id, branch, tag or an expression like HEAD~2",
+            ),
+            (
+                Action::CopyDetails,
+                "Copy the Details pane to the clipboard",
+            ),
+            (
+                Action::CopyPath,
+                "Copy the selected file's path to the clipboard",
+            ),
+        ],
+    ),
+    (
+        "Commits",
+        &[
+            (
+                Action::Commit,
+                "Open the commit dialog to commit what is staged",
+            ),
+            (Action::History, "Toggle the commit history"),
+            (
+                Action::Reflog,
+                "Open the HEAD reflog, to reset back to where HEAD was before",
+            ),
+            (
+                Action::Revert,
+                "Revert the selected commit (commit history)",
+            ),
+            (
+                Action::Reset,
+                "Reset the branch to the selected commit (commit history): soft, mixed or hard",
+            ),
+            (
+                Action::Rebase,
+                "Rebase from the selected commit (commit history), or continue a stopped rebase",
+            ),
+            (
+                Action::CherryPick,
+                "Cherry-pick a commit, branch or tag onto the current branch",
+            ),
+            (
+                Action::NewTag,
+                "Tag HEAD, or the selected commit in the commit history",
+            ),
+            (
+                Action::ToggleDates,
+                "Show relative or absolute commit dates (commit history)",
+            ),
+            (
+                Action::BisectBad,
+                "Mark HEAD, or the selected commit in the commit history, bad, starting a bisect",
+            ),
+            (
+                Action::BisectGood,
+                "Mark HEAD, or the selected commit in the commit history, good",
+            ),
+            (
+                Action::BisectReset,
+                "End the bisect and go back to where HEAD was before it",
+            ),
+        ],
+    ),
+    (
+        "Branches and remotes",
+        &[
+            (Action::Branches, "Open the branch list"),
+            (Action::NewBranch, "Create a new branch at HEAD"),
+            (Action::Push, "Push the current branch to its upstream"),
+            (Action::Fetch, "Fetch from origin"),
+            (Action::Pull, "Pull (fast-forward only) from the upstream"),
+            (Action::Submodules, "Open the submodule list"),
+        ],
+    ),
+    (
+        "General",
+        &[
+            (Action::Help, "Toggle this help"),
+            (Action::ToggleDebug, "Toggle the debug pane"),
+            (Action::Close, "Close the open dialog or view"),
+            (Action::Quit, "Quit the application"),
+        ],
+    ),
+];
+
+/// Keys that only mean something inside a dialog or view.
+const DIALOG_HELP: &str = "
+    In branch list:
+    ↑/↓: Select a branch
+    Enter: Switch to the selected branch
+    m: Merge the selected branch into the current one
+    x/Delete: Delete the selected branch if it is merged
+    D: Delete the selected branch even if it isn't merged
+    r: Rename the selected branch
+    Esc: Close the branch list
+
+    In search prompt:
+    Enter: Keep the search and return to the file list
+    Esc: Cancel the search
+
+    In rebase todo list:
+    p/r/s: Pick, reword or squash the selected commit into the one above
+    J/K: Move the selected commit down/up
+    Enter: Start the rebase, or continue it once conflicts are staged
+    a: Abort a stopped rebase
+    Esc: Close the list
+
+    In stash list:
+    Enter: Apply and drop the selected stash
+    a: Apply the selected stash, keeping it
+    x/Delete: Drop the selected stash
+    Esc: Close the stash list
+
+    In reflog:
+    ↑/↓: Select an entry
+    Enter: Reset the branch to the selected entry: soft, mixed or hard
+    Esc: Close the reflog
+
+    In submodule list:
+    ↑/↓: Select a submodule
+    Enter: Initialize or update the selected submodule
+    Esc: Close the submodule list
+
+    In new branch dialog:
+    Enter: Create the branch
+    Tab: Toggle switching to the new branch
+    Esc: Cancel
+
+    In commit history:
+    ↑/↓: Select a commit
+    Enter: Show the commit's diff
+    Esc: Back to the file list
 
     In commit dialog:
-    Enter: Confirm commit
-    Esc: Cancel commit
-    "
-    .trim()
-    .to_string()
+    Ctrl+S: Confirm commit
+    Enter: Start a new line
+    Arrows, Home/End: Move the cursor
+    Ctrl+E: Commit even though the message is empty
+    Ctrl+A: Toggle amending the last commit
+    Ctrl+P: Cycle through the commit prefixes, like feat: and fix:
+    Ctrl+O: Finish the message in your editor
+    Ctrl+D: Preview the diff of what will be committed
+    Esc: Cancel commit, keeping the message, e.g. to unstage a file first
+";
+
+/// The help text, with the keys the actions are actually bound to. Keys that only mean
+/// something inside a dialog aren't configurable and are listed as they are.
+fn help_content(keymap: &KeyMap) -> String {
+    let keys = |action| keymap.labels_for(action).join("/");
+    let mut help = String::new();
+    for (title, entries) in HELP_SECTIONS {
+        help.push_str(title);
+        help.push_str(":\n");
+        for (action, description) in entries.iter() {
+            let keys = keys(*action);
+            if !keys.is_empty() {
+                help.push_str(&format!("{}: {}\n", keys, description));
+            }
+        }
+        help.push('\n');
+    }
+    for line in DIALOG_HELP.trim().lines() {
+        help.push_str(line.trim());
+        help.push('\n');
+    }
+    help
+}
+
+/// Where the entry at `index` of the `previous` list is in `files`. When it is gone, like a
+/// file filtered out once staged, the closest one around it that is still there, looking
+/// below it first.
+fn reselect(previous: &[String], index: usize, files: &[FileEntry]) -> Option<usize> {
+    let positions: HashMap<&str, usize> = files
+        .iter()
+        .enumerate()
+        .map(|(position, file)| (file.name.as_str(), position))
+        .collect();
+    let listed = |i: usize| {
+        previous
+            .get(i)
+            .and_then(|name| positions.get(name.as_str()).copied())
+    };
+    (0..previous.len()).find_map(|distance| {
+        listed(index + distance).or_else(|| index.checked_sub(distance).and_then(listed))
+    })
+}
+
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    (area.x..area.x + area.width).contains(&column) && (area.y..area.y + area.height).contains(&row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestRepo;
+
+    fn test_app(test: &TestRepo) -> App {
+        App::new(&test.repo, KeyMap::default(), Config::default()).unwrap()
+    }
+
+    /// Hands the App what the worker sends back until it has what the test waits for.
+    fn wait_for(app: &mut App, repo: &Repository, done: impl Fn(&App) -> bool) {
+        let start = Instant::now();
+        while !done(app) {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "the worker didn't answer"
+            );
+            app.handle_worker_results(repo);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn diverged(ahead: usize, behind: usize) -> HeadStatus {
+        HeadStatus {
+            branch: "main".to_string(),
+            detached: false,
+            upstream: Some("origin/main".to_string()),
+            ahead_behind: Some((ahead, behind)),
+            tags: Vec::new(),
+            last_commit: None,
+        }
+    }
+
+    #[test]
+    fn sync_hint_names_the_configured_keys() {
+        let keymap = KeyMap::default();
+        assert_eq!(
+            sync_hint(&diverged(0, 2), &keymap).as_deref(),
+            Some("2 commits behind origin/main; press p to pull")
+        );
+        assert_eq!(
+            sync_hint(&diverged(1, 0), &keymap).as_deref(),
+            Some("1 commit ahead of origin/main; press P to push")
+        );
+        assert_eq!(sync_hint(&diverged(0, 0), &keymap), None);
+    }
+
+    #[test]
+    fn sync_hint_leaves_out_unbound_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.toml");
+        // Giving `p` to push leaves pull without a key.
+        std::fs::write(&path, "push = \"p\"\n").unwrap();
+        let (keymap, warnings) = KeyMap::load(&path);
+        assert!(warnings.is_empty());
+        assert_eq!(
+            sync_hint(&diverged(0, 2), &keymap).as_deref(),
+            Some("2 commits behind origin/main")
+        );
+        assert_eq!(
+            sync_hint(&diverged(1, 0), &keymap).as_deref(),
+            Some("1 commit ahead of origin/main; press p to push")
+        );
+    }
+
+    #[test]
+    fn reads_the_file_list_on_the_worker() {
+        let test = TestRepo::new();
+        let mut app = test_app(&test);
+        assert!(app.files.is_empty());
+        test.write("new.txt", "new\n");
+        app.request_file_list();
+        wait_for(&mut app, &test.repo, |app| !app.files.is_empty());
+        assert_eq!(app.files[0].name, "new.txt");
+        assert_eq!(app.status_summary.untracked, 1);
+    }
+
+    #[test]
+    fn drops_a_file_list_read_before_the_last_refresh() {
+        let test = TestRepo::new();
+        let mut app = test_app(&test);
+        test.write("new.txt", "new\n");
+        app.request_file_list();
+        let request = app.pending_status.clone().unwrap();
+        let mut expanded_dirs = HashMap::new();
+        let (files, summary) =
+            get_file_list(&test.repo, app.list_options, &mut expanded_dirs).unwrap();
+        std::fs::remove_file(test.path("new.txt")).unwrap();
+        app.poll_file_list(&test.repo);
+        app.finish_file_list(&test.repo, request, Ok((files, summary, expanded_dirs)));
+        assert!(app.files.is_empty());
+    }
+
+    #[test]
+    fn reads_the_diff_on_the_worker() {
+        let test = TestRepo::new();
+        test.write("file.txt", "one\n");
+        test.stage("file.txt");
+        test.commit("first");
+        test.write("file.txt", "one\ntwo\n");
+        let mut app = test_app(&test);
+        app.request_details(&test.repo);
+        // Nothing to stage until the diff is in.
+        assert!(app.diff_hunks.is_empty());
+        wait_for(&mut app, &test.repo, |app| !app.diff_hunks.is_empty());
+        assert!(app
+            .right_pane_lines
+            .iter()
+            .any(|line| line.kind == LineKind::Addition && line.content == "two"));
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    /// A repository with a file staged for its first commit, and an App with the commit
+    /// modal open on `message`.
+    fn committing(message: &str) -> (TestRepo, App) {
+        let test = TestRepo::new();
+        test.write("file.txt", "content\n");
+        test.stage("file.txt");
+        let mut app = test_app(&test);
+        app.commit_modal.is_visible = true;
+        app.commit_modal.set_content(message.to_string());
+        (test, app)
+    }
+
+    #[test]
+    fn refuses_to_commit_without_a_message() {
+        for message in ["", "  \n\n", "# Please enter the commit message\n#\n"] {
+            let (test, mut app) = committing(message);
+            app.handle_key_event(ctrl('s'), &test.repo).unwrap();
+            assert!(test.repo.head().is_err(), "committed with {:?}", message);
+            assert!(app.commit_modal.is_visible);
+            assert_eq!(
+                app.commit_modal.error.as_deref(),
+                Some("The commit message is empty; press Ctrl+E to commit anyway")
+            );
+        }
+    }
+
+    #[test]
+    fn ctrl_e_commits_without_a_message() {
+        let (test, mut app) = committing("# Please enter the commit message\n");
+        app.handle_key_event(ctrl('e'), &test.repo).unwrap();
+        let head = test.repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.message().map(str::trim), Some(""));
+        assert!(!app.commit_modal.is_visible);
+        assert_eq!(app.commit_modal.error, None);
+    }
+
+    #[test]
+    fn commits_with_a_message() {
+        let (test, mut app) = committing("Add a file\n# Please enter the commit message\n");
+        app.handle_key_event(ctrl('s'), &test.repo).unwrap();
+        let head = test.repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.summary(), Some("Add a file"));
+        assert!(!app.commit_modal.is_visible);
+    }
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    /// A repository with two committed files, both changed since.
+    fn two_modified() -> TestRepo {
+        let test = TestRepo::new();
+        test.write("one.txt", "one\n");
+        test.write("two.txt", "two\n");
+        test.stage("one.txt");
+        test.stage("two.txt");
+        test.commit("first");
+        test.write("one.txt", "one changed\n");
+        test.write("two.txt", "two changed\n");
+        test
+    }
+
+    fn commit_paths(app: &App) -> Vec<&str> {
+        app.commit_files
+            .iter()
+            .map(|file| file.path.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn commit_key_commits_only_what_is_staged() {
+        let test = two_modified();
+        test.stage("one.txt");
+        let mut app = test_app(&test);
+        app.handle_key_event(key('c'), &test.repo).unwrap();
+        assert!(app.commit_modal.is_visible);
+        assert_eq!(commit_paths(&app), ["one.txt"]);
+    }
+
+    #[test]
+    fn a_file_unstaged_after_esc_is_left_out_of_the_commit() {
+        let test = two_modified();
+        test.stage("one.txt");
+        test.stage("two.txt");
+        let mut app = test_app(&test);
+        app.handle_key_event(key('c'), &test.repo).unwrap();
+        app.handle_key_event(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE), &test.repo)
+            .unwrap();
+        assert!(!app.commit_modal.is_visible);
+        app.selected_index = app
+            .files
+            .iter()
+            .position(|file| file.name == "two.txt")
+            .unwrap();
+        app.handle_key_event(key('u'), &test.repo).unwrap();
+        app.handle_key_event(key('c'), &test.repo).unwrap();
+        assert_eq!(commit_paths(&app), ["one.txt"]);
+    }
+
+    #[test]
+    fn returning_from_the_preview_stages_nothing_more() {
+        let test = two_modified();
+        test.stage("one.txt");
+        let mut app = test_app(&test);
+        app.handle_key_event(key('c'), &test.repo).unwrap();
+        app.handle_key_event(ctrl('d'), &test.repo).unwrap();
+        assert!(!app.commit_modal.is_visible);
+        test.write("two.txt", "changed during the preview\n");
+        app.handle_key_event(key('c'), &test.repo).unwrap();
+        assert!(app.commit_modal.is_visible);
+        assert_eq!(commit_paths(&app), ["one.txt"]);
+        let index = test.repo.index().unwrap();
+        let head = test.repo.head().unwrap().peel_to_tree().unwrap();
+        let staged = test
+            .repo
+            .diff_tree_to_index(Some(&head), Some(&index), None)
+            .unwrap();
+        assert_eq!(staged.deltas().len(), 1);
+    }
 }
@@ -0,0 +1,141 @@
+use git2::{Oid, Repository, Sort};
+
+use crate::rebase::has_tracked_changes;
+
+/// Where a bisect stands after the latest mark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BisectProgress {
+    /// A bad commit has been marked but no good one yet.
+    NeedsGood,
+    /// A good commit has been marked but no bad one yet.
+    NeedsBad,
+    /// The commit checked out to be tested, with about this many steps left, counting it.
+    Testing { commit: Oid, steps: u32 },
+    /// The first bad commit.
+    Found(Oid),
+}
+
+/// What HEAD was before the bisect started, to go back to at the end.
+enum OrigHead {
+    Branch(String),
+    Detached(Oid),
+}
+
+/// A bisect in progress, done by halving the commits between the good and the bad ones
+/// with a revwalk, since git2 has no bisect of its own. Like `Rebase`, the state only lives
+/// as long as gitui does.
+pub struct Bisect {
+    bad: Option<Oid>,
+    good: Vec<Oid>,
+    orig_head: OrigHead,
+    progress: BisectProgress,
+}
+
+impl Bisect {
+    pub fn start(repo: &Repository) -> Result<Self, git2::Error> {
+        if has_tracked_changes(repo)? {
+            return Err(git2::Error::from_str(
+                "there are uncommitted changes; commit or stash them first",
+            ));
+        }
+        let head = repo
+            .head()
+            .map_err(|_| git2::Error::from_str("HEAD has no commit"))?;
+        let orig_head = match head.target() {
+            Some(oid) if repo.head_detached()? => OrigHead::Detached(oid),
+            _ => OrigHead::Branch(head.name().unwrap_or_default().to_string()),
+        };
+        Ok(Self {
+            bad: None,
+            good: Vec::new(),
+            orig_head,
+            progress: BisectProgress::NeedsBad,
+        })
+    }
+
+    /// Marks `oid` as good or bad and, once both are known, checks out the next commit to
+    /// test. On failure nothing is marked.
+    pub fn mark(
+        &mut self,
+        repo: &Repository,
+        oid: Oid,
+        good: bool,
+    ) -> Result<BisectProgress, git2::Error> {
+        let (mut bad, mut goods) = (self.bad, self.good.clone());
+        if good {
+            goods.push(oid);
+        } else {
+            bad = Some(oid);
+        }
+        let progress = match bad {
+            None => BisectProgress::NeedsBad,
+            Some(_) if goods.is_empty() => BisectProgress::NeedsGood,
+            Some(bad) => {
+                for &good in &goods {
+                    if good == bad || repo.graph_descendant_of(good, bad)? {
+                        return Err(git2::Error::from_str(&format!(
+                            "good commit {:.7} is not an ancestor of bad commit {:.7}",
+                            good, bad
+                        )));
+                    }
+                }
+                next_step(repo, bad, &goods)?
+            }
+        };
+        if let BisectProgress::Testing { commit, .. } = progress {
+            checkout_detached(repo, commit)?;
+        }
+        self.bad = bad;
+        self.good = goods;
+        self.progress = progress;
+        Ok(progress)
+    }
+
+    pub fn progress(&self) -> BisectProgress {
+        self.progress
+    }
+
+    /// Ends the bisect, putting HEAD back where it was before it started.
+    pub fn reset(self, repo: &Repository) -> Result<(), git2::Error> {
+        let (target, branch) = match &self.orig_head {
+            OrigHead::Branch(name) => (repo.refname_to_id(name)?, Some(name)),
+            OrigHead::Detached(oid) => (*oid, None),
+        };
+        checkout_detached(repo, target)?;
+        if let Some(name) = branch {
+            repo.set_head(name)?;
+        }
+        Ok(())
+    }
+}
+
+/// The commits that may be the first bad one are those reachable from `bad` but from
+/// none of the good ones. The one halfway down that list, newest first, splits it in two.
+fn next_step(repo: &Repository, bad: Oid, goods: &[Oid]) -> Result<BisectProgress, git2::Error> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL)?;
+    revwalk.push(bad)?;
+    for &good in goods {
+        revwalk.hide(good)?;
+    }
+    let candidates = revwalk.collect::<Result<Vec<Oid>, _>>()?;
+    if candidates.len() <= 1 {
+        return Ok(BisectProgress::Found(bad));
+    }
+    let midpoint = candidates.len() / 2;
+    // Whichever half the test rules out, at most this many candidates are left.
+    let left = candidates.len() - midpoint;
+    Ok(BisectProgress::Testing {
+        commit: candidates[midpoint],
+        steps: left.ilog2() + 1,
+    })
+}
+
+fn checkout_detached(repo: &Repository, oid: Oid) -> Result<(), git2::Error> {
+    let commit = repo.find_commit(oid)?;
+    repo.checkout_tree(
+        commit.as_object(),
+        Some(git2::build::CheckoutBuilder::new().safe()),
+    )?;
+    repo.set_head_detached(oid)
+}
@@ -0,0 +1,35 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Clipboard programs in the order they are tried, with their arguments.
+const CLIPBOARD_COMMANDS: [(&str, &[&str]); 4] = [
+    ("pbcopy", &[]),
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+];
+
+/// Puts `text` on the system clipboard through the first clipboard program that works.
+/// Fails with "clipboard unavailable" when there is none, as on a headless machine.
+pub fn copy(text: &str) -> Result<(), String> {
+    for (program, args) in CLIPBOARD_COMMANDS {
+        let Ok(mut child) = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        let written = child
+            .stdin
+            .take()
+            .is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+        // xclip and xsel fail here without a display to talk to.
+        if child.wait().is_ok_and(|status| status.success()) && written {
+            return Ok(());
+        }
+    }
+    Err("clipboard unavailable".to_string())
+}
@@ -0,0 +1,227 @@
+use std::path::{Path, PathBuf};
+
+use crate::config_file::{self, Value};
+use crate::theme::{parse_git_color, DiffColors, Theme};
+
+/// General settings read from `config.toml`:
+///
+/// ```toml
+/// theme = "dark"
+/// debug_mode = false
+/// debug_log_lines = 1000
+/// show_untracked = true
+/// confirm_quit = true
+///
+/// [layout]
+/// file_list_width = 30
+/// debug_pane_width = 35
+///
+/// [diff]
+/// context_lines = 3
+/// max_lines = 5000
+///
+/// [colors]
+/// addition = "green"
+/// deletion = "red"
+///
+/// [commit]
+/// editor = false
+/// require_signing = false
+/// prefixes = ["feat", "fix", "docs", "refactor", "test", "chore"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Width of the file list as a percentage of the terminal.
+    pub file_list_width: u16,
+    /// Width of the debug pane as a percentage of the terminal, when it is shown.
+    pub debug_pane_width: u16,
+    pub show_untracked: bool,
+    /// Whether quitting with uncommitted changes asks first.
+    pub confirm_quit: bool,
+    /// Whether the debug pane is open at startup.
+    pub debug_mode: bool,
+    /// Lines the debug pane keeps before dropping the oldest.
+    pub debug_log_lines: usize,
+    pub context_lines: u32,
+    /// Lines of a file's diff shown before it is cut short, until the whole of it is asked for.
+    pub max_diff_lines: usize,
+    /// Whether `c` opens the commit message in the external editor rather than the modal.
+    pub commit_in_editor: bool,
+    /// Whether a commit that `commit.gpgsign` asks to sign fails when signing does, rather
+    /// than being made unsigned.
+    pub require_signing: bool,
+    /// Conventional-commit types that Ctrl+P cycles through in the commit modal.
+    pub commit_prefixes: Vec<String>,
+    pub theme: Theme,
+    /// Diff colours that replace the theme's and git's `color.diff.*`.
+    pub colors: DiffColors,
+}
+
+/// The most context lines a diff can be shown with, from the config file or the `+` key.
+pub const MAX_CONTEXT_LINES: u32 = 100;
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            file_list_width: 30,
+            debug_pane_width: 35,
+            show_untracked: true,
+            confirm_quit: true,
+            debug_mode: false,
+            debug_log_lines: 1000,
+            context_lines: 3,
+            max_diff_lines: 5000,
+            commit_in_editor: false,
+            require_signing: false,
+            commit_prefixes: ["feat", "fix", "docs", "refactor", "test", "chore"]
+                .map(String::from)
+                .to_vec(),
+            theme: Theme::default(),
+            colors: DiffColors::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        Some(config_file::config_dir()?.join("config.toml"))
+    }
+
+    /// Loads settings from `path`. A missing file gives the defaults; invalid entries are
+    /// reported as warnings and keep their default value.
+    pub fn load(path: &Path) -> (Self, Vec<String>) {
+        let mut config = Self::default();
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return (config, Vec::new()),
+            Err(e) => return (config, vec![format!("{}: {}", path.display(), e)]),
+        };
+        let entries = match config_file::parse(&text) {
+            Ok(entries) => entries,
+            Err(e) => return (config, vec![format!("{}: {}", path.display(), e)]),
+        };
+
+        let mut warnings = Vec::new();
+        for entry in entries {
+            let value = &entry.value;
+            let result = match (entry.section.as_str(), entry.key.as_str()) {
+                ("", "theme") => read_theme(value).map(|v| config.theme = v),
+                ("", "debug_mode") => read_bool(value).map(|v| config.debug_mode = v),
+                ("", "debug_log_lines") => {
+                    read_number(value, 10, 100_000).map(|v| config.debug_log_lines = v as usize)
+                }
+                ("", "show_untracked") => read_bool(value).map(|v| config.show_untracked = v),
+                ("", "confirm_quit") => read_bool(value).map(|v| config.confirm_quit = v),
+                ("layout", "file_list_width") => {
+                    read_number(value, 10, 90).map(|v| config.file_list_width = v as u16)
+                }
+                ("layout", "debug_pane_width") => {
+                    read_number(value, 10, 50).map(|v| config.debug_pane_width = v as u16)
+                }
+                ("diff", "context_lines") => {
+                    read_number(value, 0, MAX_CONTEXT_LINES).map(|v| config.context_lines = v)
+                }
+                ("diff", "max_lines") => {
+                    read_number(value, 100, 1_000_000).map(|v| config.max_diff_lines = v as usize)
+                }
+                ("colors", "addition") => read_color(value).map(|v| config.colors.addition = v),
+                ("colors", "addition_background") => {
+                    read_color(value).map(|v| config.colors.addition_background = v)
+                }
+                ("colors", "deletion") => read_color(value).map(|v| config.colors.deletion = v),
+                ("colors", "deletion_background") => {
+                    read_color(value).map(|v| config.colors.deletion_background = v)
+                }
+                ("colors", "hunk_header") => {
+                    read_color(value).map(|v| config.colors.hunk_header = v)
+                }
+                ("colors", "commit_id") => read_color(value).map(|v| config.colors.commit_id = v),
+                ("commit", "editor") => read_bool(value).map(|v| config.commit_in_editor = v),
+                ("commit", "require_signing") => {
+                    read_bool(value).map(|v| config.require_signing = v)
+                }
+                ("commit", "prefixes") => read_strings(value).map(|v| config.commit_prefixes = v),
+                _ => Err("unknown setting".to_string()),
+            };
+            if let Err(message) = result {
+                let name = if entry.section.is_empty() {
+                    entry.key.clone()
+                } else {
+                    format!("{}.{}", entry.section, entry.key)
+                };
+                warnings.push(format!(
+                    "{}:{}: {}: {}",
+                    path.display(),
+                    entry.line,
+                    name,
+                    message
+                ));
+            }
+        }
+
+        if config.file_list_width + config.debug_pane_width > 90 {
+            warnings.push(format!(
+                "{}: file_list_width and debug_pane_width leave no room for the Details pane",
+                path.display()
+            ));
+            config.file_list_width = Self::default().file_list_width;
+            config.debug_pane_width = Self::default().debug_pane_width;
+        }
+
+        (config, warnings)
+    }
+}
+
+fn read_bool(value: &Value) -> Result<bool, String> {
+    match value {
+        Value::Boolean(value) => Ok(*value),
+        _ => Err("expected true or false".to_string()),
+    }
+}
+
+fn read_strings(value: &Value) -> Result<Vec<String>, String> {
+    let expected = || "expected a list of strings".to_string();
+    match value {
+        Value::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::String(s) => Ok(s.clone()),
+                _ => Err(expected()),
+            })
+            .collect(),
+        _ => Err(expected()),
+    }
+}
+
+fn read_theme(value: &Value) -> Result<Theme, String> {
+    match value {
+        Value::String(name) => Theme::from_name(name)
+            .ok_or_else(|| format!("unknown theme `{}`, expected \"dark\" or \"light\"", name)),
+        _ => Err("expected \"dark\" or \"light\"".to_string()),
+    }
+}
+
+/// A colour written as git writes them: a name like `red` or `brightblue`, a number from
+/// 0 to 255 or `#rrggbb`.
+fn read_color(value: &Value) -> Result<Option<ratatui::style::Color>, String> {
+    let expected = || "expected a colour like \"red\", 214 or \"#ffaa00\"".to_string();
+    match value {
+        Value::String(s) => match parse_git_color(s) {
+            Some((Some(color), None)) => Ok(Some(color)),
+            _ => Err(expected()),
+        },
+        Value::Integer(index) => u8::try_from(*index)
+            .map(|index| Some(ratatui::style::Color::Indexed(index)))
+            .map_err(|_| expected()),
+        _ => Err(expected()),
+    }
+}
+
+fn read_number(value: &Value, min: u32, max: u32) -> Result<u32, String> {
+    match value {
+        Value::Integer(value) if (i64::from(min)..=i64::from(max)).contains(value) => {
+            Ok(*value as u32)
+        }
+        _ => Err(format!("expected a number from {} to {}", min, max)),
+    }
+}
@@ -0,0 +1,47 @@
+use git2::Repository;
+use std::process::Command;
+
+use crate::git_ops::strip_comments;
+
+/// Shown below the draft, as `git commit` does.
+const INSTRUCTIONS: &str = "
+# Please enter the commit message for your changes. Lines starting
+# with '#' will be ignored, and an empty message aborts the commit.
+";
+
+/// The editor git would use: `GIT_EDITOR`, `core.editor`, `VISUAL`, `EDITOR`, then `vi`.
+fn editor_command(repo: &Repository) -> String {
+    let from_env = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+    from_env("GIT_EDITOR")
+        .or_else(|| {
+            repo.config()
+                .ok()
+                .and_then(|config| config.get_string("core.editor").ok())
+        })
+        .or_else(|| from_env("VISUAL"))
+        .or_else(|| from_env("EDITOR"))
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// Lets the user write a commit message in their editor, starting from `draft`, and returns
+/// it without comment lines. The terminal has to be handed over to the editor beforehand.
+pub fn compose_message(repo: &Repository, draft: &str) -> Result<String, String> {
+    let path = repo.path().join("COMMIT_EDITMSG");
+    std::fs::write(&path, format!("{}\n{}", draft, INSTRUCTIONS))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let editor = editor_command(repo);
+    // Through the shell, like git, so that editors configured with arguments work.
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(&editor)
+        .arg(&path)
+        .current_dir(repo.workdir().unwrap_or(repo.path()))
+        .status()
+        .map_err(|e| format!("could not start {}: {}", editor, e))?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", editor, status));
+    }
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(strip_comments(&text))
+}
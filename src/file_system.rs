@@ -1,53 +1,383 @@
 use git2::{Repository, Status};
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::SystemTime;
 
-use crate::debug;
+use crate::git_ops::INDEX_STATUSES;
 
+/// One row of the file tree. `name` is the path relative to the working directory and
+/// `depth` the number of directories above the entry.
+#[derive(Debug)]
 pub struct FileEntry {
     pub name: String,
     pub status: Status,
+    /// The index and working tree columns of `git status --short`, blank for directories.
+    pub status_code: [char; 2],
     pub is_dir: bool,
+    pub depth: usize,
+    /// Size in bytes and modification time, read only when the list shows them or is sorted
+    /// by time. `None` for directories and for files that no longer exist.
+    pub metadata: Option<(u64, SystemTime)>,
 }
 
-pub fn get_file_list(repo: &Repository) -> Vec<FileEntry> {
-    let mut files = Vec::new();
-    let mut file_set = HashSet::new();
+impl FileEntry {
+    /// The last component of the path, which is what the tree shows.
+    pub fn file_name(&self) -> &str {
+        self.name.rsplit('/').next().unwrap_or(&self.name)
+    }
+}
+
+/// Restricts the file list to entries with a particular kind of change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusFilter {
+    /// Tracked files with unstaged changes.
+    Modified,
+    Untracked,
+    Staged,
+}
+
+impl StatusFilter {
+    /// The filter after `current` when cycling with the filter key, ending back at no filter.
+    pub fn next(current: Option<Self>) -> Option<Self> {
+        match current {
+            None => Some(StatusFilter::Modified),
+            Some(StatusFilter::Modified) => Some(StatusFilter::Untracked),
+            Some(StatusFilter::Untracked) => Some(StatusFilter::Staged),
+            Some(StatusFilter::Staged) => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StatusFilter::Modified => "modified",
+            StatusFilter::Untracked => "untracked",
+            StatusFilter::Staged => "staged",
+        }
+    }
+
+    fn includes(self, status: Status) -> bool {
+        match self {
+            StatusFilter::Modified => status.intersects(
+                Status::WT_MODIFIED
+                    | Status::WT_DELETED
+                    | Status::WT_RENAMED
+                    | Status::WT_TYPECHANGE,
+            ),
+            StatusFilter::Untracked => status.contains(Status::WT_NEW),
+            StatusFilter::Staged => status.intersects(INDEX_STATUSES),
+        }
+    }
+}
+
+/// The order of the entries within each directory of the file list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Directories first, then by name.
+    Name,
+    /// Conflicts, then staged, modified, untracked and ignored files.
+    Status,
+    /// Most recently modified first, a directory by the newest file in it.
+    Modified,
+}
+
+impl SortOrder {
+    pub fn next(self) -> Self {
+        match self {
+            SortOrder::Name => SortOrder::Status,
+            SortOrder::Status => SortOrder::Modified,
+            SortOrder::Modified => SortOrder::Name,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortOrder::Name => "name",
+            SortOrder::Status => "status",
+            SortOrder::Modified => "time",
+        }
+    }
+}
+
+/// Counts of changed files by kind, over the whole repository regardless of the filter. A
+/// file with both staged and unstaged changes counts towards both.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StatusSummary {
+    pub modified: usize,
+    pub untracked: usize,
+    pub staged: usize,
+}
+
+impl StatusSummary {
+    /// Whether anything isn't committed yet, untracked files included.
+    pub fn is_dirty(&self) -> bool {
+        self.modified + self.untracked + self.staged > 0
+    }
+}
+
+/// Which entries `get_file_list` includes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileListOptions {
+    pub show_untracked: bool,
+    /// Whether files matched by `.gitignore` are listed too. Ignored directories are listed
+    /// as one entry rather than with everything inside them.
+    pub show_ignored: bool,
+    pub status_filter: Option<StatusFilter>,
+    pub sort: SortOrder,
+    /// Whether each file's size and modification time are shown.
+    pub show_file_info: bool,
+}
+
+/// Whether the directory at `path` is shown expanded. Directories start out expanded so
+/// that every change is visible until the user collapses something.
+pub fn is_expanded(expanded_dirs: &HashMap<String, bool>, path: &str) -> bool {
+    expanded_dirs.get(path).copied().unwrap_or(true)
+}
+
+struct TreeNode {
+    /// For directories, the union of everything below them.
+    status: Status,
+    is_dir: bool,
+    /// Only read when sorting by modification time or showing it.
+    metadata: Option<(u64, SystemTime)>,
+    /// The newest modification time below a directory, for sorting by time.
+    modified: Option<SystemTime>,
+    children: BTreeMap<String, TreeNode>,
+}
+
+impl Default for TreeNode {
+    fn default() -> Self {
+        Self {
+            status: Status::empty(),
+            is_dir: false,
+            metadata: None,
+            modified: None,
+            children: BTreeMap::new(),
+        }
+    }
+}
 
+/// Lists the changed files as a tree, omitting the contents of collapsed directories, along
+/// with a summary of all changes. Directories that no longer contain changes are forgotten
+/// from `expanded_dirs`, so that one coming back later starts out expanded again.
+pub fn get_file_list(
+    repo: &Repository,
+    options: FileListOptions,
+    expanded_dirs: &mut HashMap<String, bool>,
+) -> Result<(Vec<FileEntry>, StatusSummary), git2::Error> {
+    // Everything comes from libgit2's status rather than a directory walk, so `.git` and,
+    // unless asked for, anything matched by `.gitignore` or `info/exclude` is left out, as
+    // with `git status`.
     let mut opts = git2::StatusOptions::new();
+    opts.include_untracked(options.show_untracked)
+        .recurse_untracked_dirs(true)
+        .include_ignored(options.show_ignored);
 
-    let statuses = repo
-        .statuses(Some(&mut opts))
-        .expect("Couldn't get repository status");
+    let statuses = repo.statuses(Some(&mut opts))?;
 
-    let mut entries_debug = String::new();
+    let mut root = TreeNode::default();
+    let mut summary = StatusSummary::default();
+    // Every directory with a change, including ones the filter hides.
+    let mut directories = HashSet::new();
     for entry in statuses.iter() {
-        entries_debug.push_str(entry.path().unwrap_or_default());
-        entries_debug.push('\n');
-        let path = PathBuf::from(entry.path().unwrap_or_default());
-        let name = path.to_string_lossy().into_owned();
-        let is_dir = path.is_dir();
+        let path = entry.path().unwrap_or_default().trim_end_matches('/');
+        directories.extend(
+            path.match_indices('/')
+                .map(|(end, _)| path[..end].to_string()),
+        );
         let status = entry.status();
+        summary.modified += usize::from(StatusFilter::Modified.includes(status));
+        summary.untracked += usize::from(StatusFilter::Untracked.includes(status));
+        summary.staged += usize::from(StatusFilter::Staged.includes(status));
+        if options
+            .status_filter
+            .is_some_and(|filter| !filter.includes(status))
+        {
+            continue;
+        }
 
-        if !file_set.contains(&name) {
-            files.push(FileEntry {
-                name: name.clone(),
-                status,
-                is_dir,
-            });
-            file_set.insert(name.clone());
+        // Deleted files have no metadata, and go last when sorting by time.
+        let needs_metadata = options.sort == SortOrder::Modified || options.show_file_info;
+        let metadata = match repo.workdir() {
+            Some(workdir) if needs_metadata => std::fs::symlink_metadata(workdir.join(path))
+                .and_then(|metadata| Ok((metadata.len(), metadata.modified()?)))
+                .ok(),
+            _ => None,
+        };
+        let modified = metadata.map(|(_, modified)| modified);
+        let mut node = &mut root;
+        for component in path.split('/') {
+            node.is_dir = true;
+            node.status |= status;
+            node.modified = node.modified.max(modified);
+            node = node.children.entry(component.to_string()).or_default();
         }
+        node.status |= status;
+        node.metadata = metadata;
+        node.modified = modified;
     }
 
-    debug::debug_log(&entries_debug);
+    expanded_dirs.retain(|name, _| directories.contains(name));
+
+    let mut files = Vec::new();
+    flatten(&root, "", 0, options.sort, expanded_dirs, &mut files);
+    Ok((files, summary))
+}
+
+/// The two status letters `git status --short` shows for a file: what is staged, then what
+/// is changed in the working tree but not staged.
+pub fn status_code(status: Status) -> [char; 2] {
+    if status.contains(Status::WT_NEW) {
+        return ['?', '?'];
+    }
+    if status.contains(Status::IGNORED) {
+        return ['!', '!'];
+    }
+    if status.contains(Status::CONFLICTED) {
+        return ['U', 'U'];
+    }
+    let index = if status.contains(Status::INDEX_NEW) {
+        'A'
+    } else if status.contains(Status::INDEX_DELETED) {
+        'D'
+    } else if status.contains(Status::INDEX_RENAMED) {
+        'R'
+    } else if status.contains(Status::INDEX_TYPECHANGE) {
+        'T'
+    } else if status.contains(Status::INDEX_MODIFIED) {
+        'M'
+    } else {
+        ' '
+    };
+    let worktree = if status.contains(Status::WT_DELETED) {
+        'D'
+    } else if status.contains(Status::WT_RENAMED) {
+        'R'
+    } else if status.contains(Status::WT_TYPECHANGE) {
+        'T'
+    } else if status.contains(Status::WT_MODIFIED) {
+        'M'
+    } else {
+        ' '
+    };
+    [index, worktree]
+}
+
+/// How early an entry with `status` comes when sorting by status; directories go by the
+/// most pressing change below them.
+fn status_rank(status: Status) -> u8 {
+    if status.contains(Status::CONFLICTED) {
+        0
+    } else if status.intersects(INDEX_STATUSES) {
+        1
+    } else if StatusFilter::Modified.includes(status) {
+        2
+    } else if status.contains(Status::WT_NEW) {
+        3
+    } else {
+        4
+    }
+}
 
-    files.sort_by(|a, b| {
-        if a.is_dir == b.is_dir {
-            a.name.cmp(&b.name)
+/// Appends the children of `node` in display order, by `sort` and then by name.
+fn flatten(
+    node: &TreeNode,
+    prefix: &str,
+    depth: usize,
+    sort: SortOrder,
+    expanded_dirs: &HashMap<String, bool>,
+    files: &mut Vec<FileEntry>,
+) {
+    let mut children: Vec<_> = node.children.iter().collect();
+    // The map already has them by name, and the sort is stable.
+    children.sort_by(|(_, a), (_, b)| match sort {
+        SortOrder::Name => b.is_dir.cmp(&a.is_dir),
+        SortOrder::Status => status_rank(a.status).cmp(&status_rank(b.status)),
+        SortOrder::Modified => b.modified.cmp(&a.modified),
+    });
+    for (component, child) in children {
+        let name = if prefix.is_empty() {
+            component.clone()
         } else {
-            b.is_dir.cmp(&a.is_dir)
+            format!("{}/{}", prefix, component)
+        };
+        let expanded = child.is_dir && is_expanded(expanded_dirs, &name);
+        files.push(FileEntry {
+            name: name.clone(),
+            status: child.status,
+            status_code: if child.is_dir {
+                [' ', ' ']
+            } else {
+                status_code(child.status)
+            },
+            is_dir: child.is_dir,
+            depth,
+            metadata: child.metadata.filter(|_| !child.is_dir),
+        });
+        if expanded {
+            flatten(child, &name, depth + 1, sort, expanded_dirs, files);
         }
-    });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestRepo;
 
-    files
+    fn options(show_ignored: bool) -> FileListOptions {
+        FileListOptions {
+            show_untracked: true,
+            show_ignored,
+            status_filter: None,
+            sort: SortOrder::Name,
+            show_file_info: false,
+        }
+    }
+
+    fn listed(test: &TestRepo, show_ignored: bool) -> Vec<(String, bool)> {
+        let (files, _) =
+            get_file_list(&test.repo, options(show_ignored), &mut HashMap::new()).unwrap();
+        files
+            .into_iter()
+            .map(|file| (file.name, file.status.contains(Status::IGNORED)))
+            .collect()
+    }
+
+    fn names(files: &[(String, bool)]) -> Vec<&str> {
+        files.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    fn ignored_repo() -> TestRepo {
+        let test = TestRepo::new();
+        test.write(".gitignore", "build/\n*.log\n");
+        test.write("src/main.rs", "fn main() {}\n");
+        test.write("debug.log", "log\n");
+        test.write("build/output.o", "binary\n");
+        test
+    }
+
+    #[test]
+    fn ignored_files_are_left_out() {
+        let test = ignored_repo();
+        let files = listed(&test, false);
+        assert_eq!(names(&files), ["src", "src/main.rs", ".gitignore"]);
+        let (_, summary) = get_file_list(&test.repo, options(false), &mut HashMap::new()).unwrap();
+        assert_eq!(summary.untracked, 2);
+    }
+
+    #[test]
+    fn ignored_files_are_listed_when_asked_for() {
+        let test = ignored_repo();
+        let files = listed(&test, true);
+        // An ignored directory is one entry, without everything inside it.
+        assert_eq!(
+            names(&files),
+            ["src", "src/main.rs", ".gitignore", "build", "debug.log"]
+        );
+        let ignored: Vec<&str> = files
+            .iter()
+            .filter(|(_, ignored)| *ignored)
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(ignored, ["build", "debug.log"]);
+    }
 }
@@ -1,96 +1,2628 @@
 use crate::app::App;
-use git2::{Diff, DiffOptions, Repository, Status};
+use crate::debug;
+use crate::file_system::status_code;
+use crate::keymap::Action;
+use crate::signing::{sign, signing_enabled, verify_commit};
+use crate::time_format::format_absolute;
+use git2::{
+    ApplyLocation, ApplyOptions, BranchType, Commit, Cred, CredentialType, Delta, Diff,
+    DiffFindOptions, DiffOptions, ErrorCode, FetchOptions, Oid, Patch, PushOptions,
+    RemoteCallbacks, Repository, Signature, Sort, StashApplyOptions, StashFlags, Status, Time,
+    Tree,
+};
+use std::collections::HashMap;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 
+/// What a line in the Details pane represents, which decides how it is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    /// Section headings such as "Staged changes:".
+    Header,
+    FileHeader,
+    HunkHeader,
+    Addition,
+    Deletion,
+    Context,
+    /// A line of a blamed file, annotated with the commit it comes from.
+    Blame,
+    /// A line of a conflicted file as it is in the working tree, markers included.
+    Conflict,
+    /// Anything else: messages, commit metadata, "no newline" markers.
+    Text,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    pub content: String,
+    /// Line numbers in the old and new file, for lines inside a hunk.
+    pub old_lineno: Option<u32>,
+    pub new_lineno: Option<u32>,
+    /// Shown ahead of the line, like the commit and author of a blamed line.
+    pub annotation: Option<String>,
+}
+
+impl DiffLine {
+    pub fn new(kind: LineKind, content: impl Into<String>) -> Self {
+        Self {
+            kind,
+            content: content.into(),
+            old_lineno: None,
+            new_lineno: None,
+            annotation: None,
+        }
+    }
+}
+
+/// Appends `text` as one line of `kind` per line of text.
+pub fn push_lines(lines: &mut Vec<DiffLine>, kind: LineKind, text: &str) {
+    lines.extend(text.lines().map(|line| DiffLine::new(kind, line)));
+}
+
+/// A row of the side-by-side diff view, given as indices into the unified lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitRow {
+    /// Headings, hunk headers and messages, which span both columns.
+    Full(usize),
+    /// The old and the new side of a row. Context lines appear on both sides.
+    Pair(Option<usize>, Option<usize>),
+}
+
+/// Lays `lines` out side by side. Each run of deletions is paired up with the additions
+/// that follow it, so that the unchanged lines around them stay level across the columns.
+pub fn split_rows(lines: &[DiffLine]) -> Vec<SplitRow> {
+    let mut rows = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        match lines[index].kind {
+            LineKind::Context => {
+                rows.push(SplitRow::Pair(Some(index), Some(index)));
+                index += 1;
+            }
+            LineKind::Deletion | LineKind::Addition => {
+                let deletions_start = index;
+                while lines
+                    .get(index)
+                    .is_some_and(|line| line.kind == LineKind::Deletion)
+                {
+                    index += 1;
+                }
+                let additions_start = index;
+                while lines
+                    .get(index)
+                    .is_some_and(|line| line.kind == LineKind::Addition)
+                {
+                    index += 1;
+                }
+                let deletions = deletions_start..additions_start;
+                let additions = additions_start..index;
+                for row in 0..deletions.len().max(additions.len()) {
+                    rows.push(SplitRow::Pair(
+                        Some(deletions.start + row).filter(|i| deletions.contains(i)),
+                        Some(additions.start + row).filter(|i| additions.contains(i)),
+                    ));
+                }
+            }
+            _ => {
+                rows.push(SplitRow::Full(index));
+                index += 1;
+            }
+        }
+    }
+    rows
+}
+
+/// A hunk of the patch shown in the Details pane. `start_line` and `line_count` locate the
+/// hunk within `right_pane_lines`; the remaining ranges come from the `@@` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub header: String,
+    pub start_line: usize,
+    pub line_count: usize,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub staged: bool,
+}
+
+/// Untracked files bigger than this are not read into the Details pane.
+const MAX_PREVIEW_BYTES: u64 = 1024 * 1024;
+
+/// Opens the repository containing `path`, looking in its parent directories like git does,
+/// so that gitui can be started from anywhere in the working tree. Bare repositories are
+/// rejected since there is no working tree to show.
+pub fn open_repository(path: &Path) -> Result<Repository, git2::Error> {
+    let repo = Repository::discover(path)?;
+    if repo.is_bare() {
+        return Err(git2::Error::from_str(&format!(
+            "{} is a bare repository; gitui needs a working tree",
+            path.display()
+        )));
+    }
+    Ok(repo)
+}
+
+/// What the Details pane shows for the selected entry of the file list. It holds all that
+/// `file_details` needs from the App, so that the diff can be worked out on the worker
+/// thread too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffRequest {
+    pub name: String,
+    pub status: Status,
+    pub is_dir: bool,
+    pub settings: DiffSettings,
+    /// Where the diff is cut short, or `None` when the whole of it was asked for.
+    pub max_lines: Option<usize>,
+    /// The keys that show the whole diff, for the note below a truncated one.
+    pub full_diff_keys: String,
+}
+
+impl DiffRequest {
+    /// `None` when the file list is empty.
+    pub fn for_selection(app: &App) -> Option<Self> {
+        let file = app.files.get(app.selected_index)?;
+        Some(Self {
+            name: file.name.clone(),
+            status: file.status,
+            is_dir: file.is_dir,
+            settings: app.diff_settings,
+            // Generated files and large refactors would take long to show, so the diff is
+            // cut short unless the whole of it was asked for.
+            max_lines: (app.full_diff.as_deref() != Some(file.name.as_str()))
+                .then_some(app.config.max_diff_lines),
+            full_diff_keys: app.keymap.labels_for(Action::FullDiff).join("/"),
+        })
+    }
+}
+
 pub fn update_right_pane(repo: &Repository, app: &mut App) -> Result<(), git2::Error> {
-    let selected_file = &app.files[app.selected_index];
-    let path = PathBuf::from(&selected_file.name);
+    let Some(request) = DiffRequest::for_selection(app) else {
+        return Ok(());
+    };
+    app.diff_hunks.clear();
+    let (lines, hunks) = file_details(repo, &request)?;
+    app.right_pane_lines = lines;
+    app.diff_hunks = hunks;
+    if app.selected_hunk >= app.diff_hunks.len() {
+        app.selected_hunk = app.diff_hunks.len().saturating_sub(1);
+    }
+    Ok(())
+}
+
+/// The lines of the Details pane for a file or directory of the file list, and the hunks
+/// among them that can be staged or unstaged one by one.
+pub fn file_details(
+    repo: &Repository,
+    request: &DiffRequest,
+) -> Result<(Vec<DiffLine>, Vec<Hunk>), git2::Error> {
+    let path = PathBuf::from(&request.name);
+
+    // Untracked files have nothing to diff against, so their whole contents are shown as
+    // additions, unless they are too big to be worth reading.
+    let is_untracked = !request.is_dir && request.status.contains(Status::WT_NEW);
+    if is_untracked {
+        let size = repo
+            .workdir()
+            .and_then(|workdir| std::fs::metadata(workdir.join(&path)).ok())
+            .map_or(0, |metadata| metadata.len());
+        if size > MAX_PREVIEW_BYTES {
+            let lines = vec![
+                DiffLine::new(LineKind::FileHeader, format!("New file: {}", request.name)),
+                DiffLine::new(
+                    LineKind::Text,
+                    format!("File too large to preview ({} bytes)", size),
+                ),
+            ];
+            return Ok((lines, Vec::new()));
+        }
+    }
+
+    if !request.is_dir && request.status.contains(Status::IGNORED) {
+        let lines = vec![
+            DiffLine::new(LineKind::FileHeader, format!("Ignored: {}", request.name)),
+            DiffLine::new(
+                LineKind::Text,
+                "Matched by .gitignore or info/exclude, so git doesn't track it",
+            ),
+        ];
+        return Ok((lines, Vec::new()));
+    }
 
-    if selected_file.is_dir {
-        app.right_pane_content = format!("Directory: {}", selected_file.name);
+    // A diff against a conflicted index entry says little; the conflict markers say more.
+    if !request.is_dir && request.status.contains(Status::CONFLICTED) {
+        return Ok((conflict_lines(repo, &request.name), Vec::new()));
+    }
+
+    let mut opts = diff_options(&request.name, &request.settings);
+    opts.include_untracked(true)
+        .show_untracked_content(is_untracked);
+    let unstaged = repo.diff_index_to_workdir(None, Some(&mut opts))?;
+    let tree = head_tree(repo)?;
+    // A staged addition or deletion may be one side of a rename, whose other side is
+    // listed separately, so the diff takes in both.
+    let counterpart = if !request.is_dir
+        && request
+            .status
+            .intersects(Status::INDEX_NEW | Status::INDEX_DELETED)
+    {
+        rename_counterpart(repo, tree.as_ref(), &path)?
     } else {
-        let mut diff_content = String::new();
-
-        // Check for unstaged changes
-        let mut opts = DiffOptions::new();
-        opts.pathspec(selected_file.name.clone());
-        opts.include_untracked(true);
-
-        let diff = repo.diff_index_to_workdir(None, Some(&mut opts))?;
-        diff_content.push_str("Unstaged changes:\n");
-        append_diff(&mut diff_content, &diff, &path)?;
-
-        // Check for staged changes
-        let head = repo.head()?;
-        let tree = head.peel_to_tree()?;
-        let diff = repo.diff_tree_to_index(Some(&tree), None, Some(&mut opts))?;
-        diff_content.push_str("\nStaged changes:\n");
-        append_diff(&mut diff_content, &diff, &path)?;
-
-        app.right_pane_content = if diff_content.trim() == "Unstaged changes:\nStaged changes:" {
-            format!("No changes detected for file: {}", selected_file.name)
-        } else {
-            diff_content
+        None
+    };
+    if let Some(counterpart) = &counterpart {
+        opts.pathspec(counterpart);
+    }
+    let mut staged = repo.diff_tree_to_index(tree.as_ref(), None, Some(&mut opts))?;
+    detect_renames(&mut staged)?;
+
+    let max_lines = request.max_lines;
+    let mut hunks = Vec::new();
+    let mut truncated = false;
+    let mut lines = Vec::new();
+    if request.is_dir {
+        lines.push(DiffLine::new(
+            LineKind::Text,
+            format!("Directory: {}", request.name),
+        ));
+    }
+    let stat_line = diff_stat_line(&unstaged, &staged, request.is_dir)?;
+    if let Some(stat_line) = &stat_line {
+        lines.push(DiffLine::new(LineKind::Header, stat_line.as_str()));
+        lines.push(DiffLine::new(LineKind::Text, ""));
+    }
+
+    if request.is_dir {
+        // An overview of everything under the directory. Its hunks aren't offered for
+        // staging, which goes by the selected path.
+        for (header, diff) in [
+            ("Unstaged changes:", &unstaged),
+            ("Staged changes:", &staged),
+        ] {
+            if diff.deltas().len() == 0 {
+                continue;
+            }
+            lines.push(DiffLine::new(LineKind::Header, header));
+            append_file_stats(&mut lines, diff)?;
+            lines.push(DiffLine::new(LineKind::Text, ""));
+            truncated = append_diff(&mut lines, diff, None, max_lines)?.1;
+            if truncated {
+                break;
+            }
+            lines.push(DiffLine::new(LineKind::Text, ""));
+        }
+        if stat_line.is_none() {
+            lines.push(DiffLine::new(
+                LineKind::Text,
+                "No changes under this directory",
+            ));
+        }
+    } else if stat_line.is_none() {
+        // Nothing pending, so show where the file's current content comes from instead.
+        lines = match last_commit_touching(repo, &path) {
+            Ok(Some(commit)) => last_commit_lines(&request.name, &commit),
+            _ => vec![DiffLine::new(
+                LineKind::Text,
+                format!("No changes detected for file: {}", request.name),
+            )],
         };
+    } else {
+        lines.push(DiffLine::new(LineKind::Header, "Unstaged changes:"));
+        let (unstaged_hunks, unstaged_truncated) =
+            append_diff(&mut lines, &unstaged, Some(&path), max_lines)?;
+        truncated = unstaged_truncated;
+        // The index has no entry to apply an untracked file's hunk to; it is staged whole.
+        if !is_untracked {
+            hunks.extend(unstaged_hunks);
+        }
+
+        if !truncated {
+            lines.push(DiffLine::new(LineKind::Text, ""));
+            lines.push(DiffLine::new(LineKind::Header, "Staged changes:"));
+            let (staged_hunks, staged_truncated) =
+                append_diff(&mut lines, &staged, Some(&path), max_lines)?;
+            truncated = staged_truncated;
+            // Unstaging goes by the file's own diff, in which a renamed file is added whole,
+            // so the hunks against the old file can't be unstaged one by one.
+            if counterpart.is_none() {
+                hunks.extend(staged_hunks.into_iter().map(|hunk| Hunk {
+                    staged: true,
+                    ..hunk
+                }));
+            }
+        }
+    }
+    if let (true, Some(max_lines)) = (truncated, max_lines) {
+        lines.push(DiffLine::new(LineKind::Text, ""));
+        lines.push(DiffLine::new(
+            LineKind::Header,
+            format!(
+                "Diff truncated at {} lines; press {} to show all of it",
+                max_lines, request.full_diff_keys
+            ),
+        ));
     }
 
+    Ok((lines, hunks))
+}
+
+/// A one-line summary like "Unstaged: 12 insertions(+), 4 deletions(-)" covering both diffs,
+/// or `None` when neither changes anything. File counts are included for directories.
+fn diff_stat_line(
+    unstaged: &Diff,
+    staged: &Diff,
+    with_file_counts: bool,
+) -> Result<Option<String>, git2::Error> {
+    let mut parts = Vec::new();
+    for (label, diff) in [("Unstaged", unstaged), ("Staged", staged)] {
+        if diff.deltas().len() == 0 {
+            continue;
+        }
+        let stats = diff.stats()?;
+        let mut counts = Vec::new();
+        if with_file_counts {
+            counts.push(plural(
+                stats.files_changed(),
+                "file changed",
+                "files changed",
+            ));
+        }
+        counts.push(plural(stats.insertions(), "insertion(+)", "insertions(+)"));
+        counts.push(plural(stats.deletions(), "deletion(-)", "deletions(-)"));
+        parts.push(format!("{}: {}", label, counts.join(", ")));
+    }
+    Ok((!parts.is_empty()).then(|| parts.join(" | ")))
+}
+
+/// Everything the next commit will change: the index against HEAD, or against the parent
+/// of HEAD when amending, since the amended commit replaces it. A first commit is diffed
+/// against the empty tree.
+pub fn staged_diff(
+    repo: &Repository,
+    amend: bool,
+    settings: &DiffSettings,
+    max_lines: usize,
+) -> Result<Vec<DiffLine>, git2::Error> {
+    let base = if amend {
+        match head_commit(repo)?.and_then(|commit| commit.parent(0).ok()) {
+            Some(parent) => Some(parent.tree()?),
+            None => None,
+        }
+    } else {
+        head_tree(repo)?
+    };
+    let mut opts = DiffOptions::new();
+    settings.apply(&mut opts);
+    let mut diff = repo.diff_tree_to_index(base.as_ref(), None, Some(&mut opts))?;
+    detect_renames(&mut diff)?;
+
+    let mut lines = vec![DiffLine::new(LineKind::Header, "Changes to be committed:")];
+    if diff.deltas().len() == 0 {
+        lines.push(DiffLine::new(LineKind::Text, "Nothing is staged"));
+        return Ok(lines);
+    }
+    append_file_stats(&mut lines, &diff)?;
+    lines.push(DiffLine::new(LineKind::Text, ""));
+    if append_diff(&mut lines, &diff, None, Some(max_lines))?.1 {
+        lines.push(DiffLine::new(LineKind::Text, ""));
+        lines.push(DiffLine::new(
+            LineKind::Header,
+            format!("Diff truncated at {} lines", max_lines),
+        ));
+    }
+    Ok(lines)
+}
+
+/// One line per file in `diff` with its insertions and deletions, like `git diff --stat`.
+fn append_file_stats(lines: &mut Vec<DiffLine>, diff: &Diff) -> Result<(), git2::Error> {
+    let mut files = Vec::new();
+    for (index, delta) in diff.deltas().enumerate() {
+        let path = match (
+            delta.status(),
+            delta.old_file().path(),
+            delta.new_file().path(),
+        ) {
+            (Delta::Renamed | Delta::Copied, Some(old), Some(new)) => {
+                format!("{} -> {}", old.display(), new.display())
+            }
+            (_, old, new) => new
+                .or(old)
+                .map(|path| path.display().to_string())
+                .unwrap_or_default(),
+        };
+        let counts = if delta.status() == Delta::Untracked {
+            "untracked".to_string()
+        } else {
+            match Patch::from_diff(diff, index)? {
+                Some(patch) => {
+                    let (_, insertions, deletions) = patch.line_stats()?;
+                    format!("+{} -{}", insertions, deletions)
+                }
+                None => "binary".to_string(),
+            }
+        };
+        files.push((path, counts));
+    }
+    let width = files
+        .iter()
+        .map(|(path, _)| path.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (path, counts) in files {
+        lines.push(DiffLine::new(
+            LineKind::Text,
+            format!("  {:<width$} | {}", path, counts, width = width),
+        ));
+    }
     Ok(())
 }
 
-pub fn append_diff(content: &mut String, diff: &Diff, path: &Path) -> Result<(), git2::Error> {
+fn plural(count: usize, singular: &str, plural: &str) -> String {
+    format!("{} {}", count, if count == 1 { singular } else { plural })
+}
+
+/// User-adjustable settings for how diffs are computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DiffSettings {
+    pub context_lines: u32,
+    pub whitespace: Whitespace,
+}
+
+impl DiffSettings {
+    fn apply(&self, opts: &mut DiffOptions) {
+        opts.context_lines(self.context_lines);
+        match self.whitespace {
+            Whitespace::Show => {}
+            Whitespace::IgnoreAtEol => {
+                opts.ignore_whitespace_eol(true);
+            }
+            Whitespace::IgnoreChange => {
+                opts.ignore_whitespace_change(true);
+            }
+            Whitespace::IgnoreAll => {
+                opts.ignore_whitespace(true);
+            }
+        }
+    }
+}
+
+/// Which whitespace changes diffs leave out, like git's `--ignore-space-at-eol`,
+/// `--ignore-space-change` and `--ignore-all-space`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Whitespace {
+    Show,
+    IgnoreAtEol,
+    IgnoreChange,
+    IgnoreAll,
+}
+
+impl Whitespace {
+    /// The mode after `self` when cycling with the whitespace key, ending back at `Show`.
+    pub fn next(self) -> Self {
+        match self {
+            Whitespace::Show => Whitespace::IgnoreAtEol,
+            Whitespace::IgnoreAtEol => Whitespace::IgnoreChange,
+            Whitespace::IgnoreChange => Whitespace::IgnoreAll,
+            Whitespace::IgnoreAll => Whitespace::Show,
+        }
+    }
+
+    /// What is left out, for the Details pane's title.
+    pub fn label(self) -> Option<&'static str> {
+        match self {
+            Whitespace::Show => None,
+            Whitespace::IgnoreAtEol => Some("ignoring whitespace at line ends"),
+            Whitespace::IgnoreChange => Some("ignoring whitespace changes"),
+            Whitespace::IgnoreAll => Some("ignoring all whitespace"),
+        }
+    }
+}
+
+/// Pairs up deleted and added files into renames, and added files with the modified ones
+/// they were copied from, as `git diff -M -C` does.
+fn detect_renames(diff: &mut Diff) -> Result<(), git2::Error> {
+    diff.find_similar(Some(DiffFindOptions::new().renames(true).copies(true)))
+}
+
+/// The other side of a staged rename or copy that `path` is part of.
+fn rename_counterpart(
+    repo: &Repository,
+    tree: Option<&Tree>,
+    path: &Path,
+) -> Result<Option<PathBuf>, git2::Error> {
+    let mut diff = repo.diff_tree_to_index(tree, None, None)?;
+    detect_renames(&mut diff)?;
+    let counterpart = diff
+        .deltas()
+        .filter(|delta| matches!(delta.status(), Delta::Renamed | Delta::Copied))
+        .find_map(|delta| {
+            let (old, new) = (delta.old_file().path()?, delta.new_file().path()?);
+            if new == path {
+                Some(old.to_path_buf())
+            } else if old == path {
+                Some(new.to_path_buf())
+            } else {
+                None
+            }
+        });
+    Ok(counterpart)
+}
+
+/// Options shared by the displayed diff and hunk staging, so both see identical hunks.
+fn diff_options(path: &str, settings: &DiffSettings) -> DiffOptions {
+    let mut opts = DiffOptions::new();
+    opts.pathspec(path);
+    settings.apply(&mut opts);
+    opts
+}
+
+/// Appends the patch for `path` (or every file when `None`) to `lines` and returns the
+/// hunks it contained. With `max_lines`, the patch stops once `lines` has that many, and
+/// the returned flag says whether it was cut short; the rest is never generated.
+pub fn append_diff(
+    lines: &mut Vec<DiffLine>,
+    diff: &Diff,
+    path: Option<&Path>,
+    max_lines: Option<usize>,
+) -> Result<(Vec<Hunk>, bool), git2::Error> {
     let mut has_changes = false;
-    diff.print(git2::DiffFormat::Patch, |delta, _, line| {
-        if delta.new_file().path() == Some(path) || delta.old_file().path() == Some(path) {
+    let mut truncated = false;
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let result = diff.print(git2::DiffFormat::Patch, |delta, hunk, line| {
+        if path.is_none() || delta.new_file().path() == path || delta.old_file().path() == path {
+            if max_lines.is_some_and(|max| lines.len() >= max) {
+                truncated = true;
+                return false;
+            }
             has_changes = true;
+            let line_count = lines.len();
+            let content = String::from_utf8_lossy(line.content());
             use git2::DiffLineType;
             match line.origin_value() {
-                DiffLineType::Addition => content.push('+'),
-                DiffLineType::Deletion => content.push('-'),
-                DiffLineType::AddEOFNL => content.push_str("+\n"),
-                DiffLineType::DeleteEOFNL => content.push_str("-\n"),
-                DiffLineType::Context => content.push(' '),
-                _ => {}
+                DiffLineType::Addition | DiffLineType::Deletion | DiffLineType::Context => {
+                    let kind = match line.origin_value() {
+                        DiffLineType::Addition => LineKind::Addition,
+                        DiffLineType::Deletion => LineKind::Deletion,
+                        _ => LineKind::Context,
+                    };
+                    lines.push(DiffLine {
+                        old_lineno: line.old_lineno(),
+                        new_lineno: line.new_lineno(),
+                        ..DiffLine::new(kind, content.trim_end_matches(['\n', '\r']))
+                    });
+                }
+                DiffLineType::FileHeader => {
+                    let verb = match delta.status() {
+                        git2::Delta::Renamed => Some("Renamed"),
+                        git2::Delta::Copied => Some("Copied"),
+                        _ => None,
+                    };
+                    if let (Some(verb), Some(old), Some(new)) =
+                        (verb, delta.old_file().path(), delta.new_file().path())
+                    {
+                        lines.push(DiffLine::new(
+                            LineKind::Header,
+                            format!("{} from {} to {}", verb, old.display(), new.display()),
+                        ));
+                    }
+                    push_lines(lines, LineKind::FileHeader, &content)
+                }
+                // libgit2's "Binary files a/x and b/x differ" says nothing about the change,
+                // so the size of the file as it now stands is shown instead.
+                DiffLineType::Binary => {
+                    let size = match delta.status() {
+                        git2::Delta::Deleted => delta.old_file().size(),
+                        _ => delta.new_file().size(),
+                    };
+                    lines.push(DiffLine::new(
+                        LineKind::Text,
+                        format!("Binary file differs ({} bytes)", size),
+                    ));
+                }
+                DiffLineType::HunkHeader => {
+                    if let Some(hunk) = hunk {
+                        hunks.push(Hunk {
+                            header: String::from_utf8_lossy(hunk.header())
+                                .trim_end()
+                                .to_string(),
+                            start_line: line_count,
+                            line_count: 0,
+                            old_start: hunk.old_start(),
+                            old_lines: hunk.old_lines(),
+                            new_start: hunk.new_start(),
+                            new_lines: hunk.new_lines(),
+                            staged: false,
+                        });
+                    }
+                    push_lines(lines, LineKind::HunkHeader, &content);
+                }
+                _ => push_lines(lines, LineKind::Text, content.trim_start_matches('\n')),
+            }
+            if line.origin_value() != DiffLineType::FileHeader {
+                if let Some(last) = hunks.last_mut() {
+                    last.line_count += lines.len() - line_count;
+                }
             }
-            content.push_str(std::str::from_utf8(line.content()).unwrap_or(""));
         }
         true
-    })?;
-    if !has_changes {
-        content.push_str("No changes\n");
+    });
+    // Stopping the patch early makes libgit2 report it as an error.
+    if !truncated {
+        result?;
+    }
+    if !has_changes && !truncated {
+        lines.push(DiffLine::new(LineKind::Text, "No changes"));
+    }
+    Ok((hunks, truncated))
+}
+
+/// Applies a single unstaged hunk of `path` to the index.
+pub fn stage_hunk(
+    repo: &Repository,
+    path: &str,
+    hunk: &Hunk,
+    settings: &DiffSettings,
+) -> Result<(), git2::Error> {
+    let mut opts = diff_options(path, settings);
+    let diff = repo.diff_index_to_workdir(None, Some(&mut opts))?;
+    apply_hunk_to_index(
+        repo,
+        &diff,
+        (
+            hunk.old_start,
+            hunk.old_lines,
+            hunk.new_start,
+            hunk.new_lines,
+        ),
+    )
+}
+
+/// Stages only the lines of an unstaged `hunk`, or unstages only the lines of a staged one,
+/// whose indices into `lines` (the Details pane) fall in `selected`. The hunk is rewritten
+/// into a patch against the index: unselected additions are left out, and unselected
+/// deletions are kept as context (the other way around when unstaging).
+pub fn apply_hunk_lines(
+    repo: &Repository,
+    path: &str,
+    hunk: &Hunk,
+    lines: &[DiffLine],
+    selected: Range<usize>,
+) -> Result<(), git2::Error> {
+    let mut body = String::new();
+    let (mut old_lines, mut new_lines) = (0, 0);
+    let mut picked_changes = false;
+    let mut kept_previous = false;
+    let hunk_lines = lines
+        .iter()
+        .enumerate()
+        .take(hunk.start_line + hunk.line_count)
+        .skip(hunk.start_line + 1);
+    for (index, line) in hunk_lines {
+        let picked = selected.contains(&index);
+        let prefix = match (line.kind, hunk.staged, picked) {
+            (LineKind::Context, _, _) => Some(' '),
+            (LineKind::Addition, false, true) | (LineKind::Deletion, true, true) => Some('+'),
+            (LineKind::Deletion, false, true) | (LineKind::Addition, true, true) => Some('-'),
+            (LineKind::Deletion, false, false) | (LineKind::Addition, true, false) => Some(' '),
+            (LineKind::Addition, false, false) | (LineKind::Deletion, true, false) => None,
+            // "\ No newline at end of file" belongs to the line before it.
+            _ => {
+                if kept_previous && line.content.starts_with('\\') {
+                    body.push_str(&line.content);
+                    body.push('\n');
+                }
+                continue;
+            }
+        };
+        kept_previous = prefix.is_some();
+        let Some(prefix) = prefix else {
+            continue;
+        };
+        picked_changes |= prefix != ' ';
+        if prefix != '+' {
+            old_lines += 1;
+        }
+        if prefix != '-' {
+            new_lines += 1;
+        }
+        body.push(prefix);
+        body.push_str(&line.content);
+        body.push('\n');
+    }
+    if !picked_changes {
+        return Err(git2::Error::from_str(
+            "no added or removed lines are selected",
+        ));
+    }
+
+    // The patch applies to the index, which is the old side of an unstaged hunk and the new
+    // side of a staged one.
+    let start = if hunk.staged {
+        hunk.new_start
+    } else {
+        hunk.old_start
+    };
+    let patch = format!(
+        "diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n@@ -{1},{2} +{1},{3} @@\n{4}",
+        path, start, old_lines, new_lines, body
+    );
+    let diff = Diff::from_buffer(patch.as_bytes())?;
+    repo.apply(&diff, ApplyLocation::Index, None)
+}
+
+/// Removes a single staged hunk of `path` from the index, leaving the working tree alone.
+pub fn unstage_hunk(
+    repo: &Repository,
+    path: &str,
+    hunk: &Hunk,
+    settings: &DiffSettings,
+) -> Result<(), git2::Error> {
+    // Every file of a first commit, and any other newly added one, is a single hunk adding
+    // all of it. Reversed, that deletes the file without going through the hunk callback,
+    // so the file is unstaged whole instead.
+    if hunk.old_start == 0 && hunk.old_lines == 0 {
+        return unstage_file(repo, path).map(|_| ());
+    }
+    let head_tree = head_tree(repo)?;
+    // The reversed HEAD-to-index diff undoes staged changes when applied to the index, with
+    // the old and new ranges of each hunk swapped.
+    let mut opts = diff_options(path, settings);
+    opts.reverse(true);
+    let diff = repo.diff_tree_to_index(head_tree.as_ref(), None, Some(&mut opts))?;
+    apply_hunk_to_index(
+        repo,
+        &diff,
+        (
+            hunk.new_start,
+            hunk.new_lines,
+            hunk.old_start,
+            hunk.old_lines,
+        ),
+    )
+}
+
+fn apply_hunk_to_index(
+    repo: &Repository,
+    diff: &Diff,
+    (old_start, old_lines, new_start, new_lines): (u32, u32, u32, u32),
+) -> Result<(), git2::Error> {
+    let mut found = false;
+    {
+        let mut apply_opts = ApplyOptions::new();
+        apply_opts.hunk_callback(|candidate| {
+            let matches = candidate.is_some_and(|candidate| {
+                candidate.old_start() == old_start
+                    && candidate.old_lines() == old_lines
+                    && candidate.new_start() == new_start
+                    && candidate.new_lines() == new_lines
+            });
+            found |= matches;
+            matches
+        });
+        repo.apply(diff, ApplyLocation::Index, Some(&mut apply_opts))?;
+    }
+    if !found {
+        return Err(git2::Error::from_str(
+            "hunk no longer matches the current diff",
+        ));
     }
     Ok(())
 }
 
-pub fn stage_all_modified(repo: &Repository) -> Result<(), git2::Error> {
+pub fn stage_file(repo: &Repository, path: &str) -> Result<(), git2::Error> {
     let mut index = repo.index()?;
+    let workdir_path = repo.workdir().map(|dir| dir.join(path));
+
+    match workdir_path {
+        // Stage everything below a directory, including files deleted from it.
+        Some(full_path) if full_path.is_dir() => {
+            index.add_all([path], git2::IndexAddOption::DEFAULT, None)?;
+            index.update_all([path], None)?;
+        }
+        // A file deleted from the working tree is staged by removing it from the index.
+        Some(full_path) if !full_path.exists() => index.remove_path(Path::new(path))?,
+        _ => index.add_path(Path::new(path))?,
+    }
+
+    index.write()?;
+    Ok(())
+}
+
+/// Statuses meaning the index differs from HEAD.
+pub const INDEX_STATUSES: Status = Status::INDEX_NEW
+    .union(Status::INDEX_MODIFIED)
+    .union(Status::INDEX_DELETED)
+    .union(Status::INDEX_RENAMED)
+    .union(Status::INDEX_TYPECHANGE);
+
+fn has_staged_changes(repo: &Repository) -> Result<bool, git2::Error> {
+    Ok(repo
+        .statuses(None)?
+        .iter()
+        .any(|entry| entry.status().intersects(INDEX_STATUSES)))
+}
+
+/// A file whose staged changes the next commit takes.
+pub struct StagedFile {
+    /// The path, or `old -> new` for a rename.
+    pub path: String,
+    /// The staged status letter, as `git status --short` shows it.
+    pub code: char,
+}
+
+/// The files that differ between HEAD and the index, which is what committing takes.
+pub fn staged_files(repo: &Repository) -> Result<Vec<StagedFile>, git2::Error> {
     let mut opts = git2::StatusOptions::new();
-    opts.include_untracked(true);
+    opts.include_untracked(false).renames_head_to_index(true);
     let statuses = repo.statuses(Some(&mut opts))?;
+    Ok(statuses
+        .iter()
+        .filter(|entry| entry.status().intersects(INDEX_STATUSES))
+        .map(|entry| {
+            let delta = entry.head_to_index();
+            let old = delta.as_ref().and_then(|d| d.old_file().path());
+            let new = delta.as_ref().and_then(|d| d.new_file().path());
+            let path = match (old, new) {
+                (Some(old), Some(new)) if old != new => {
+                    format!("{} -> {}", old.display(), new.display())
+                }
+                _ => entry.path().unwrap_or_default().to_string(),
+            };
+            StagedFile {
+                path,
+                code: status_code(entry.status())[0],
+            }
+        })
+        .collect())
+}
 
-    for entry in statuses.iter() {
-        let path = entry.path().unwrap_or_default();
-        if entry.status() != Status::CURRENT {
+/// Paths with unresolved conflicts in the index.
+pub fn conflicted_paths(repo: &Repository) -> Result<Vec<String>, git2::Error> {
+    let mut paths = Vec::new();
+    let mut index = repo.index()?;
+    // Conflicts are often resolved and staged from the shell.
+    index.read(false)?;
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        let entry = conflict.our.or(conflict.their).or(conflict.ancestor);
+        if let Some(entry) = entry {
+            paths.push(String::from_utf8_lossy(&entry.path).into_owned());
+        }
+    }
+    Ok(paths)
+}
+
+/// The working tree content of a conflicted file, for resolving the conflicts by eye.
+fn conflict_lines(repo: &Repository, path: &str) -> Vec<DiffLine> {
+    let mut lines = vec![DiffLine::new(
+        LineKind::FileHeader,
+        format!("Conflicts in {}", path),
+    )];
+    let full_path = repo.workdir().unwrap_or(repo.path()).join(path);
+    match std::fs::metadata(&full_path) {
+        Ok(metadata) if metadata.len() > MAX_PREVIEW_BYTES => lines.push(DiffLine::new(
+            LineKind::Text,
+            format!("File too large to preview ({} bytes)", metadata.len()),
+        )),
+        Ok(_) => {
+            let content = std::fs::read(&full_path).unwrap_or_default();
+            for (index, line) in String::from_utf8_lossy(&content).lines().enumerate() {
+                let mut line = DiffLine::new(LineKind::Conflict, line);
+                line.new_lineno = Some(index as u32 + 1);
+                lines.push(line);
+            }
+        }
+        Err(_) => lines.push(DiffLine::new(
+            LineKind::Text,
+            format!("{} is deleted on one side of the conflict", path),
+        )),
+    }
+    lines
+}
+
+/// Resolves the conflicts in `path` by taking the whole file from our side (the current
+/// branch) or from theirs, and stages the result. If that side deleted the file, the file
+/// is deleted.
+pub fn take_conflict_side(repo: &Repository, path: &str, ours: bool) -> Result<(), git2::Error> {
+    let mut index = repo.index()?;
+    index.read(false)?;
+    let conflict = index.conflicts()?.filter_map(Result::ok).find(|conflict| {
+        let entry = conflict.our.as_ref().or(conflict.their.as_ref());
+        entry.is_some_and(|entry| entry.path == path.as_bytes())
+    });
+    let Some(conflict) = conflict else {
+        return Err(git2::Error::from_str(&format!("{} has no conflicts", path)));
+    };
+    let side = if ours { conflict.our } else { conflict.their };
+
+    let full_path = repo.workdir().unwrap_or(repo.path()).join(path);
+    match side {
+        Some(entry) => {
+            let blob = repo.find_blob(entry.id)?;
+            std::fs::write(&full_path, blob.content())
+                .map_err(|e| git2::Error::from_str(&format!("could not write {}: {}", path, e)))?;
+            // Adding the file replaces its conflicting stages with the resolved one.
             index.add_path(Path::new(path))?;
         }
+        None => {
+            if full_path.exists() {
+                std::fs::remove_file(&full_path).map_err(|e| {
+                    git2::Error::from_str(&format!("could not delete {}: {}", path, e))
+                })?;
+            }
+            index.remove_path(Path::new(path))?;
+        }
     }
+    index.write()
+}
 
-    index.write()?;
-    Ok(())
+/// Equivalent of `git revert <oid>`: applies the inverse of the commit and commits it with
+/// git's usual "Revert ..." message. When the inverse doesn't apply cleanly, the conflicted
+/// paths are returned instead and the repository is left mid-revert, with the message
+/// waiting in `MERGE_MSG`, for the conflicts to be resolved and committed.
+pub fn revert_commit(
+    repo: &Repository,
+    oid: Oid,
+    require_signature: bool,
+) -> Result<Vec<String>, git2::Error> {
+    let commit = repo.find_commit(oid)?;
+    if commit.parent_count() > 1 {
+        return Err(git2::Error::from_str(
+            "reverting a merge commit is not supported",
+        ));
+    }
+    // The revert commit is made from the whole index, which must not sweep up anything else.
+    if has_staged_changes(repo)? {
+        return Err(git2::Error::from_str(
+            "there are staged changes; commit or unstage them first",
+        ));
+    }
+    let head = head_commit(repo)?.ok_or_else(|| git2::Error::from_str("HEAD has no commit"))?;
+
+    repo.revert(&commit, None)?;
+    let conflicts = conflicted_paths(repo)?;
+    if !conflicts.is_empty() {
+        return Ok(conflicts);
+    }
+
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    if tree.id() == head.tree_id() {
+        repo.cleanup_state()?;
+        return Err(git2::Error::from_str(
+            "the commit's changes are already undone",
+        ));
+    }
+    let signature = repo.signature()?;
+    let message = format!(
+        "Revert \"{}\"\n\nThis reverts commit {}.\n",
+        commit.summary().unwrap_or_default(),
+        commit.id()
+    );
+    let revert = write_commit(
+        repo,
+        &signature,
+        &signature,
+        &message,
+        &tree,
+        &[&head],
+        require_signature,
+    )?;
+    move_head(repo, revert, &format!("revert: {}", first_line(&message)))?;
+    repo.cleanup_state()?;
+    Ok(Vec::new())
 }
 
-pub fn create_commit(repo: &Repository, message: &str) -> Result<(), git2::Error> {
+/// Equivalent of `git blame HEAD -- <path>`: every line of the committed file annotated
+/// with the commit and author that last changed it.
+pub fn blame_file(repo: &Repository, path: &str) -> Result<Vec<DiffLine>, git2::Error> {
+    let entry = match head_commit(repo)? {
+        Some(commit) => commit.tree()?.get_path(Path::new(path)),
+        None => Err(git2::Error::from_str("HEAD has no commit")),
+    };
+    let Ok(entry) = entry else {
+        return Ok(vec![DiffLine::new(
+            LineKind::Text,
+            format!(
+                "{} is not committed yet, so it has no history to blame",
+                path
+            ),
+        )]);
+    };
+    let blob = entry.to_object(repo)?.peel_to_blob()?;
+    let blame = repo.blame_file(Path::new(path), None)?;
+
+    let mut lines = vec![DiffLine::new(
+        LineKind::FileHeader,
+        format!("Blame of {}", path),
+    )];
+    for (index, code) in String::from_utf8_lossy(blob.content()).lines().enumerate() {
+        let lineno = index + 1;
+        let annotation = match blame.get_line(lineno) {
+            Some(hunk) => format!(
+                "{} {:<16.16}",
+                &hunk.final_commit_id().to_string()[..7],
+                hunk.final_signature().name().unwrap_or_default()
+            ),
+            None => " ".repeat(24),
+        };
+        lines.push(DiffLine {
+            new_lineno: Some(lineno as u32),
+            annotation: Some(annotation),
+            ..DiffLine::new(LineKind::Blame, code)
+        });
+    }
+    Ok(lines)
+}
+
+/// Resolves a commit id, branch, tag or expression like `HEAD~2` to a commit.
+pub fn resolve_commit(repo: &Repository, spec: &str) -> Result<Oid, git2::Error> {
+    match repo.revparse_single(spec) {
+        Ok(object) => Ok(object.peel_to_commit()?.id()),
+        Err(e) if matches!(e.code(), ErrorCode::NotFound | ErrorCode::InvalidSpec) => Err(
+            git2::Error::from_str(&format!("'{}' is not a commit, branch or tag", spec)),
+        ),
+        Err(e) => Err(e),
+    }
+}
+
+/// Equivalent of `git cherry-pick --no-commit <oid>`: applies the commit's changes to the
+/// index and working tree and leaves them to be committed. Returns the conflicted paths,
+/// which have to be resolved first.
+pub fn cherry_pick_commit(repo: &Repository, oid: Oid) -> Result<Vec<String>, git2::Error> {
+    let commit = repo.find_commit(oid)?;
+    if commit.parent_count() > 1 {
+        return Err(git2::Error::from_str(
+            "cherry-picking a merge commit is not supported",
+        ));
+    }
+    if has_staged_changes(repo)? {
+        return Err(git2::Error::from_str(
+            "there are staged changes; commit or unstage them first",
+        ));
+    }
+
+    repo.cherrypick(&commit, None)?;
+    let conflicts = conflicted_paths(repo)?;
+    if conflicts.is_empty() && !has_staged_changes(repo)? {
+        repo.cleanup_state()?;
+        return Err(git2::Error::from_str(
+            "the commit's changes are already on this branch",
+        ));
+    }
+    Ok(conflicts)
+}
+
+/// Equivalent of `git reset HEAD -- <path>`, where `path` may be a file or a directory.
+/// Returns `false` without touching the index when the path has no staged changes.
+pub fn unstage_file(repo: &Repository, path: &str) -> Result<bool, git2::Error> {
+    let mut opts = git2::StatusOptions::new();
+    opts.pathspec(path);
+    let has_staged_changes = repo
+        .statuses(Some(&mut opts))?
+        .iter()
+        .any(|entry| entry.status().intersects(INDEX_STATUSES));
+    if !has_staged_changes {
+        return Ok(false);
+    }
+
+    let head_commit = head_commit(repo)?;
+    repo.reset_default(
+        head_commit.as_ref().map(|commit| commit.as_object()),
+        [path],
+    )?;
+    Ok(true)
+}
+
+/// Equivalent of `git reset` with no paths: moves every staged change back to the working
+/// tree and returns how many paths were affected. With an unborn HEAD there is nothing to
+/// reset to, so the index is simply emptied.
+pub fn unstage_all(repo: &Repository) -> Result<usize, git2::Error> {
+    let statuses = repo.statuses(None)?;
+    let mut paths = Vec::new();
+    for entry in statuses.iter() {
+        if !entry.status().intersects(INDEX_STATUSES) {
+            continue;
+        }
+        // A staged rename has to be reset at both its old and new path.
+        if let Some(delta) = entry.head_to_index() {
+            paths.extend(delta.old_file().path().map(Path::to_path_buf));
+            paths.extend(delta.new_file().path().map(Path::to_path_buf));
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    if paths.is_empty() {
+        return Ok(0);
+    }
+
+    match head_commit(repo)? {
+        Some(commit) => repo.reset_default(Some(commit.as_object()), &paths)?,
+        None => {
+            let mut index = repo.index()?;
+            index.clear()?;
+            index.write()?;
+        }
+    }
+    Ok(paths.len())
+}
+
+/// Returns the commit HEAD points at, or `None` when HEAD is unborn (a freshly
+/// initialised repository or an orphan branch with no commits yet).
+pub fn head_commit(repo: &Repository) -> Result<Option<Commit<'_>>, git2::Error> {
+    match repo.head() {
+        Ok(head) => Ok(Some(head.peel_to_commit()?)),
+        Err(e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// The tree of the HEAD commit, or `None` when HEAD is unborn. Diffing against `None`
+/// diffs against the empty tree, so that everything in the index counts as staged.
+fn head_tree(repo: &Repository) -> Result<Option<Tree<'_>>, git2::Error> {
+    match head_commit(repo)? {
+        Some(commit) => Ok(Some(commit.tree()?)),
+        None => Ok(None),
+    }
+}
+
+pub fn create_commit(
+    repo: &Repository,
+    message: &str,
+    require_signature: bool,
+) -> Result<(), git2::Error> {
     let mut index = repo.index()?;
+    // Pick up conflicts resolved and staged from the shell.
+    index.read(false)?;
     let oid = index.write_tree()?;
     let signature = repo.signature()?;
-    let parent_commit = repo.head()?.peel_to_commit()?;
     let tree = repo.find_tree(oid)?;
-    repo.commit(
+    // With an unborn HEAD this becomes the root commit of the branch.
+    let mut parent_commits: Vec<Commit> = head_commit(repo)?.into_iter().collect();
+    // Concluding a merge that stopped on conflicts also takes the merged commits as parents.
+    if repo.state() == git2::RepositoryState::Merge {
+        let merge_heads = std::fs::read_to_string(repo.path().join("MERGE_HEAD"))
+            .map_err(|e| git2::Error::from_str(&format!("could not read MERGE_HEAD: {}", e)))?;
+        for line in merge_heads.lines().filter(|line| !line.is_empty()) {
+            parent_commits.push(repo.find_commit(Oid::from_str(line.trim())?)?);
+        }
+    }
+    let parents: Vec<&Commit> = parent_commits.iter().collect();
+    // A cherry-picked commit keeps its original author, as with git.
+    let author = match repo
+        .find_reference("CHERRY_PICK_HEAD")
+        .and_then(|reference| reference.peel_to_commit())
+    {
+        Ok(picked) => picked.author().to_owned(),
+        Err(_) => signature.clone(),
+    };
+    let signed = signed_commit(
+        repo,
+        &author,
+        &signature,
+        message,
+        &tree,
+        &parents,
+        require_signature,
+    )?;
+    match signed {
+        Some(oid) => move_head(repo, oid, &format!("commit: {}", first_line(message)))?,
+        None => {
+            repo.commit(Some("HEAD"), &author, &signature, message, &tree, &parents)?;
+        }
+    }
+    // Committing a cherry-pick, or the resolution of a conflicted revert or merge, finishes it.
+    repo.cleanup_state()?;
+    Ok(())
+}
+
+pub fn last_commit_message(repo: &Repository) -> Result<Option<String>, git2::Error> {
+    Ok(head_commit(repo)?.map(|commit| commit.message().unwrap_or_default().to_string()))
+}
+
+/// The commit message template from git's `commit.template`, or else `.gitmessage` at the
+/// root of the working tree. `None` when there is no template or it can't be read.
+pub fn commit_template(repo: &Repository) -> Option<String> {
+    let configured = repo
+        .config()
+        .ok()
+        .and_then(|config| config.get_path("commit.template").ok());
+    let workdir = repo.workdir()?;
+    let path = match configured {
+        // Like git, a relative path is taken from the top of the working tree.
+        Some(path) => workdir.join(path),
+        None => workdir.join(".gitmessage"),
+    };
+    std::fs::read_to_string(path).ok()
+}
+
+/// Removes the `#` comment lines that git leaves out of commit messages, along with the
+/// blank lines this leaves at the end.
+pub fn strip_comments(message: &str) -> String {
+    let lines: Vec<&str> = message
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect();
+    lines.join("\n").trim_end().to_string()
+}
+
+/// Replaces HEAD with a commit of the current index and `message`, keeping the original
+/// author. Works without newly staged changes, in which case only the message changes.
+pub fn amend_commit(
+    repo: &Repository,
+    message: &str,
+    require_signature: bool,
+) -> Result<(), git2::Error> {
+    let commit =
+        head_commit(repo)?.ok_or_else(|| git2::Error::from_str("there is no commit to amend"))?;
+    let mut index = repo.index()?;
+    let oid = index.write_tree()?;
+    let tree = repo.find_tree(oid)?;
+    let committer = repo.signature()?;
+    let parents: Vec<Commit> = commit.parents().collect();
+    let parents: Vec<&Commit> = parents.iter().collect();
+    let signed = signed_commit(
+        repo,
+        &commit.author(),
+        &committer,
+        message,
+        &tree,
+        &parents,
+        require_signature,
+    )?;
+    if let Some(oid) = signed {
+        return move_head(
+            repo,
+            oid,
+            &format!("commit (amend): {}", first_line(message)),
+        );
+    }
+    commit.amend(
         Some("HEAD"),
+        None,
+        Some(&committer),
+        None,
+        Some(message),
+        Some(&tree),
+    )?;
+    Ok(())
+}
+
+/// Writes a signed commit when `commit.gpgsign` is set, without moving any reference to it.
+/// `None` means the commit is to be made unsigned: signing is off, or it failed and isn't
+/// required, which is logged.
+fn signed_commit(
+    repo: &Repository,
+    author: &Signature,
+    committer: &Signature,
+    message: &str,
+    tree: &Tree,
+    parents: &[&Commit],
+    require_signature: bool,
+) -> Result<Option<Oid>, git2::Error> {
+    if !signing_enabled(repo) {
+        return Ok(None);
+    }
+    let buffer = repo.commit_create_buffer(author, committer, message, tree, parents)?;
+    let content = buffer
+        .as_str()
+        .ok_or_else(|| git2::Error::from_str("the commit is not valid UTF-8"))?;
+    match sign(repo, content) {
+        Ok(signature) => Ok(Some(repo.commit_signed(content, &signature, None)?)),
+        Err(e) if require_signature => Err(git2::Error::from_str(&format!(
+            "could not sign the commit: {}",
+            e
+        ))),
+        Err(e) => {
+            debug::debug_log(&format!(
+                "Could not sign the commit, committing it unsigned: {}",
+                e
+            ));
+            Ok(None)
+        }
+    }
+}
+
+/// Writes a commit without moving any reference to it, signed like [`signed_commit`] and
+/// otherwise unsigned, for the caller to point a branch at.
+pub fn write_commit(
+    repo: &Repository,
+    author: &Signature,
+    committer: &Signature,
+    message: &str,
+    tree: &Tree,
+    parents: &[&Commit],
+    require_signature: bool,
+) -> Result<Oid, git2::Error> {
+    let signed = signed_commit(
+        repo,
+        author,
+        committer,
+        message,
+        tree,
+        parents,
+        require_signature,
+    )?;
+    match signed {
+        Some(oid) => Ok(oid),
+        None => repo.commit(None, author, committer, message, tree, parents),
+    }
+}
+
+/// Points HEAD at `oid`, through the branch it is on, as committing with git does.
+fn move_head(repo: &Repository, oid: Oid, reflog_message: &str) -> Result<(), git2::Error> {
+    let head = repo.find_reference("HEAD")?;
+    match head.symbolic_target() {
+        Some(branch) => {
+            repo.reference(branch, oid, true, reflog_message)?;
+        }
+        None => repo.set_head_detached(oid)?,
+    }
+    Ok(())
+}
+
+fn first_line(message: &str) -> &str {
+    message.lines().next().unwrap_or_default()
+}
+
+/// Callbacks for network operations, authenticating with [`make_credentials_callback`].
+fn remote_callbacks(repo: &Repository) -> RemoteCallbacks<'_> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(make_credentials_callback(repo));
+    callbacks
+}
+
+/// Private keys in `~/.ssh` with the default names, in the order ssh tries them.
+fn ssh_key_files() -> Vec<PathBuf> {
+    let Some(home) = std::env::var_os("HOME") else {
+        return Vec::new();
+    };
+    let ssh_dir = Path::new(&home).join(".ssh");
+    ["id_ed25519", "id_ecdsa", "id_rsa"]
+        .into_iter()
+        .map(|name| ssh_dir.join(name))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// libgit2 calls this again each time the server rejects what it returned, so every call
+/// moves on to the next method the server allows: the ssh agent, then each key file in
+/// `~/.ssh`, then the git credential helper for HTTPS. Once all are used up it fails with
+/// an error naming what was tried.
+fn make_credentials_callback(
+    repo: &Repository,
+) -> impl FnMut(&str, Option<&str>, CredentialType) -> Result<Cred, git2::Error> + '_ {
+    let mut key_files = ssh_key_files().into_iter();
+    let mut username_sent = false;
+    let mut agent_tried = false;
+    let mut helper_tried = false;
+    let mut default_tried = false;
+    let mut tried = Vec::new();
+    move |url, username, allowed| {
+        let ssh_username = username.unwrap_or("git");
+        // Asked first for ssh URLs that don't name a user.
+        if allowed.contains(CredentialType::USERNAME) && !username_sent {
+            username_sent = true;
+            return Cred::username(ssh_username);
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            if !agent_tried {
+                agent_tried = true;
+                tried.push("the ssh agent".to_string());
+                return Cred::ssh_key_from_agent(ssh_username);
+            }
+            if let Some(key_file) = key_files.next() {
+                tried.push(key_file.display().to_string());
+                let public_key = key_file.with_extension("pub");
+                return Cred::ssh_key(
+                    ssh_username,
+                    public_key.is_file().then_some(public_key.as_path()),
+                    &key_file,
+                    None,
+                );
+            }
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) && !helper_tried {
+            helper_tried = true;
+            tried.push("the git credential helper".to_string());
+            if let Ok(cred) = Cred::credential_helper(&repo.config()?, url, username) {
+                return Ok(cred);
+            }
+        }
+        if allowed.contains(CredentialType::DEFAULT) && !default_tried {
+            default_tried = true;
+            return Cred::default();
+        }
+        Err(git2::Error::from_str(&if tried.is_empty() {
+            format!("authentication failed for {}", url)
+        } else {
+            format!(
+                "authentication failed for {} (tried {})",
+                url,
+                tried.join(", ")
+            )
+        }))
+    }
+}
+
+/// Logs the progress of one phase of a transfer in steps of 10%, so that a slow transfer
+/// shows it is moving without flooding the debug pane.
+struct ProgressLog {
+    label: &'static str,
+    last_step: Option<usize>,
+}
+
+impl ProgressLog {
+    fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            last_step: None,
+        }
+    }
+
+    fn update(&mut self, current: usize, total: usize, bytes: Option<usize>) {
+        if total == 0 {
+            return;
+        }
+        let step = current * 10 / total;
+        if self.last_step == Some(step) {
+            return;
+        }
+        self.last_step = Some(step);
+        let bytes = bytes.map_or(String::new(), |bytes| format!(", {} bytes", bytes));
+        debug::debug_log(&format!(
+            "{}: {}% ({}/{}{})",
+            self.label,
+            step * 10,
+            current,
+            total,
+            bytes
+        ));
+    }
+}
+
+/// Sends the remote's messages to the debug pane. Its progress lines are redrawn with `\r`
+/// as they update, so only lines finished with `\n` are logged.
+fn log_remote_messages(buffer: &mut String, data: &[u8]) {
+    buffer.push_str(&String::from_utf8_lossy(data));
+    while let Some(end) = buffer.find(['\r', '\n']) {
+        if buffer[end..].starts_with('\n') && !buffer[..end].trim().is_empty() {
+            debug::debug_log(&format!("remote: {}", buffer[..end].trim_end()));
+        }
+        buffer.drain(..=end);
+    }
+}
+
+/// The branch HEAD is on and where it is configured to push to and pull from.
+struct Upstream {
+    local_ref: String,
+    branch_name: String,
+    remote_name: String,
+    merge_ref: String,
+}
+
+fn current_upstream(repo: &Repository) -> Result<Upstream, git2::Error> {
+    let head = repo.head()?;
+    if !head.is_branch() {
+        return Err(git2::Error::from_str(
+            "HEAD is detached; switch to a branch first",
+        ));
+    }
+    let local_ref = head.name().unwrap_or_default().to_string();
+    let branch_name = head.shorthand().unwrap_or_default().to_string();
+
+    let no_upstream = || {
+        git2::Error::from_str(&format!(
+            "branch '{0}' has no upstream; run `git push -u origin {0}` once to set it",
+            branch_name
+        ))
+    };
+    let remote_name = repo
+        .branch_upstream_remote(&local_ref)
+        .map_err(|_| no_upstream())?;
+    let remote_name = remote_name.as_str().unwrap_or("origin").to_string();
+    let merge_ref = repo
+        .config()?
+        .get_string(&format!("branch.{}.merge", branch_name))
+        .map_err(|_| no_upstream())?;
+
+    Ok(Upstream {
+        local_ref,
+        branch_name,
+        remote_name,
+        merge_ref,
+    })
+}
+
+/// What the status bar shows about HEAD.
+pub struct HeadStatus {
+    /// The branch name, or a description of a detached HEAD.
+    pub branch: String,
+    /// Whether HEAD points straight at a commit rather than at a branch.
+    pub detached: bool,
+    /// The short name of the upstream branch, like `origin/main`, when the branch has one.
+    pub upstream: Option<String>,
+    /// Commits ahead of and behind the upstream.
+    pub ahead_behind: Option<(usize, usize)>,
+    /// Tags pointing at the HEAD commit.
+    pub tags: Vec<String>,
+    /// The short id and summary of the HEAD commit, unless the branch is unborn.
+    pub last_commit: Option<String>,
+}
+
+pub fn head_status(repo: &Repository) -> HeadStatus {
+    let commit = head_commit(repo).ok().flatten();
+    let tags = match (&commit, tags_by_commit(repo)) {
+        (Some(commit), Ok(mut tags)) => tags.remove(&commit.id()).unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    let last_commit = commit.map(|commit| {
+        format!(
+            "{:.7} {}",
+            commit.id(),
+            commit.summary().unwrap_or_default()
+        )
+    });
+    match repo.head() {
+        Ok(head) if !repo.head_detached().unwrap_or(false) => {
+            let upstream = upstream_ahead_behind(repo, &head).ok();
+            HeadStatus {
+                branch: head.shorthand().unwrap_or_default().to_string(),
+                detached: false,
+                ahead_behind: upstream.as_ref().map(|(_, counts)| *counts),
+                upstream: upstream.map(|(name, _)| name),
+                tags,
+                last_commit,
+            }
+        }
+        Ok(head) => HeadStatus {
+            branch: match head.target() {
+                Some(oid) => format!("(detached at {:.7})", oid),
+                None => "(detached)".to_string(),
+            },
+            detached: true,
+            upstream: None,
+            ahead_behind: None,
+            tags,
+            last_commit,
+        },
+        // An unborn branch has no commit yet, but HEAD still names it.
+        Err(_) => HeadStatus {
+            branch: repo
+                .find_reference("HEAD")
+                .ok()
+                .and_then(|head| head.symbolic_target().map(str::to_string))
+                .map(|target| target.trim_start_matches("refs/heads/").to_string())
+                .unwrap_or_else(|| "HEAD".to_string()),
+            detached: false,
+            upstream: None,
+            ahead_behind: None,
+            tags,
+            last_commit,
+        },
+    }
+}
+
+/// The upstream's short name with the commits ahead of and behind it.
+fn upstream_ahead_behind(
+    repo: &Repository,
+    head: &git2::Reference,
+) -> Result<(String, (usize, usize)), git2::Error> {
+    let upstream_name = repo.branch_upstream_name(head.name().unwrap_or_default())?;
+    let upstream_name = upstream_name.as_str().unwrap_or_default();
+    let upstream = repo.refname_to_id(upstream_name)?;
+    let local = head
+        .target()
+        .ok_or_else(|| git2::Error::from_str("HEAD has no target"))?;
+    let short_name = upstream_name
+        .strip_prefix("refs/remotes/")
+        .unwrap_or(upstream_name);
+    Ok((
+        short_name.to_string(),
+        repo.graph_ahead_behind(local, upstream)?,
+    ))
+}
+
+/// Pushes the current branch to the branch it tracks, returning a summary of what was pushed.
+pub fn push_current_branch(repo: &Repository) -> Result<String, git2::Error> {
+    let Upstream {
+        local_ref,
+        branch_name,
+        remote_name,
+        merge_ref,
+    } = current_upstream(repo)?;
+
+    let mut remote = repo.find_remote(&remote_name)?;
+    let mut rejection = None;
+    {
+        let mut callbacks = remote_callbacks(repo);
+        let mut progress = ProgressLog::new("Writing objects");
+        callbacks.push_transfer_progress(move |current, total, bytes| {
+            progress.update(current, total, Some(bytes));
+        });
+        let mut remote_messages = String::new();
+        callbacks.sideband_progress(move |data| {
+            log_remote_messages(&mut remote_messages, data);
+            true
+        });
+        callbacks.push_update_reference(|refname, status| {
+            if let Some(message) = status {
+                rejection = Some(format!("{} was rejected: {}", refname, message));
+            }
+            Ok(())
+        });
+        let mut push_options = PushOptions::new();
+        push_options.remote_callbacks(callbacks);
+        let refspec = format!("{}:{}", local_ref, merge_ref);
+        remote.push(&[refspec.as_str()], Some(&mut push_options))?;
+    }
+    if let Some(message) = rejection {
+        return Err(git2::Error::from_str(&message));
+    }
+
+    Ok(format!(
+        "Pushed {} to {}/{}",
+        branch_name,
+        remote_name,
+        merge_ref.trim_start_matches("refs/heads/")
+    ))
+}
+
+/// Fetches `origin` using its configured refspecs.
+pub fn fetch_remote(repo: &Repository) -> Result<String, git2::Error> {
+    fetch(repo, "origin")
+}
+
+fn fetch(repo: &Repository, remote_name: &str) -> Result<String, git2::Error> {
+    let mut remote = repo.find_remote(remote_name)?;
+    let mut callbacks = remote_callbacks(repo);
+    let mut receiving = ProgressLog::new("Receiving objects");
+    let mut resolving = ProgressLog::new("Resolving deltas");
+    callbacks.transfer_progress(move |stats| {
+        receiving.update(
+            stats.received_objects(),
+            stats.total_objects(),
+            Some(stats.received_bytes()),
+        );
+        if stats.received_objects() == stats.total_objects() {
+            resolving.update(stats.indexed_deltas(), stats.total_deltas(), None);
+        }
+        true
+    });
+    let mut remote_messages = String::new();
+    callbacks.sideband_progress(move |data| {
+        log_remote_messages(&mut remote_messages, data);
+        true
+    });
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks);
+    remote.fetch::<&str>(&[], Some(&mut fetch_options), None)?;
+
+    let stats = remote.stats();
+    Ok(format!(
+        "Fetched {}: {} objects received ({} bytes)",
+        remote_name,
+        stats.received_objects(),
+        stats.received_bytes()
+    ))
+}
+
+/// Fetches the current branch's upstream and fast-forwards onto it. Diverged histories are
+/// refused rather than merged.
+pub fn pull_current_branch(repo: &Repository) -> Result<String, git2::Error> {
+    let upstream = current_upstream(repo)?;
+    let fetch_summary = fetch(repo, &upstream.remote_name)?;
+    debug::debug_log(&fetch_summary);
+
+    let upstream_ref = repo.branch_upstream_name(&upstream.local_ref)?;
+    let upstream_ref = repo.find_reference(upstream_ref.as_str().unwrap_or_default())?;
+    let target = repo.reference_to_annotated_commit(&upstream_ref)?;
+    let (analysis, _) = repo.merge_analysis(&[&target])?;
+
+    if analysis.is_up_to_date() {
+        return Ok(format!("{} is already up to date", upstream.branch_name));
+    }
+    if !analysis.is_fast_forward() {
+        return Err(git2::Error::from_str(&format!(
+            "{} has diverged from its upstream and cannot be fast-forwarded",
+            upstream.branch_name
+        )));
+    }
+
+    let target_object = repo.find_object(target.id(), None)?;
+    repo.checkout_tree(
+        &target_object,
+        Some(git2::build::CheckoutBuilder::new().safe()),
+    )?;
+    repo.find_reference(&upstream.local_ref)?
+        .set_target(target.id(), "pull: fast-forward")?;
+
+    Ok(format!(
+        "Fast-forwarded {} to {}",
+        upstream.branch_name,
+        target.id()
+    ))
+}
+
+/// Equivalent of `git reset --soft/--mixed/--hard <oid>`. `ORIG_HEAD` is left pointing at
+/// the commit HEAD was on, as with git, for undoing the reset.
+pub fn reset_to_commit(
+    repo: &Repository,
+    oid: Oid,
+    kind: git2::ResetType,
+) -> Result<(), git2::Error> {
+    let target = repo.find_object(oid, None)?;
+    if let Some(head) = head_commit(repo)? {
+        repo.reference("ORIG_HEAD", head.id(), true, "reset: moving away")?;
+    }
+    repo.reset(&target, kind, None)
+}
+
+/// What `merge_branch` did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeOutcome {
+    UpToDate,
+    FastForwarded,
+    /// A merge commit was created.
+    Merged,
+    /// The merge stopped with these paths conflicted. Committing once they are resolved and
+    /// staged concludes it.
+    Conflicts(Vec<String>),
+}
+
+/// Equivalent of `git merge <name>` for a local branch: the current branch is fast-forwarded
+/// when possible, and otherwise gets a merge commit. Like the checkout, this refuses to
+/// touch uncommitted changes that the merge would overwrite.
+pub fn merge_branch(
+    repo: &Repository,
+    name: &str,
+    require_signature: bool,
+) -> Result<MergeOutcome, git2::Error> {
+    // The merge commit is made from the whole index, which must not sweep up anything else.
+    if has_staged_changes(repo)? {
+        return Err(git2::Error::from_str(
+            "there are staged changes; commit or unstage them first",
+        ));
+    }
+    let branch = repo.find_branch(name, BranchType::Local)?;
+    let target = repo.reference_to_annotated_commit(branch.get())?;
+    let (analysis, _) = repo.merge_analysis(&[&target])?;
+    if analysis.is_up_to_date() {
+        return Ok(MergeOutcome::UpToDate);
+    }
+    let head = head_commit(repo)?
+        .ok_or_else(|| git2::Error::from_str("the current branch has no commits yet"))?;
+    let would_overwrite = |e: git2::Error| {
+        if e.code() == ErrorCode::Conflict {
+            git2::Error::from_str(&format!(
+                "uncommitted changes would be overwritten by merging {}; commit or stash them first",
+                name
+            ))
+        } else {
+            e
+        }
+    };
+
+    if analysis.is_fast_forward() {
+        let target_object = repo.find_object(target.id(), None)?;
+        repo.checkout_tree(
+            &target_object,
+            Some(git2::build::CheckoutBuilder::new().safe()),
+        )
+        .map_err(would_overwrite)?;
+        repo.head()?
+            .set_target(target.id(), &format!("merge {}: Fast-forward", name))?;
+        return Ok(MergeOutcome::FastForwarded);
+    }
+
+    repo.merge(
+        &[&target],
+        None,
+        Some(git2::build::CheckoutBuilder::new().safe()),
+    )
+    .map_err(would_overwrite)?;
+    let conflicts = conflicted_paths(repo)?;
+    if !conflicts.is_empty() {
+        return Ok(MergeOutcome::Conflicts(conflicts));
+    }
+
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    let theirs = repo.find_commit(target.id())?;
+    let signature = repo.signature()?;
+    let message = repo
+        .message()
+        .unwrap_or_else(|_| format!("Merge branch '{}'\n", name));
+    let merge = write_commit(
+        repo,
         &signature,
         &signature,
-        message,
+        &message,
         &tree,
-        &[&parent_commit],
+        &[&head, &theirs],
+        require_signature,
+    )?;
+    move_head(
+        repo,
+        merge,
+        &format!("commit (merge): {}", first_line(&message)),
     )?;
+    repo.cleanup_state()?;
+    Ok(MergeOutcome::Merged)
+}
+
+pub struct BranchInfo {
+    pub name: String,
+    pub is_head: bool,
+    pub upstream: BranchUpstream,
+    /// Commits ahead of and behind the upstream, worked out by `branch_ahead_behind` only
+    /// for the branches the list shows, since it walks their histories.
+    pub ahead_behind: Option<(usize, usize)>,
+}
+
+/// The branch a local branch tracks, by its short name like `origin/main`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BranchUpstream {
+    None,
+    Tracking(String),
+    /// Configured, but the remote branch is missing, because it was deleted or its remote
+    /// was removed.
+    Gone(String),
+}
+
+pub fn list_local_branches(repo: &Repository) -> Result<Vec<BranchInfo>, git2::Error> {
+    let config = repo.config()?;
+    let mut branches = Vec::new();
+    for branch in repo.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch?;
+        let name = branch.name()?.unwrap_or_default().to_string();
+        let refname = branch.get().name().unwrap_or_default();
+        let upstream = match config.get_string(&format!("branch.{}.merge", name)) {
+            Err(_) => BranchUpstream::None,
+            Ok(merge) => match repo.branch_upstream_name(refname) {
+                Ok(upstream_name) => {
+                    let upstream_name = upstream_name.as_str().unwrap_or_default();
+                    let short_name = upstream_name
+                        .strip_prefix("refs/remotes/")
+                        .unwrap_or(upstream_name)
+                        .to_string();
+                    if repo.refname_to_id(upstream_name).is_ok() {
+                        BranchUpstream::Tracking(short_name)
+                    } else {
+                        BranchUpstream::Gone(short_name)
+                    }
+                }
+                // The remote is no longer configured, so git can't say where it would be.
+                Err(_) => {
+                    let remote = config
+                        .get_string(&format!("branch.{}.remote", name))
+                        .unwrap_or_default();
+                    let merge = merge.strip_prefix("refs/heads/").unwrap_or(&merge);
+                    BranchUpstream::Gone(format!("{}/{}", remote, merge))
+                }
+            },
+        };
+        branches.push(BranchInfo {
+            name,
+            is_head: branch.is_head(),
+            upstream,
+            ahead_behind: None,
+        });
+    }
+    branches.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(branches)
+}
+
+/// Commits the local branch `name` is ahead of and behind its upstream.
+pub fn branch_ahead_behind(repo: &Repository, name: &str) -> Result<(usize, usize), git2::Error> {
+    let branch = repo.find_branch(name, BranchType::Local)?;
+    let missing = || git2::Error::from_str("the branch has no target");
+    let local = branch.get().target().ok_or_else(missing)?;
+    let upstream = branch.upstream()?.get().target().ok_or_else(missing)?;
+    repo.graph_ahead_behind(local, upstream)
+}
+
+/// Switches to the local branch `name`. The checkout is done safely, so it fails instead of
+/// overwriting uncommitted changes that conflict with the target branch.
+pub fn checkout_branch(repo: &Repository, name: &str) -> Result<(), git2::Error> {
+    let branch = repo.find_branch(name, BranchType::Local)?;
+    let refname = branch.get().name().unwrap_or_default().to_string();
+    let target = branch.get().peel(git2::ObjectType::Commit)?;
+
+    repo.checkout_tree(&target, Some(git2::build::CheckoutBuilder::new().safe()))
+        .map_err(|e| {
+            if e.code() == ErrorCode::Conflict {
+                git2::Error::from_str(&format!(
+                    "uncommitted changes would be overwritten by switching to {}; commit or stash them first",
+                    name
+                ))
+            } else {
+                e
+            }
+        })?;
+    repo.set_head(&refname)?;
+    Ok(())
+}
+
+/// How many commits the local branch `name` has that HEAD doesn't, which deleting it would
+/// leave unreachable.
+pub fn unmerged_commit_count(repo: &Repository, name: &str) -> Result<usize, git2::Error> {
+    let branch = repo.find_branch(name, BranchType::Local)?;
+    let tip = branch
+        .get()
+        .target()
+        .ok_or_else(|| git2::Error::from_str("the branch has no target"))?;
+    match head_commit(repo)? {
+        Some(head) => Ok(repo.graph_ahead_behind(tip, head.id())?.0),
+        None => {
+            let mut revwalk = repo.revwalk()?;
+            revwalk.push(tip)?;
+            Ok(revwalk.count())
+        }
+    }
+}
+
+/// Deletes the local branch `name`, which must not be the current one.
+pub fn delete_branch(repo: &Repository, name: &str) -> Result<(), git2::Error> {
+    let mut branch = repo.find_branch(name, BranchType::Local)?;
+    if branch.is_head() {
+        return Err(git2::Error::from_str(&format!(
+            "'{}' is the current branch",
+            name
+        )));
+    }
+    branch.delete()
+}
+
+/// Creates a local branch at the current HEAD commit, optionally switching to it.
+pub fn create_branch(repo: &Repository, name: &str, checkout: bool) -> Result<(), git2::Error> {
+    check_new_branch_name(repo, name)?;
+    let commit = head_commit(repo)?
+        .ok_or_else(|| git2::Error::from_str("cannot create a branch before the first commit"))?;
+    repo.branch(name, &commit, false)?;
+    if checkout {
+        // The new branch points at HEAD, so nothing in the working tree changes.
+        repo.set_head(&format!("refs/heads/{}", name))?;
+    }
+    Ok(())
+}
+
+/// Renames the local branch `old` to `new`. Renaming the current branch moves HEAD along
+/// with it.
+pub fn rename_branch(repo: &Repository, old: &str, new: &str) -> Result<(), git2::Error> {
+    check_new_branch_name(repo, new)?;
+    repo.find_branch(old, BranchType::Local)?
+        .rename(new, false)?;
+    Ok(())
+}
+
+fn check_new_branch_name(repo: &Repository, name: &str) -> Result<(), git2::Error> {
+    if !git2::Branch::name_is_valid(name)? {
+        return Err(git2::Error::from_str(&format!(
+            "'{}' is not a valid branch name",
+            name
+        )));
+    }
+    if repo.find_branch(name, BranchType::Local).is_ok() {
+        return Err(git2::Error::from_str(&format!(
+            "a branch named '{}' already exists",
+            name
+        )));
+    }
     Ok(())
 }
+
+/// Tag names grouped by the commit they point at, whether lightweight or annotated.
+pub fn tags_by_commit(repo: &Repository) -> Result<HashMap<Oid, Vec<String>>, git2::Error> {
+    let mut tags: HashMap<Oid, Vec<String>> = HashMap::new();
+    for name in repo.tag_names(None)?.iter().flatten() {
+        // Tags of trees or blobs have no place in the commit history.
+        let Ok(commit) = repo
+            .revparse_single(&format!("refs/tags/{}", name))
+            .and_then(|object| object.peel_to_commit())
+        else {
+            continue;
+        };
+        tags.entry(commit.id()).or_default().push(name.to_string());
+    }
+    Ok(tags)
+}
+
+/// Tags `target`, or HEAD when `None`. A message makes it an annotated tag, otherwise it is
+/// a lightweight one.
+pub fn create_tag(
+    repo: &Repository,
+    name: &str,
+    message: Option<&str>,
+    target: Option<Oid>,
+) -> Result<(), git2::Error> {
+    if !git2::Reference::is_valid_name(&format!("refs/tags/{}", name)) {
+        return Err(git2::Error::from_str(&format!(
+            "'{}' is not a valid tag name",
+            name
+        )));
+    }
+    if repo.find_reference(&format!("refs/tags/{}", name)).is_ok() {
+        return Err(git2::Error::from_str(&format!(
+            "a tag named '{}' already exists",
+            name
+        )));
+    }
+    let target = match target {
+        Some(oid) => repo.find_commit(oid)?,
+        None => head_commit(repo)?
+            .ok_or_else(|| git2::Error::from_str("cannot tag before the first commit"))?,
+    };
+    match message {
+        Some(message) => {
+            repo.tag(name, target.as_object(), &repo.signature()?, message, false)?;
+        }
+        None => {
+            repo.tag_lightweight(name, target.as_object(), false)?;
+        }
+    }
+    Ok(())
+}
+
+pub struct CommitInfo {
+    pub id: Oid,
+    pub short_id: String,
+    pub summary: String,
+    pub author: String,
+    pub time: Time,
+}
+
+/// Loads up to `count` commits reachable from HEAD, newest first, after skipping `skip`.
+pub fn load_commits(
+    repo: &Repository,
+    skip: usize,
+    count: usize,
+) -> Result<Vec<CommitInfo>, git2::Error> {
+    if head_commit(repo)?.is_none() {
+        return Ok(Vec::new());
+    }
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TIME)?;
+    revwalk.push_head()?;
+
+    let mut commits = Vec::new();
+    for oid in revwalk.skip(skip).take(count) {
+        let commit = repo.find_commit(oid?)?;
+        commits.push(CommitInfo {
+            id: commit.id(),
+            short_id: commit
+                .as_object()
+                .short_id()?
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            summary: commit.summary().unwrap_or_default().to_string(),
+            author: commit.author().name().unwrap_or_default().to_string(),
+            time: commit.time(),
+        });
+    }
+    Ok(commits)
+}
+
+/// The newest commit reachable from HEAD that changed `path`, following first parents.
+fn last_commit_touching<'repo>(
+    repo: &'repo Repository,
+    path: &Path,
+) -> Result<Option<Commit<'repo>>, git2::Error> {
+    let Some(head) = head_commit(repo)? else {
+        return Ok(None);
+    };
+    let entry_id = |commit: &Commit| -> Result<Option<Oid>, git2::Error> {
+        Ok(commit.tree()?.get_path(path).ok().map(|entry| entry.id()))
+    };
+    let mut commit = head;
+    let mut id = entry_id(&commit)?;
+    if id.is_none() {
+        return Ok(None);
+    }
+    loop {
+        let Ok(parent) = commit.parent(0) else {
+            // The file was there from the root commit.
+            return Ok(Some(commit));
+        };
+        let parent_id = entry_id(&parent)?;
+        if parent_id != id {
+            return Ok(Some(commit));
+        }
+        commit = parent;
+        id = parent_id;
+    }
+}
+
+fn last_commit_lines(path: &str, commit: &Commit) -> Vec<DiffLine> {
+    let author = commit.author();
+    vec![
+        DiffLine::new(LineKind::Header, format!("No changes to {}", path)),
+        DiffLine::new(LineKind::Text, ""),
+        DiffLine::new(LineKind::Text, "Last changed in:"),
+        DiffLine::new(LineKind::Text, format!("commit {}", commit.id())),
+        DiffLine::new(
+            LineKind::Text,
+            format!(
+                "Author: {} <{}>",
+                author.name().unwrap_or_default(),
+                author.email().unwrap_or_default()
+            ),
+        ),
+        DiffLine::new(
+            LineKind::Text,
+            format!("Date:   {}", format_absolute(commit.time())),
+        ),
+        DiffLine::new(LineKind::Text, ""),
+        DiffLine::new(
+            LineKind::Text,
+            format!("    {}", commit.summary().unwrap_or_default()),
+        ),
+    ]
+}
+
+/// Renders a commit's metadata followed by its patch against the first parent.
+pub fn show_commit(repo: &Repository, oid: Oid) -> Result<Vec<DiffLine>, git2::Error> {
+    let commit = repo.find_commit(oid)?;
+    let author = commit.author();
+    let mut lines = vec![
+        DiffLine::new(LineKind::Header, format!("commit {}", commit.id())),
+        DiffLine::new(
+            LineKind::Text,
+            format!(
+                "Author: {} <{}>",
+                author.name().unwrap_or_default(),
+                author.email().unwrap_or_default()
+            ),
+        ),
+        DiffLine::new(
+            LineKind::Text,
+            format!("Date:   {}", format_absolute(commit.time())),
+        ),
+        DiffLine::new(
+            LineKind::Text,
+            format!("Signature: {}", verify_commit(repo, oid)),
+        ),
+        DiffLine::new(LineKind::Text, ""),
+    ];
+    for line in commit.message().unwrap_or_default().lines() {
+        lines.push(DiffLine::new(LineKind::Text, format!("    {}", line)));
+    }
+    lines.push(DiffLine::new(LineKind::Text, ""));
+
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+    let mut diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+    detect_renames(&mut diff)?;
+    append_diff(&mut lines, &diff, None, None)?;
+    Ok(lines)
+}
+
+/// An entry of the HEAD reflog: where HEAD moved to, and why.
+pub struct ReflogEntry {
+    pub id: Oid,
+    pub message: String,
+    pub time: Time,
+}
+
+/// The HEAD reflog, newest entry first.
+pub fn load_reflog(repo: &Repository) -> Result<Vec<ReflogEntry>, git2::Error> {
+    Ok(repo
+        .reflog("HEAD")?
+        .iter()
+        .map(|entry| ReflogEntry {
+            id: entry.id_new(),
+            message: entry.message().unwrap_or_default().to_string(),
+            time: entry.committer().when(),
+        })
+        .collect())
+}
+
+pub struct StashInfo {
+    pub index: usize,
+    pub message: String,
+}
+
+/// libgit2's stash functions need a mutable repository, so they run on a second handle to
+/// the same repository rather than threading `&mut Repository` through the UI.
+fn reopen(repo: &Repository) -> Result<Repository, git2::Error> {
+    Repository::open(repo.path())
+}
+
+/// Stashes all changes, including untracked files. Without a message git's usual
+/// "WIP on <branch>" description is used.
+pub fn stash_save(repo: &Repository, message: Option<&str>) -> Result<(), git2::Error> {
+    let mut repo = reopen(repo)?;
+    let signature = repo.signature()?;
+    match repo.stash_save2(&signature, message, Some(StashFlags::INCLUDE_UNTRACKED)) {
+        Ok(_) => Ok(()),
+        Err(e) if e.code() == ErrorCode::NotFound => {
+            Err(git2::Error::from_str("No local changes to stash"))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub fn list_stashes(repo: &Repository) -> Result<Vec<StashInfo>, git2::Error> {
+    let mut repo = reopen(repo)?;
+    let mut stashes = Vec::new();
+    repo.stash_foreach(|index, message, _| {
+        stashes.push(StashInfo {
+            index,
+            message: message.to_string(),
+        });
+        true
+    })?;
+    Ok(stashes)
+}
+
+/// Applies a stash to the working tree, then drops it when `drop` is set. A stash that
+/// conflicts with local changes is left alone.
+pub fn stash_apply(repo: &Repository, index: usize, drop: bool) -> Result<(), git2::Error> {
+    // libgit2 restores a stash's untracked files before noticing conflicts in tracked ones
+    // and doesn't roll them back, so check for conflicts up front.
+    if let Some(path) = first_stash_conflict(repo, index)? {
+        return Err(git2::Error::from_str(&format!(
+            "Applying the stash would overwrite local changes to {}; commit or stash them first",
+            path
+        )));
+    }
+    let mut repo = reopen(repo)?;
+    let mut options = StashApplyOptions::new();
+    let result = if drop {
+        repo.stash_pop(index, Some(&mut options))
+    } else {
+        repo.stash_apply(index, Some(&mut options))
+    };
+    result.map_err(|e| match e.code() {
+        ErrorCode::Conflict => git2::Error::from_str(
+            "Applying the stash would overwrite local changes; commit or stash them first",
+        ),
+        _ => e,
+    })
+}
+
+/// A path the stash changes that also has local changes, or that the stash would recreate
+/// as an untracked file where a file already exists.
+fn first_stash_conflict(repo: &Repository, index: usize) -> Result<Option<String>, git2::Error> {
+    let stash = repo
+        .revparse_single(&format!("stash@{{{}}}", index))?
+        .peel_to_commit()?;
+    let base = stash.parent(0)?;
+    let diff = repo.diff_tree_to_tree(Some(&base.tree()?), Some(&stash.tree()?), None)?;
+    for delta in diff.deltas() {
+        let Some(path) = delta.new_file().path() else {
+            continue;
+        };
+        match repo.status_file(path) {
+            Ok(status) if status.is_empty() || status == Status::IGNORED => {}
+            Err(e) if e.code() == ErrorCode::NotFound => {}
+            Ok(_) => return Ok(Some(path.display().to_string())),
+            Err(e) => return Err(e),
+        }
+    }
+
+    // The third parent, when present, holds the untracked files.
+    if let (Ok(untracked), Some(workdir)) = (stash.parent(2), repo.workdir()) {
+        let mut existing = None;
+        untracked
+            .tree()?
+            .walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+                let path = format!("{}{}", dir, entry.name().unwrap_or_default());
+                if entry.kind() == Some(git2::ObjectType::Blob) && workdir.join(&path).exists() {
+                    existing = Some(path);
+                    return git2::TreeWalkResult::Abort;
+                }
+                git2::TreeWalkResult::Ok
+            })
+            .or_else(|e| if existing.is_some() { Ok(()) } else { Err(e) })?;
+        if existing.is_some() {
+            return Ok(existing);
+        }
+    }
+    Ok(None)
+}
+
+pub fn stash_drop(repo: &Repository, index: usize) -> Result<(), git2::Error> {
+    reopen(repo)?.stash_drop(index)
+}
+
+/// How a submodule's checkout compares with the commit the superproject records for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubmoduleState {
+    /// Not cloned yet.
+    Uninitialized,
+    /// A different commit than the recorded one is checked out.
+    OutOfDate,
+    /// The recorded commit is checked out, with local changes on top.
+    Modified,
+    UpToDate,
+}
+
+pub struct SubmoduleInfo {
+    pub name: String,
+    pub path: String,
+    pub state: SubmoduleState,
+    /// The commit the superproject's index records.
+    pub recorded: Option<Oid>,
+    /// The commit checked out in the submodule.
+    pub checked_out: Option<Oid>,
+}
+
+pub fn list_submodules(repo: &Repository) -> Result<Vec<SubmoduleInfo>, git2::Error> {
+    let mut submodules = Vec::new();
+    for submodule in repo.submodules()? {
+        let name = submodule.name().unwrap_or_default().to_string();
+        let status = repo.submodule_status(&name, git2::SubmoduleIgnore::None)?;
+        let state = if status.is_wd_uninitialized() {
+            SubmoduleState::Uninitialized
+        } else if status.is_wd_modified() {
+            SubmoduleState::OutOfDate
+        } else if status.intersects(
+            git2::SubmoduleStatus::WD_INDEX_MODIFIED
+                | git2::SubmoduleStatus::WD_WD_MODIFIED
+                | git2::SubmoduleStatus::WD_UNTRACKED,
+        ) {
+            SubmoduleState::Modified
+        } else {
+            SubmoduleState::UpToDate
+        };
+        submodules.push(SubmoduleInfo {
+            path: submodule.path().to_string_lossy().into_owned(),
+            name,
+            state,
+            recorded: submodule.index_id(),
+            checked_out: submodule.workdir_id(),
+        });
+    }
+    Ok(submodules)
+}
+
+/// Initializes the submodule `name` if needed and checks out the commit the superproject
+/// records, cloning or fetching it first when that commit isn't there yet.
+pub fn update_submodule(repo: &Repository, name: &str) -> Result<String, git2::Error> {
+    let mut submodule = repo.find_submodule(name)?;
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(remote_callbacks(repo));
+    let mut options = git2::SubmoduleUpdateOptions::new();
+    options.fetch(fetch_options);
+    submodule.update(true, Some(&mut options))?;
+    Ok(format!("Updated submodule {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::keymap::KeyMap;
+    use crate::testing::TestRepo;
+
+    #[test]
+    fn first_commit_is_a_root_commit() {
+        let test = TestRepo::new();
+        test.write("file.txt", "content\n");
+        test.stage("file.txt");
+        create_commit(&test.repo, "first", false).unwrap();
+
+        let head = test.repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.parent_count(), 0);
+        assert_eq!(head.message(), Some("first"));
+        let tree = head.tree().unwrap();
+        assert!(tree.get_name("file.txt").is_some());
+    }
+
+    #[test]
+    fn first_commit_on_an_orphan_branch_is_a_root_commit() {
+        let test = TestRepo::new();
+        test.write("file.txt", "content\n");
+        test.stage("file.txt");
+        let main = test.commit("first");
+        let main_branch = test.repo.head().unwrap().name().unwrap().to_string();
+
+        test.repo.set_head("refs/heads/orphan").unwrap();
+        test.write("other.txt", "other\n");
+        test.stage("other.txt");
+        create_commit(&test.repo, "orphan", false).unwrap();
+
+        let head = test.repo.head().unwrap();
+        assert_eq!(head.name(), Some("refs/heads/orphan"));
+        let commit = head.peel_to_commit().unwrap();
+        assert_eq!(commit.parent_count(), 0);
+        assert_ne!(commit.id(), main);
+        // The branch that was checked out before is left alone.
+        let reference = test.repo.find_reference(&main_branch).unwrap();
+        assert_eq!(reference.target(), Some(main));
+    }
+
+    #[test]
+    fn opening_a_directory_outside_any_repository_is_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let e = open_repository(dir.path()).err().unwrap();
+        assert_eq!(e.code(), ErrorCode::NotFound);
+    }
+
+    #[test]
+    fn opening_a_bare_repository_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        Repository::init_bare(dir.path()).unwrap();
+        let e = open_repository(dir.path()).err().unwrap();
+        assert!(
+            e.message().contains("is a bare repository"),
+            "{}",
+            e.message()
+        );
+    }
+
+    #[test]
+    fn opening_a_subdirectory_finds_the_repository_root() {
+        let test = TestRepo::new();
+        test.write("a/b/file.txt", "content\n");
+        let repo = open_repository(&test.path("a/b")).unwrap();
+        let root = test.dir.path().canonicalize().unwrap();
+        assert_eq!(repo.workdir().unwrap().canonicalize().unwrap(), root);
+    }
+
+    /// The Details pane for `name`, selected in a fresh App.
+    fn details_of(test: &TestRepo, name: &str) -> App {
+        let mut app = App::new(&test.repo, KeyMap::default(), Config::default()).unwrap();
+        app.selected_index = app.files.iter().position(|file| file.name == name).unwrap();
+        update_right_pane(&test.repo, &mut app).unwrap();
+        app
+    }
+
+    fn contents(lines: &[DiffLine]) -> Vec<&str> {
+        lines.iter().map(|line| line.content.as_str()).collect()
+    }
+
+    #[test]
+    fn staged_binary_files_are_summarised() {
+        let test = TestRepo::new();
+        test.write("image.bin", b"\x89PNG\0\0\0\rIHDR\0\x01");
+        test.stage("image.bin");
+        let app = details_of(&test, "image.bin");
+        let lines = contents(&app.right_pane_lines);
+        assert!(
+            lines.contains(&"Binary file differs (14 bytes)"),
+            "{:?}",
+            lines
+        );
+        assert!(!lines.iter().any(|line| line.contains('\0')));
+        assert!(!app
+            .right_pane_lines
+            .iter()
+            .any(|line| line.kind == LineKind::Addition));
+        assert!(app.diff_hunks.is_empty());
+    }
+
+    #[test]
+    fn first_staged_file_is_diffed_against_the_empty_tree() {
+        let test = TestRepo::new();
+        test.write("file.txt", "one\ntwo\n");
+        test.stage("file.txt");
+        let app = details_of(&test, "file.txt");
+        let added: Vec<&str> = app
+            .right_pane_lines
+            .iter()
+            .filter(|line| line.kind == LineKind::Addition)
+            .map(|line| line.content.as_str())
+            .collect();
+        assert_eq!(added, ["one", "two"]);
+        assert!(!app
+            .right_pane_lines
+            .iter()
+            .any(|line| line.kind == LineKind::Deletion));
+        assert_eq!(app.diff_hunks.len(), 1);
+        let hunk = &app.diff_hunks[0];
+        assert!(hunk.staged);
+        assert_eq!((hunk.old_start, hunk.old_lines), (0, 0));
+        assert_eq!((hunk.new_start, hunk.new_lines), (1, 2));
+
+        let settings = DiffSettings {
+            context_lines: 3,
+            whitespace: Whitespace::Show,
+        };
+        let preview = staged_diff(&test.repo, false, &settings, 100).unwrap();
+        let added = preview
+            .iter()
+            .filter(|line| line.kind == LineKind::Addition)
+            .count();
+        assert_eq!(added, 2);
+    }
+
+    #[test]
+    fn staged_renames_are_shown_as_renames() {
+        let test = TestRepo::new();
+        let content = "line one\nline two\nline three\n";
+        test.write("a.txt", content);
+        test.stage("a.txt");
+        test.commit("add a");
+        // The same as `git mv a.txt c.txt`.
+        std::fs::rename(test.path("a.txt"), test.path("c.txt")).unwrap();
+        let mut index = test.repo.index().unwrap();
+        index.remove_path(Path::new("a.txt")).unwrap();
+        index.add_path(Path::new("c.txt")).unwrap();
+        index.write().unwrap();
+
+        for name in ["c.txt", "a.txt"] {
+            let app = details_of(&test, name);
+            let lines = contents(&app.right_pane_lines);
+            assert!(
+                lines.contains(&"Renamed from a.txt to c.txt"),
+                "{:?}",
+                lines
+            );
+            assert!(lines.contains(&"rename from a.txt"), "{:?}", lines);
+            assert!(lines.contains(&"rename to c.txt"), "{:?}", lines);
+            assert!(!lines.iter().any(|line| line.starts_with("deleted file")));
+            assert!(!lines.iter().any(|line| line.starts_with("new file")));
+        }
+    }
+
+    /// Signs commits in `test` with a stand-in for gpg that signs anything.
+    #[cfg(unix)]
+    fn sign_with_fake_gpg(test: &TestRepo) {
+        use std::os::unix::fs::PermissionsExt;
+        let program = test.path("fake-gpg");
+        std::fs::write(
+            &program,
+            "#!/bin/sh\ncat >/dev/null\necho '[GNUPG:] SIG_CREATED D' >&2\n\
+             printf -- '-----BEGIN PGP SIGNATURE-----\\nfake\\n-----END PGP SIGNATURE-----\\n'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut config = test.repo.config().unwrap();
+        config.set_bool("commit.gpgsign", true).unwrap();
+        config
+            .set_str("gpg.program", program.to_str().unwrap())
+            .unwrap();
+    }
+
+    #[cfg(unix)]
+    fn head_is_signed(test: &TestRepo) -> bool {
+        let head = test.repo.head().unwrap().target().unwrap();
+        test.repo.extract_signature(&head, None).is_ok()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reverts_and_merges_are_signed() {
+        let test = TestRepo::new();
+        test.write("file.txt", "one\n");
+        test.stage("file.txt");
+        test.commit("first");
+        let main = test.repo.head().unwrap().shorthand().unwrap().to_string();
+        test.write("file.txt", "one\ntwo\n");
+        test.stage("file.txt");
+        let second = test.commit("second");
+        sign_with_fake_gpg(&test);
+
+        assert!(revert_commit(&test.repo, second, true).unwrap().is_empty());
+        assert!(head_is_signed(&test));
+        let head = test.repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.summary(), Some("Revert \"second\""));
+
+        let base = test.repo.find_commit(second).unwrap();
+        test.repo.branch("topic", &base, false).unwrap();
+        test.repo.set_head("refs/heads/topic").unwrap();
+        test.repo
+            .checkout_head(Some(git2::build::CheckoutBuilder::new().force()))
+            .unwrap();
+        test.write("other.txt", "other\n");
+        test.stage("other.txt");
+        test.commit("other");
+        test.repo.set_head(&format!("refs/heads/{}", main)).unwrap();
+        test.repo
+            .checkout_head(Some(git2::build::CheckoutBuilder::new().force()))
+            .unwrap();
+        assert!(matches!(
+            merge_branch(&test.repo, "topic", true).unwrap(),
+            MergeOutcome::Merged
+        ));
+        assert!(head_is_signed(&test));
+        let head = test.repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.parent_count(), 2);
+    }
+}
@@ -0,0 +1,177 @@
+use ratatui::style::Style;
+use ratatui::text::Span;
+use std::path::Path;
+
+use crate::theme::Theme;
+
+/// Just enough about a language to colour keywords, strings, numbers and line comments.
+/// Highlighting is per line, so constructs spanning lines (block comments, multi-line
+/// strings) are only coloured on the line where they start.
+pub struct Language {
+    /// Whitespace-separated keyword list.
+    keywords: &'static str,
+    line_comment: &'static str,
+    /// Whether `'` delimits strings; otherwise only short character literals are coloured,
+    /// so Rust lifetimes aren't mistaken for an unterminated string.
+    single_quote_strings: bool,
+}
+
+const RUST: Language = Language {
+    keywords: "as async await break const continue crate dyn else enum extern false fn for if \
+        impl in let loop match mod move mut pub ref return self Self static struct \
+        super trait true type unsafe use where while",
+    line_comment: "//",
+    single_quote_strings: false,
+};
+
+const C_LIKE: Language = Language {
+    keywords: "auto break case char class const continue default delete do double else enum \
+        extern false float for goto if include inline int long namespace new nullptr \
+        private protected public return short signed sizeof static struct switch \
+        template this true typedef union unsigned using virtual void volatile while",
+    line_comment: "//",
+    single_quote_strings: false,
+};
+
+const JAVA_LIKE: Language = Language {
+    keywords: "abstract boolean break case catch class const continue default do else enum \
+        extends false final finally for if implements import instanceof int interface \
+        new null package private protected public return static super switch this \
+        throw throws true try void while",
+    line_comment: "//",
+    single_quote_strings: false,
+};
+
+const JAVASCRIPT: Language = Language {
+    keywords: "async await break case catch class const continue default delete do else \
+        export extends false finally for from function if import in instanceof \
+        interface let new null return super switch this throw true try type typeof \
+        undefined var void while yield",
+    line_comment: "//",
+    single_quote_strings: true,
+};
+
+const GO: Language = Language {
+    keywords: "break case chan const continue default defer else false for func go goto if \
+        import interface map nil package range return select struct switch true type \
+        var",
+    line_comment: "//",
+    single_quote_strings: false,
+};
+
+const PYTHON: Language = Language {
+    keywords: "and as assert async await break class continue def del elif else except False \
+        finally for from global if import in is lambda None nonlocal not or pass raise \
+        return True try while with yield",
+    line_comment: "#",
+    single_quote_strings: true,
+};
+
+const SHELL: Language = Language {
+    keywords: "case do done elif else esac export fi for function if in local return then \
+        until while",
+    line_comment: "#",
+    single_quote_strings: true,
+};
+
+const TOML: Language = Language {
+    keywords: "false true",
+    line_comment: "#",
+    single_quote_strings: true,
+};
+
+pub fn language_for_path(path: &str) -> Option<&'static Language> {
+    let extension = Path::new(path).extension()?.to_str()?;
+    match extension {
+        "rs" => Some(&RUST),
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" => Some(&C_LIKE),
+        "java" | "kt" | "cs" | "scala" => Some(&JAVA_LIKE),
+        "js" | "jsx" | "ts" | "tsx" | "mjs" => Some(&JAVASCRIPT),
+        "go" => Some(&GO),
+        "py" => Some(&PYTHON),
+        "sh" | "bash" | "zsh" => Some(&SHELL),
+        "toml" | "yaml" | "yml" => Some(&TOML),
+        _ => None,
+    }
+}
+
+/// Splits one line of code into styled spans layered over `base`, using the syntax colours
+/// from `theme`. Without a language the line is returned as a single span in the base style.
+pub fn highlight_line<'a>(
+    code: &'a str,
+    language: Option<&Language>,
+    base: Style,
+    theme: &Theme,
+) -> Vec<Span<'a>> {
+    let Some(language) = language else {
+        return vec![Span::styled(code, base)];
+    };
+
+    let mut spans = Vec::new();
+    let mut plain_start = 0;
+    let mut chars = code.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let token = if code[start..].starts_with(language.line_comment) {
+            Some((code.len(), theme.syntax_comment))
+        } else if c == '"' || (c == '\'' && is_quote_start(code, start, language)) {
+            let mut end = code.len();
+            let mut escaped = false;
+            for (index, next) in chars.by_ref() {
+                if escaped {
+                    escaped = false;
+                } else if next == '\\' {
+                    escaped = true;
+                } else if next == c {
+                    end = index + next.len_utf8();
+                    break;
+                }
+            }
+            Some((end, theme.syntax_string))
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut end = start + c.len_utf8();
+            while let Some(&(index, next)) = chars.peek() {
+                if !(next.is_alphanumeric() || next == '_') {
+                    break;
+                }
+                end = index + next.len_utf8();
+                chars.next();
+            }
+            let word = &code[start..end];
+            if c.is_ascii_digit() {
+                Some((end, theme.syntax_number))
+            } else if language.keywords.split_whitespace().any(|k| k == word) {
+                Some((end, theme.syntax_keyword))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        if let Some((end, color)) = token {
+            if plain_start < start {
+                spans.push(Span::styled(&code[plain_start..start], base));
+            }
+            spans.push(Span::styled(&code[start..end], base.fg(color)));
+            plain_start = end;
+            if end == code.len() {
+                break;
+            }
+        }
+    }
+
+    if plain_start < code.len() {
+        spans.push(Span::styled(&code[plain_start..], base));
+    }
+    spans
+}
+
+fn is_quote_start(code: &str, start: usize, language: &Language) -> bool {
+    language.single_quote_strings
+        || code[start + 1..]
+            .char_indices()
+            .take(4)
+            .skip(1)
+            .any(|(_, c)| c == '\'')
+}
@@ -0,0 +1,539 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::config_file::{self, Value};
+
+/// Everything a key can be bound to outside of text-entry modals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    FocusFiles,
+    FocusDetails,
+    CycleFocus,
+    NarrowFileList,
+    WidenFileList,
+    Select,
+    Commit,
+    Stage,
+    Unstage,
+    UnstageAll,
+    NextHunk,
+    PreviousHunk,
+    ToggleHunk,
+    SelectLines,
+    CopyDetails,
+    CopyPath,
+    NextChange,
+    PreviousChange,
+    TakeOurs,
+    TakeTheirs,
+    Rebase,
+    Reset,
+    BisectGood,
+    BisectBad,
+    BisectReset,
+    Push,
+    Fetch,
+    Pull,
+    Branches,
+    NewBranch,
+    History,
+    Reflog,
+    Revert,
+    CherryPick,
+    Blame,
+    ShowCommit,
+    NewTag,
+    ToggleDates,
+    Search,
+    NextMatch,
+    PreviousMatch,
+    CycleFilter,
+    CycleSort,
+    ToggleIgnored,
+    ToggleFileInfo,
+    MoreContext,
+    LessContext,
+    CycleWhitespace,
+    ToggleSplitView,
+    ToggleWrap,
+    FullDiff,
+    Refresh,
+    Stash,
+    StashList,
+    Submodules,
+    Help,
+    ToggleDebug,
+    Close,
+}
+
+impl Action {
+    pub const ALL: [Action; 63] = [
+        Action::Quit,
+        Action::Up,
+        Action::Down,
+        Action::PageUp,
+        Action::PageDown,
+        Action::FocusFiles,
+        Action::FocusDetails,
+        Action::CycleFocus,
+        Action::NarrowFileList,
+        Action::WidenFileList,
+        Action::Select,
+        Action::Commit,
+        Action::Stage,
+        Action::Unstage,
+        Action::UnstageAll,
+        Action::NextHunk,
+        Action::PreviousHunk,
+        Action::ToggleHunk,
+        Action::SelectLines,
+        Action::CopyDetails,
+        Action::CopyPath,
+        Action::NextChange,
+        Action::PreviousChange,
+        Action::TakeOurs,
+        Action::TakeTheirs,
+        Action::Rebase,
+        Action::Reset,
+        Action::BisectGood,
+        Action::BisectBad,
+        Action::BisectReset,
+        Action::Push,
+        Action::Fetch,
+        Action::Pull,
+        Action::Branches,
+        Action::NewBranch,
+        Action::History,
+        Action::Reflog,
+        Action::Revert,
+        Action::CherryPick,
+        Action::Blame,
+        Action::ShowCommit,
+        Action::NewTag,
+        Action::ToggleDates,
+        Action::Search,
+        Action::NextMatch,
+        Action::PreviousMatch,
+        Action::CycleFilter,
+        Action::CycleSort,
+        Action::ToggleIgnored,
+        Action::ToggleFileInfo,
+        Action::MoreContext,
+        Action::LessContext,
+        Action::CycleWhitespace,
+        Action::ToggleSplitView,
+        Action::ToggleWrap,
+        Action::FullDiff,
+        Action::Refresh,
+        Action::Stash,
+        Action::StashList,
+        Action::Submodules,
+        Action::Help,
+        Action::ToggleDebug,
+        Action::Close,
+    ];
+
+    /// The name used for the action in `keys.toml`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::FocusFiles => "focus_files",
+            Action::FocusDetails => "focus_details",
+            Action::CycleFocus => "cycle_focus",
+            Action::NarrowFileList => "narrow_file_list",
+            Action::WidenFileList => "widen_file_list",
+            Action::Select => "select",
+            Action::Commit => "commit",
+            Action::Stage => "stage",
+            Action::Unstage => "unstage",
+            Action::UnstageAll => "unstage_all",
+            Action::NextHunk => "next_hunk",
+            Action::PreviousHunk => "previous_hunk",
+            Action::ToggleHunk => "toggle_hunk",
+            Action::SelectLines => "select_lines",
+            Action::CopyDetails => "copy_details",
+            Action::CopyPath => "copy_path",
+            Action::NextChange => "next_change",
+            Action::PreviousChange => "previous_change",
+            Action::TakeOurs => "take_ours",
+            Action::TakeTheirs => "take_theirs",
+            Action::Rebase => "rebase",
+            Action::Reset => "reset",
+            Action::BisectGood => "bisect_good",
+            Action::BisectBad => "bisect_bad",
+            Action::BisectReset => "bisect_reset",
+            Action::Push => "push",
+            Action::Fetch => "fetch",
+            Action::Pull => "pull",
+            Action::Branches => "branches",
+            Action::NewBranch => "new_branch",
+            Action::History => "history",
+            Action::Reflog => "reflog",
+            Action::Revert => "revert",
+            Action::CherryPick => "cherry_pick",
+            Action::Blame => "blame",
+            Action::ShowCommit => "show_commit",
+            Action::NewTag => "new_tag",
+            Action::ToggleDates => "toggle_dates",
+            Action::Search => "search",
+            Action::NextMatch => "next_match",
+            Action::PreviousMatch => "previous_match",
+            Action::CycleFilter => "cycle_filter",
+            Action::CycleSort => "cycle_sort",
+            Action::ToggleIgnored => "toggle_ignored",
+            Action::ToggleFileInfo => "toggle_file_info",
+            Action::MoreContext => "more_context",
+            Action::LessContext => "less_context",
+            Action::CycleWhitespace => "cycle_whitespace",
+            Action::ToggleSplitView => "toggle_split_view",
+            Action::ToggleWrap => "toggle_wrap",
+            Action::FullDiff => "full_diff",
+            Action::Refresh => "refresh",
+            Action::Stash => "stash",
+            Action::StashList => "stash_list",
+            Action::Submodules => "submodules",
+            Action::Help => "help",
+            Action::ToggleDebug => "toggle_debug",
+            Action::Close => "close",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::Up => &["up"],
+            Action::Down => &["down"],
+            Action::PageUp => &["pageup"],
+            Action::PageDown => &["pagedown"],
+            Action::FocusFiles => &["left"],
+            Action::FocusDetails => &["right"],
+            Action::CycleFocus => &["f6", "ctrl+w"],
+            Action::NarrowFileList => &["<"],
+            Action::WidenFileList => &[">"],
+            Action::Select => &["enter"],
+            Action::Commit => &["c"],
+            Action::Stage => &["s"],
+            Action::Unstage => &["u"],
+            Action::UnstageAll => &["U"],
+            Action::NextHunk => &["]"],
+            Action::PreviousHunk => &["["],
+            Action::ToggleHunk => &["space"],
+            Action::SelectLines => &["V"],
+            Action::CopyDetails => &["y"],
+            Action::CopyPath => &["Y"],
+            Action::NextChange => &["tab"],
+            Action::PreviousChange => &["backtab"],
+            Action::TakeOurs => &["o"],
+            Action::TakeTheirs => &["O"],
+            Action::Rebase => &["i"],
+            Action::Reset => &["x"],
+            Action::BisectGood => &["G"],
+            Action::BisectBad => &["X"],
+            Action::BisectReset => &["E"],
+            Action::Push => &["P"],
+            Action::Fetch => &["f"],
+            Action::Pull => &["p"],
+            Action::Branches => &["b"],
+            Action::NewBranch => &["ctrl+b"],
+            Action::History => &["l"],
+            Action::Reflog => &["H"],
+            Action::Revert => &["R"],
+            Action::CherryPick => &["C"],
+            Action::Blame => &["B"],
+            Action::ShowCommit => &["g"],
+            Action::NewTag => &["t"],
+            Action::ToggleDates => &["T"],
+            Action::Search => &["/"],
+            Action::NextMatch => &["n"],
+            Action::PreviousMatch => &["N"],
+            Action::CycleFilter => &["F"],
+            Action::CycleSort => &["a"],
+            Action::ToggleIgnored => &["I"],
+            Action::ToggleFileInfo => &["z"],
+            Action::MoreContext => &["+", "="],
+            Action::LessContext => &["-"],
+            Action::CycleWhitespace => &["W"],
+            Action::ToggleSplitView => &["v"],
+            Action::ToggleWrap => &["w"],
+            Action::FullDiff => &["e"],
+            Action::Refresh => &["r", "f5"],
+            Action::Stash => &["S"],
+            Action::StashList => &["Z"],
+            Action::Submodules => &["M"],
+            Action::Help => &["?"],
+            Action::ToggleDebug => &["d"],
+            Action::Close => &["esc"],
+        }
+    }
+}
+
+/// A key with its modifiers. Shift is dropped for characters, since the character itself
+/// already says whether it was shifted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    fn from_event(key: &KeyEvent) -> Self {
+        let mut modifiers = key.modifiers;
+        // Shift is part of the character, and of backtab, rather than a separate modifier.
+        if matches!(key.code, KeyCode::Char(_) | KeyCode::BackTab) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self {
+            code: key.code,
+            modifiers,
+        }
+    }
+
+    /// How the key is written in the help, like `Ctrl+S`, `PageDown`, `↑` or `?`.
+    fn label(&self) -> String {
+        let key = match self.code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::BackTab => "Shift+Tab".to_string(),
+            KeyCode::Up => "↑".to_string(),
+            KeyCode::Down => "↓".to_string(),
+            KeyCode::Left => "←".to_string(),
+            KeyCode::Right => "→".to_string(),
+            KeyCode::F(n) => format!("F{}", n),
+            code => format!("{:?}", code),
+        };
+        let mut label = String::new();
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "Ctrl+"),
+            (KeyModifiers::ALT, "Alt+"),
+            (KeyModifiers::SHIFT, "Shift+"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                label.push_str(name);
+            }
+        }
+        label + &key
+    }
+
+    /// Parses definitions like `q`, `P`, `ctrl+a`, `enter`, `pagedown` or `f5`.
+    fn parse(definition: &str) -> Option<Self> {
+        // "+" on its own, or after another "+" as in "ctrl++", is the plus key itself.
+        let (modifier_part, key) = if definition == "+" {
+            ("", "+")
+        } else if let Some(prefix) = definition.strip_suffix("++") {
+            (prefix, "+")
+        } else {
+            definition.rsplit_once('+').unwrap_or(("", definition))
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for modifier in modifier_part.split('+').filter(|part| !part.is_empty()) {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.insert(KeyModifiers::CONTROL),
+                "alt" => modifiers.insert(KeyModifiers::ALT),
+                "shift" => modifiers.insert(KeyModifiers::SHIFT),
+                _ => return None,
+            }
+        }
+
+        let code = match key.to_ascii_lowercase().as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "space" => KeyCode::Char(' '),
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            lower => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => match lower.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                        Some(n) => KeyCode::F(n),
+                        None => return None,
+                    },
+                }
+            }
+        };
+
+        Some(Self::from_event(&KeyEvent::new(code, modifiers)))
+    }
+}
+
+/// Boolean `keys.toml` setting that turns on [`KeyMap::enable_vim_navigation`].
+const VIM_NAVIGATION_KEY: &str = "vim_navigation";
+
+pub struct KeyMap {
+    bindings: HashMap<KeyBinding, Action>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let mut bindings = HashMap::new();
+        for action in Action::ALL {
+            for definition in action.default_keys() {
+                if let Some(binding) = KeyBinding::parse(definition) {
+                    bindings.insert(binding, action);
+                }
+            }
+        }
+        Self { bindings }
+    }
+}
+
+impl KeyMap {
+    /// Adds `j`/`k` for down/up and `h`/`l` for focusing the file list/Details pane. `l`
+    /// normally opens the history, which moves to `L` while vim navigation is on.
+    pub fn enable_vim_navigation(&mut self) {
+        for (definition, action) in [
+            ("j", Action::Down),
+            ("k", Action::Up),
+            ("h", Action::FocusFiles),
+            ("l", Action::FocusDetails),
+            ("L", Action::History),
+        ] {
+            if let Some(binding) = KeyBinding::parse(definition) {
+                self.bindings.insert(binding, action);
+            }
+        }
+    }
+
+    pub fn action_for(&self, key: &KeyEvent) -> Option<Action> {
+        self.bindings.get(&KeyBinding::from_event(key)).copied()
+    }
+
+    /// The keys bound to `action` as written in the help, named keys like `↑` before
+    /// characters, or an empty list when it has none.
+    pub fn labels_for(&self, action: Action) -> Vec<String> {
+        let mut keys: Vec<&KeyBinding> = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(binding, _)| binding)
+            .collect();
+        keys.sort_by_key(|binding| (matches!(binding.code, KeyCode::Char(_)), binding.label()));
+        keys.into_iter().map(KeyBinding::label).collect()
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        Some(config_file::config_dir()?.join("keys.toml"))
+    }
+
+    /// Loads the key map from `path`. A missing file gives the defaults; problems with
+    /// individual entries are returned as warnings and leave those actions on their defaults.
+    pub fn load(path: &Path) -> (Self, Vec<String>) {
+        let mut keymap = Self::default();
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return (keymap, Vec::new()),
+            Err(e) => return (keymap, vec![format!("{}: {}", path.display(), e)]),
+        };
+        let entries = match config_file::parse(&text) {
+            Ok(entries) => entries,
+            Err(e) => return (keymap, vec![format!("{}: {}", path.display(), e)]),
+        };
+
+        let mut warnings = Vec::new();
+        // Applied first so that explicitly configured actions override the vim keys.
+        if entries
+            .iter()
+            .any(|entry| entry.key == VIM_NAVIGATION_KEY && entry.value == Value::Boolean(true))
+        {
+            keymap.enable_vim_navigation();
+        }
+
+        for entry in entries {
+            let warn = |message: String| format!("{}:{}: {}", path.display(), entry.line, message);
+            if entry.key == VIM_NAVIGATION_KEY {
+                if !matches!(entry.value, Value::Boolean(_)) {
+                    warnings.push(warn("expected true or false".to_string()));
+                }
+                continue;
+            }
+            let Some(action) = Action::from_name(&entry.key) else {
+                warnings.push(warn(format!("unknown action `{}`", entry.key)));
+                continue;
+            };
+            let definitions = match &entry.value {
+                Value::String(definition) => vec![definition.as_str()],
+                Value::Array(values) => values
+                    .iter()
+                    .filter_map(|value| match value {
+                        Value::String(definition) => Some(definition.as_str()),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => {
+                    warnings.push(warn("expected a key or a list of keys".to_string()));
+                    continue;
+                }
+            };
+            let mut parsed = Vec::new();
+            for definition in definitions {
+                match KeyBinding::parse(definition) {
+                    Some(binding) => parsed.push(binding),
+                    None => warnings.push(warn(format!("unrecognised key `{}`", definition))),
+                }
+            }
+            if parsed.is_empty() {
+                continue;
+            }
+            // Configured keys replace the action's defaults rather than adding to them.
+            keymap.bindings.retain(|_, bound| *bound != action);
+            for binding in parsed {
+                keymap.bindings.insert(binding, action);
+            }
+        }
+
+        (keymap, warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_keys_are_not_shared() {
+        let mut seen = HashMap::new();
+        for action in Action::ALL {
+            for definition in action.default_keys() {
+                let binding = KeyBinding::parse(definition).unwrap();
+                if let Some(other) = seen.insert(binding, action) {
+                    panic!(
+                        "{} is bound to both {:?} and {:?}",
+                        definition, other, action
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn blame_and_new_branch_keys() {
+        let keymap = KeyMap::default();
+        assert_eq!(keymap.labels_for(Action::Blame), ["B"]);
+        assert_eq!(keymap.labels_for(Action::NewBranch), ["Ctrl+b"]);
+    }
+}
@@ -1,74 +1,225 @@
 mod app;
+mod bisect;
+mod clipboard;
+mod config;
+mod config_file;
 mod debug;
+mod editor;
 mod file_system;
 mod git_ops;
+mod highlight;
+mod keymap;
+mod rebase;
+mod signing;
+#[cfg(test)]
+mod testing;
+mod theme;
+mod time_format;
 mod ui;
+mod watcher;
+mod worker;
 
 use crossterm::{
-    event::{self, DisableMouseCapture, Event, KeyCode, KeyEvent},
+    cursor,
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event},
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
+    },
 };
 use git2::Repository;
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
-use std::io;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
 use std::time::Duration;
 
 use crate::app::{App, AppResult};
+use crate::config::Config;
+use crate::git_ops::open_repository;
+use crate::keymap::{Action, KeyMap};
+use crate::theme::DiffColors;
 use crate::ui::draw;
 
-fn main() -> AppResult<()> {
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("gitui: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> AppResult<()> {
     // Initialize debug channel
     let debug_receiver = debug::init_debug();
 
-    // Setup terminal
+    // Create app state before touching the terminal, so that startup errors are printed to
+    // a normal screen.
+    let path = repo_path_from_args()?;
+    let repo = open_repository(&path).map_err(|e| {
+        if e.code() == git2::ErrorCode::NotFound {
+            format!("{} is not inside a git repository", path.display())
+        } else {
+            e.message().to_string()
+        }
+    })?;
+    let (keymap, keymap_warnings) = KeyMap::default_path()
+        .map(|path| KeyMap::load(&path))
+        .unwrap_or_default();
+    for warning in keymap_warnings {
+        debug::debug_log(&format!("keys.toml: {}", warning));
+    }
+    let (mut config, config_warnings) = Config::default_path()
+        .map(|path| Config::load(&path))
+        .unwrap_or_default();
+    for warning in config_warnings {
+        debug::debug_log(&format!("config.toml: {}", warning));
+    }
+    // git's diff colours replace the theme's, and the ones from config.toml replace both.
+    config.theme = config
+        .theme
+        .with_diff_colors(&DiffColors::from_git(&repo))
+        .with_diff_colors(&config.colors);
+    let mut app = App::new(&repo, keymap, config)
+        .map_err(|e| format!("failed to read the repository status: {}", e))?;
+
+    // Setup terminal, putting it back before a panic message is printed so that the message
+    // is readable and the shell still usable afterwards.
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        let _ = restore_title();
+        default_hook(info);
+    }));
     enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    save_title()?;
+    let result = run_app(&mut app, &repo, &debug_receiver);
+
+    // Restore terminal, even if the main loop failed
+    restore_terminal()?;
+    restore_title()?;
+    result
+}
+
+/// The title can't be read back, so it is pushed onto the terminal's title stack, an xterm
+/// extension that most terminals support and the rest ignore.
+fn save_title() -> io::Result<()> {
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    stdout.write_all(b"\x1b[22;0t")?;
+    stdout.flush()
+}
 
-    // Create app state
-    let repo = Repository::open(".").expect("Failed to open repository");
-    let mut app = App::new(&repo);
+fn restore_title() -> io::Result<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(b"\x1b[23;0t")?;
+    stdout.flush()
+}
+
+/// The repository's name, the branch and a `*` when there are uncommitted changes.
+fn window_title(app: &App, repo: &Repository) -> String {
+    let root = repo.workdir().unwrap_or(repo.path());
+    let name = root
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| root.display().to_string());
+    let dirty = app.status_summary.is_dirty();
+    format!(
+        "{} ({}){} - gitui",
+        name,
+        app.head_status.branch.trim_matches(['(', ')']),
+        if dirty { " *" } else { "" }
+    )
+}
+
+/// The repository to open: the one argument given, or the current directory.
+fn repo_path_from_args() -> AppResult<PathBuf> {
+    let mut args = std::env::args_os().skip(1);
+    let path = args.next();
+    if args.next().is_some() {
+        return Err("too many arguments\nusage: gitui [path]".into());
+    }
+    match path {
+        Some(arg) if arg == "-h" || arg == "--help" => {
+            println!("usage: gitui [path]\n\nOpens the git repository containing path, by default the current directory.");
+            std::process::exit(0);
+        }
+        Some(path) => Ok(PathBuf::from(path)),
+        None => Ok(PathBuf::from(".")),
+    }
+}
+
+fn restore_terminal() -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        cursor::Show
+    )
+}
+
+/// Hands the terminal to `f`, for running a program like an editor, and takes it back after.
+fn suspend_terminal<T>(f: impl FnOnce() -> T) -> io::Result<T> {
+    restore_terminal()?;
+    let result = f();
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    Ok(result)
+}
+
+fn run_app(app: &mut App, repo: &Repository, debug_receiver: &Receiver<String>) -> AppResult<()> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    // Changes made outside gitui are picked up from the watcher and read on the worker
+    // thread; actions taken inside gitui refresh the file list straight away.
+    let changes = watcher::watch(repo);
+    let mut title = String::new();
 
     // Main loop
     loop {
-        // Refresh file list
-        app.refresh_file_list(&repo);
-        terminal.draw(|f| draw(f, &mut app))?;
+        if changes.try_recv().is_ok() {
+            app.request_file_list();
+        }
+        let new_title = window_title(app, repo);
+        if new_title != title {
+            execute!(io::stdout(), SetTitle(&new_title))?;
+            title = new_title;
+        }
+        terminal.draw(|f| draw(f, app))?;
 
         // Check for debug messages
-        if let Ok(debug_message) = debug_receiver.try_recv() {
+        while let Ok(debug_message) = debug_receiver.try_recv() {
             app.debug_log(&debug_message);
         }
+        app.handle_worker_results(repo);
 
         if event::poll(Duration::from_millis(16))? {
             if let Ok(event) = event::read() {
                 match event {
-                    Event::Key(KeyEvent {
-                        code: KeyCode::Char('q'),
-                        ..
-                    }) => {
-                        if !app.commit_modal.is_visible && !app.help_modal.is_visible {
-                            break;
-                        }
+                    Event::Key(key)
+                        if !app.has_open_modal()
+                            && app.keymap.action_for(&key) == Some(Action::Quit) =>
+                    {
+                        app.request_quit()
                     }
-                    _ => app.handle_event(event, &repo)?,
+                    // Resize the buffers straight away; the next draw then recomputes the
+                    // layout, including the pane areas used for mouse hit-testing.
+                    Event::Resize(_, _) => terminal.autoresize()?,
+                    _ => app.handle_event(event, repo)?,
                 }
             }
         }
+        if app.should_quit {
+            break;
+        }
+        if let Some(draft) = app.take_editor_request() {
+            let message = suspend_terminal(|| editor::compose_message(repo, &draft))?;
+            terminal.clear()?;
+            // The editor may have set a title of its own.
+            title.clear();
+            app.finish_editor_commit(repo, message)?;
+        }
     }
 
-    // Restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
-
     Ok(())
 }
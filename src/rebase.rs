@@ -0,0 +1,247 @@
+use git2::{Commit, Oid, Repository, ResetType, Status, StatusOptions};
+use std::collections::{HashSet, VecDeque};
+
+use crate::git_ops::{conflicted_paths, head_commit, write_commit};
+
+/// What an interactive rebase does with a commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TodoAction {
+    Pick,
+    /// Pick with a new message.
+    Reword,
+    /// Fold into the commit before, combining the messages.
+    Squash,
+}
+
+impl TodoAction {
+    pub fn name(self) -> &'static str {
+        match self {
+            TodoAction::Pick => "pick",
+            TodoAction::Reword => "reword",
+            TodoAction::Squash => "squash",
+        }
+    }
+}
+
+/// One line of the rebase todo list.
+#[derive(Debug, Clone)]
+pub struct TodoItem {
+    pub action: TodoAction,
+    pub id: Oid,
+    pub summary: String,
+    /// The message the rewritten commit gets: the original one unless reworded.
+    pub message: String,
+}
+
+/// The todo list for rebasing the commits from `oid` up to HEAD, oldest first, all picked.
+/// Only a straight line of ordinary commits can be rebased, and not the root commit, which
+/// has nothing to rebase onto.
+pub fn todo_list(repo: &Repository, oid: Oid) -> Result<Vec<TodoItem>, git2::Error> {
+    let mut commit =
+        head_commit(repo)?.ok_or_else(|| git2::Error::from_str("HEAD has no commit"))?;
+    let mut items = Vec::new();
+    loop {
+        if commit.parent_count() > 1 {
+            return Err(git2::Error::from_str(
+                "rebasing across a merge commit is not supported",
+            ));
+        }
+        items.push(TodoItem {
+            action: TodoAction::Pick,
+            id: commit.id(),
+            summary: commit.summary().unwrap_or_default().to_string(),
+            message: commit.message().unwrap_or_default().to_string(),
+        });
+        if commit.id() == oid {
+            break;
+        }
+        commit = commit
+            .parent(0)
+            .map_err(|_| git2::Error::from_str("the commit is not on the current branch"))?;
+    }
+    if commit.parent_count() == 0 {
+        return Err(git2::Error::from_str(
+            "rebasing the root commit is not supported",
+        ));
+    }
+    items.reverse();
+    Ok(items)
+}
+
+/// How far `Rebase::start` or `Rebase::resume` got.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RebaseProgress {
+    Finished,
+    /// The step stopped with these paths conflicted. Once they are resolved and staged, the
+    /// rebase is resumed.
+    Conflicts(Vec<String>),
+}
+
+/// An interactive rebase in progress. The current branch is reset to the parent of the
+/// first commit and the todo list is replayed on top of it one cherry-pick at a time, so
+/// that it can be reordered, which git2's `Rebase` can't do.
+///
+/// The state only lives as long as gitui does. `ORIG_HEAD` points at the branch as it was
+/// before, as with `git rebase`, for getting back to it by hand.
+pub struct Rebase {
+    todo: VecDeque<TodoItem>,
+    /// The step that stopped on conflicts, committed when the rebase is resumed.
+    stopped: Option<TodoItem>,
+    orig_head: Oid,
+    total: usize,
+    /// Whether a commit that can't be signed stops the rebase, as `commit.require_signing` asks.
+    require_signature: bool,
+}
+
+impl Rebase {
+    pub fn start(
+        repo: &Repository,
+        items: Vec<TodoItem>,
+        require_signature: bool,
+    ) -> Result<(Self, RebaseProgress), git2::Error> {
+        let Some(first) = items.first() else {
+            return Err(git2::Error::from_str("there is nothing to rebase"));
+        };
+        if first.action == TodoAction::Squash {
+            return Err(git2::Error::from_str(
+                "the first commit has no commit before it to squash into",
+            ));
+        }
+        if has_tracked_changes(repo)? {
+            return Err(git2::Error::from_str(
+                "there are uncommitted changes; commit or stash them first",
+            ));
+        }
+        let head = head_commit(repo)?.ok_or_else(|| git2::Error::from_str("HEAD has no commit"))?;
+        // Whatever the order now, the commits go back on top of the one the oldest was on.
+        let ids: HashSet<Oid> = items.iter().map(|item| item.id).collect();
+        let mut base = None;
+        for item in &items {
+            let parent = repo.find_commit(item.id)?.parent(0)?;
+            if !ids.contains(&parent.id()) {
+                base = Some(parent);
+            }
+        }
+        let base = base.ok_or_else(|| git2::Error::from_str("the commits have no base"))?;
+
+        repo.reference("ORIG_HEAD", head.id(), true, "rebase (start)")?;
+        repo.reset(base.as_object(), ResetType::Hard, None)?;
+        let mut rebase = Self {
+            total: items.len(),
+            todo: items.into(),
+            stopped: None,
+            orig_head: head.id(),
+            require_signature,
+        };
+        let progress = rebase.run(repo)?;
+        Ok((rebase, progress))
+    }
+
+    /// Continues after the conflicts of the stopped step have been resolved and staged.
+    pub fn resume(&mut self, repo: &Repository) -> Result<RebaseProgress, git2::Error> {
+        let conflicts = conflicted_paths(repo)?;
+        if !conflicts.is_empty() {
+            return Err(git2::Error::from_str(&format!(
+                "resolve and stage the conflicts in {} first",
+                conflicts.join(", ")
+            )));
+        }
+        if let Some(item) = self.stopped.take() {
+            commit_step(repo, &item, self.require_signature)?;
+        }
+        self.run(repo)
+    }
+
+    /// Puts the branch back where it was before the rebase, discarding its progress.
+    pub fn abort(self, repo: &Repository) -> Result<(), git2::Error> {
+        repo.cleanup_state()?;
+        let orig_head = repo.find_object(self.orig_head, None)?;
+        repo.reset(&orig_head, ResetType::Hard, None)
+    }
+
+    /// The step the rebase stopped at, counting from 1, and the number of steps.
+    pub fn position(&self) -> (usize, usize) {
+        (self.total - self.todo.len(), self.total)
+    }
+
+    /// The stopped step followed by the ones still to run.
+    pub fn remaining(&self) -> impl Iterator<Item = &TodoItem> {
+        self.stopped.iter().chain(self.todo.iter())
+    }
+
+    fn run(&mut self, repo: &Repository) -> Result<RebaseProgress, git2::Error> {
+        while let Some(item) = self.todo.pop_front() {
+            let commit = repo.find_commit(item.id)?;
+            repo.cherrypick(&commit, None)?;
+            let conflicts = conflicted_paths(repo)?;
+            if !conflicts.is_empty() {
+                self.stopped = Some(item);
+                return Ok(RebaseProgress::Conflicts(conflicts));
+            }
+            commit_step(repo, &item, self.require_signature)?;
+        }
+        Ok(RebaseProgress::Finished)
+    }
+}
+
+/// Commits the index for `item` and moves the branch to the new commit. A squash replaces
+/// the commit before it; a pick whose changes are already there is dropped.
+fn commit_step(
+    repo: &Repository,
+    item: &TodoItem,
+    require_signature: bool,
+) -> Result<(), git2::Error> {
+    let mut index = repo.index()?;
+    index.read(false)?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let head = head_commit(repo)?.ok_or_else(|| git2::Error::from_str("HEAD has no commit"))?;
+    let committer = repo.signature()?;
+
+    let new_commit = if item.action == TodoAction::Squash {
+        let parents: Vec<Commit> = head.parents().collect();
+        let message = format!(
+            "{}\n\n{}",
+            head.message().unwrap_or_default().trim_end(),
+            item.message
+        );
+        let parents: Vec<&Commit> = parents.iter().collect();
+        write_commit(
+            repo,
+            &head.author(),
+            &committer,
+            &message,
+            &tree,
+            &parents,
+            require_signature,
+        )?
+    } else if tree.id() == head.tree_id() {
+        repo.cleanup_state()?;
+        return Ok(());
+    } else {
+        let author = repo.find_commit(item.id)?.author().to_owned();
+        write_commit(
+            repo,
+            &author,
+            &committer,
+            &item.message,
+            &tree,
+            &[&head],
+            require_signature,
+        )?
+    };
+    repo.head()?.set_target(
+        new_commit,
+        &format!("rebase: {} {}", item.action.name(), item.summary),
+    )?;
+    repo.cleanup_state()
+}
+
+/// Whether tracked files have staged or unstaged changes, which a hard reset would lose.
+pub fn has_tracked_changes(repo: &Repository) -> Result<bool, git2::Error> {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(false).include_ignored(false);
+    Ok(repo
+        .statuses(Some(&mut opts))?
+        .iter()
+        .any(|entry| entry.status() != Status::CURRENT))
+}
@@ -0,0 +1,425 @@
+use git2::{ErrorCode, Oid, Repository};
+use std::fmt;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Whether a commit is signed and whether the signature checks out, as far as the gpg or
+/// ssh-keygen git is configured with can tell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    Unsigned,
+    /// The signature is good, made by this signer.
+    Good(String),
+    /// The signature doesn't match the commit.
+    Bad,
+    /// Signed, but whether by a trusted key couldn't be told, for this reason.
+    Unverified(String),
+}
+
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureStatus::Unsigned => write!(f, "[unsigned]"),
+            SignatureStatus::Good(signer) => write!(f, "[signed] {}", signer),
+            SignatureStatus::Bad => write!(f, "[invalid]"),
+            SignatureStatus::Unverified(reason) => write!(f, "[signed, unverified] {}", reason),
+        }
+    }
+}
+
+/// The signature formats git knows, told apart by their armor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    OpenPgp,
+    X509,
+    Ssh,
+}
+
+impl Format {
+    fn of(signature: &str) -> Option<Self> {
+        let first_line = signature.lines().next().unwrap_or_default().trim();
+        match first_line {
+            "-----BEGIN PGP SIGNATURE-----" | "-----BEGIN PGP MESSAGE-----" => {
+                Some(Format::OpenPgp)
+            }
+            "-----BEGIN SIGNED MESSAGE-----" => Some(Format::X509),
+            "-----BEGIN SSH SIGNATURE-----" => Some(Format::Ssh),
+            _ => None,
+        }
+    }
+
+    /// The format new signatures are made in, from `gpg.format`.
+    fn configured(repo: &Repository) -> Result<Self, String> {
+        let format = repo
+            .config()
+            .ok()
+            .and_then(|config| config.get_string("gpg.format").ok());
+        match format.as_deref() {
+            None | Some("openpgp") => Ok(Format::OpenPgp),
+            Some("x509") => Ok(Format::X509),
+            Some("ssh") => Ok(Format::Ssh),
+            Some(other) => Err(format!("unknown gpg.format `{}`", other)),
+        }
+    }
+
+    /// The program for this format, from `gpg.<format>.program` as with git.
+    fn program(self, repo: &Repository) -> String {
+        let config = repo.config().ok();
+        let get = |name: &str| config.as_ref().and_then(|c| c.get_string(name).ok());
+        match self {
+            Format::OpenPgp => get("gpg.openpgp.program")
+                .or_else(|| get("gpg.program"))
+                .unwrap_or_else(|| "gpg".to_string()),
+            Format::X509 => get("gpg.x509.program").unwrap_or_else(|| "gpgsm".to_string()),
+            Format::Ssh => get("gpg.ssh.program").unwrap_or_else(|| "ssh-keygen".to_string()),
+        }
+    }
+}
+
+/// Whether `commit.gpgsign` asks for new commits to be signed.
+pub fn signing_enabled(repo: &Repository) -> bool {
+    repo.config()
+        .and_then(|config| config.get_bool("commit.gpgsign"))
+        .unwrap_or(false)
+}
+
+/// Signs the contents of a commit the way git would, in `gpg.format` with the key from
+/// `user.signingkey`, and returns the armored signature.
+pub fn sign(repo: &Repository, content: &str) -> Result<String, String> {
+    let format = Format::configured(repo)?;
+    let program = format.program(repo);
+    let key = repo
+        .config()
+        .ok()
+        .and_then(|config| config.get_string("user.signingkey").ok())
+        .filter(|key| !key.is_empty());
+    let result = match format {
+        Format::OpenPgp | Format::X509 => {
+            // Like git, gpg picks the key from the committer's identity when none is set.
+            let key = match key {
+                Some(key) => key,
+                None => {
+                    let committer = repo.signature().map_err(|e| e.message().to_string())?;
+                    format!(
+                        "{} <{}>",
+                        committer.name().unwrap_or_default(),
+                        committer.email().unwrap_or_default()
+                    )
+                }
+            };
+            sign_gpg(&program, &key, content)
+        }
+        Format::Ssh => {
+            let key = key.ok_or("user.signingkey is not set")?;
+            sign_ssh(&program, &key, content)
+        }
+    };
+    result.map_err(|e| format!("{}: {}", program, e))
+}
+
+fn sign_gpg(program: &str, key: &str, content: &str) -> std::io::Result<String> {
+    let mut command = Command::new(program);
+    command.args(["--status-fd=2", "-bsau", key]);
+    let output = run(command, content.as_bytes())?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() || !stderr.contains("[GNUPG:] SIG_CREATED ") {
+        return Err(std::io::Error::other(last_message(&stderr)));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `key` is the path of a key file, or the public key itself for a key in the ssh agent.
+fn sign_ssh(program: &str, key: &str, content: &str) -> std::io::Result<String> {
+    let dir = TempDir::new()?;
+    let buffer = dir.write("commit", content.as_bytes())?;
+    let literal_key = key
+        .strip_prefix("key::")
+        .or_else(|| key.starts_with("ssh-").then_some(key));
+    let mut command = Command::new(program);
+    command.args(["-Y", "sign", "-n", "git", "-f"]);
+    match literal_key {
+        Some(literal_key) => {
+            command
+                .arg(dir.write("key.pub", literal_key.as_bytes())?)
+                .arg("-U");
+        }
+        None => {
+            command.arg(expand_home(key));
+        }
+    }
+    command.arg(&buffer);
+    let output = run(command, &[])?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(last_message(
+            &String::from_utf8_lossy(&output.stderr),
+        )));
+    }
+    // ssh-keygen writes the signature next to what it signed.
+    std::fs::read_to_string(dir.path("commit.sig"))
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// The last line a program printed to stderr, which is usually why it failed.
+fn last_message(stderr: &str) -> String {
+    stderr
+        .lines()
+        .rev()
+        .find(|line| !line.starts_with("[GNUPG:]") && !line.trim().is_empty())
+        .unwrap_or("failed without saying why")
+        .to_string()
+}
+
+/// Checks the signature of commit `oid`. Problems running the verifier are reported as an
+/// unverified signature rather than an error, so that the commit can still be shown.
+pub fn verify_commit(repo: &Repository, oid: Oid) -> SignatureStatus {
+    let (signature, signed_data) = match repo.extract_signature(&oid, None) {
+        Ok(parts) => parts,
+        Err(e) if e.code() == ErrorCode::NotFound => return SignatureStatus::Unsigned,
+        Err(e) => return SignatureStatus::Unverified(e.message().to_string()),
+    };
+    let signature = String::from_utf8_lossy(&signature).into_owned();
+    let Some(format) = Format::of(&signature) else {
+        return SignatureStatus::Unverified("unknown signature format".to_string());
+    };
+    // The directory goes, with the signature in it, at the end of the function.
+    let (_dir, file) = match TempDir::new().and_then(|dir| {
+        let file = dir.write(&format!("{}.sig", oid), signature.as_bytes())?;
+        Ok((dir, file))
+    }) {
+        Ok(saved) => saved,
+        Err(e) => {
+            return SignatureStatus::Unverified(format!("couldn't save the signature: {}", e))
+        }
+    };
+    let program = format.program(repo);
+    let result = match format {
+        Format::OpenPgp | Format::X509 => verify_gpg(&program, &file, &signed_data),
+        Format::Ssh => verify_ssh(repo, &program, &file, &signed_data),
+    };
+    result.unwrap_or_else(|e| SignatureStatus::Unverified(format!("{}: {}", program, e)))
+}
+
+/// Reads gpg's machine-readable status lines, which are the same for gpgsm.
+fn verify_gpg(program: &str, signature: &Path, data: &[u8]) -> std::io::Result<SignatureStatus> {
+    let mut command = Command::new(program);
+    command
+        .arg("--status-fd=1")
+        .arg("--verify")
+        .arg(signature)
+        .arg("-");
+    let output = run(command, data)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut status = None;
+    for line in stdout.lines() {
+        let Some(line) = line.strip_prefix("[GNUPG:] ") else {
+            continue;
+        };
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        // The key id comes before the user id.
+        let signer = || {
+            rest.split_once(' ')
+                .map_or(rest, |(_, uid)| uid)
+                .to_string()
+        };
+        status = match keyword {
+            "GOODSIG" => Some(SignatureStatus::Good(signer())),
+            "BADSIG" => Some(SignatureStatus::Bad),
+            "EXPKEYSIG" => Some(SignatureStatus::Unverified(format!(
+                "key of {} has expired",
+                signer()
+            ))),
+            "REVKEYSIG" => Some(SignatureStatus::Unverified(format!(
+                "key of {} has been revoked",
+                signer()
+            ))),
+            "NO_PUBKEY" => Some(SignatureStatus::Unverified(format!(
+                "no public key {}",
+                rest
+            ))),
+            _ => status,
+        };
+    }
+    Ok(status.unwrap_or_else(|| {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        SignatureStatus::Unverified(stderr.lines().last().unwrap_or("no verdict").to_string())
+    }))
+}
+
+/// SSH signatures are checked against `gpg.ssh.allowedSignersFile`. Without one, or for a
+/// key that isn't in it, the signature can only be checked to match the commit.
+fn verify_ssh(
+    repo: &Repository,
+    program: &str,
+    signature: &Path,
+    data: &[u8],
+) -> std::io::Result<SignatureStatus> {
+    let allowed_signers = repo
+        .config()
+        .ok()
+        .and_then(|config| config.get_path("gpg.ssh.allowedSignersFile").ok());
+    let Some(allowed_signers) = allowed_signers else {
+        return check_ssh_signature(
+            program,
+            signature,
+            data,
+            "gpg.ssh.allowedSignersFile is not set",
+        );
+    };
+
+    let mut find = Command::new(program);
+    find.args(["-Y", "find-principals", "-f"])
+        .arg(&allowed_signers)
+        .arg("-s")
+        .arg(signature);
+    let found = run(find, &[])?;
+    let principals = String::from_utf8_lossy(&found.stdout);
+    let Some(principal) = principals.lines().next().filter(|_| found.status.success()) else {
+        return check_ssh_signature(program, signature, data, "the key is not an allowed signer");
+    };
+
+    let mut verify = Command::new(program);
+    verify
+        .args(["-Y", "verify", "-n", "git", "-f"])
+        .arg(&allowed_signers)
+        .args(["-I", principal, "-s"])
+        .arg(signature);
+    Ok(if run(verify, data)?.status.success() {
+        SignatureStatus::Good(principal.to_string())
+    } else {
+        SignatureStatus::Bad
+    })
+}
+
+/// Checks only that the signature matches the commit, whoever's key made it.
+fn check_ssh_signature(
+    program: &str,
+    signature: &Path,
+    data: &[u8],
+    reason: &str,
+) -> std::io::Result<SignatureStatus> {
+    let mut check = Command::new(program);
+    check
+        .args(["-Y", "check-novalidate", "-n", "git", "-s"])
+        .arg(signature);
+    Ok(if run(check, data)?.status.success() {
+        SignatureStatus::Unverified(reason.to_string())
+    } else {
+        SignatureStatus::Bad
+    })
+}
+
+/// Runs `command` with `input` on stdin and waits for it to exit.
+fn run(mut command: Command, input: &[u8]) -> std::io::Result<Output> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Dropped straight after writing, so the program sees the end of its input. One that
+    // gives up without reading all of it still has its say in its output.
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let written = stdin.write_all(input);
+    drop(stdin);
+    let output = child.wait_with_output()?;
+    match written {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e),
+        _ => Ok(output),
+    }
+}
+
+/// A directory only the current user can get into, for the files handed to gpg or
+/// ssh-keygen. It is removed, with everything in it, once they are done with.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> std::io::Result<Self> {
+        let mut builder = std::fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.subsec_nanos());
+        let mut attempt = 0;
+        loop {
+            let path = std::env::temp_dir().join(format!(
+                "gitui-{}-{:x}",
+                std::process::id(),
+                nanos.wrapping_add(attempt)
+            ));
+            // Creating the directory fails, rather than taking it over, when something by
+            // that name is already there, so nobody else can have put files in it or made
+            // it a link to somewhere else.
+            match builder.create(&path) {
+                Ok(()) => return Ok(Self(path)),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    /// Writes a new file in the directory, failing if there is one by that name already.
+    fn write(&self, name: &str, content: &[u8]) -> std::io::Result<PathBuf> {
+        let path = self.path(name);
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?
+            .write_all(content)?;
+        Ok(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_dir_is_private_and_removed_when_dropped() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.0.clone();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        let file = dir.write("commit", b"content").unwrap();
+        assert_eq!(std::fs::read(&file).unwrap(), b"content");
+        drop(dir);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn temp_dir_writes_only_new_files() {
+        let dir = TempDir::new().unwrap();
+        dir.write("commit", b"first").unwrap();
+        let e = dir.write("commit", b"second").unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read(dir.path("commit")).unwrap(), b"first");
+    }
+
+    #[test]
+    fn temp_dirs_are_distinct() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        assert_ne!(first.0, second.0);
+    }
+}
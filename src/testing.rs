@@ -0,0 +1,65 @@
+use git2::{Oid, Repository};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// A repository in a temporary directory, removed again when the test is done with it.
+pub struct TestRepo {
+    pub dir: TempDir,
+    pub repo: Repository,
+}
+
+impl TestRepo {
+    /// An empty repository with an author configured, so that commits can be made.
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        // Whatever the global config says, commits aren't signed unless a test asks.
+        config.set_bool("commit.gpgsign", false).unwrap();
+        // Logging goes through the debug channel, which has to be set up first.
+        let _ = crate::debug::init_debug();
+        Self { dir, repo }
+    }
+
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.dir.path().join(relative)
+    }
+
+    /// Writes a file in the working tree, creating the directories above it.
+    pub fn write(&self, relative: &str, content: impl AsRef<[u8]>) {
+        let path = self.path(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    pub fn stage(&self, relative: &str) {
+        let mut index = self.repo.index().unwrap();
+        index.add_path(Path::new(relative)).unwrap();
+        index.write().unwrap();
+    }
+
+    /// Commits the index on top of HEAD, if there is a HEAD commit yet.
+    pub fn commit(&self, message: &str) -> Oid {
+        let signature = self.repo.signature().unwrap();
+        let tree_id = self.repo.index().unwrap().write_tree().unwrap();
+        let tree = self.repo.find_tree(tree_id).unwrap();
+        let parent = self
+            .repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        self.repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents,
+            )
+            .unwrap()
+    }
+}
@@ -0,0 +1,271 @@
+use ratatui::style::Color;
+
+/// Colours used throughout the UI, selected with `theme = "dark"` or `theme = "light"` in
+/// `config.toml`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub border: Color,
+    pub focused_border: Color,
+    pub text: Color,
+    /// De-emphasised text such as line numbers and commit authors.
+    pub muted: Color,
+    pub error: Color,
+
+    pub file_new: Color,
+    pub file_modified: Color,
+    pub file_deleted: Color,
+    /// The index and working tree letters of a file's status.
+    pub status_staged: Color,
+    pub status_unstaged: Color,
+    pub current_branch: Color,
+    pub commit_id: Color,
+
+    pub addition: Color,
+    pub addition_background: Color,
+    pub deletion: Color,
+    pub deletion_background: Color,
+    pub hunk_header: Color,
+    pub selected_hunk_background: Color,
+    /// Conflicted files, and the markers around the conflicting regions.
+    pub conflict: Color,
+    pub conflict_ours_background: Color,
+    pub conflict_theirs_background: Color,
+
+    pub syntax_keyword: Color,
+    pub syntax_string: Color,
+    pub syntax_number: Color,
+    pub syntax_comment: Color,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            border: Color::White,
+            focused_border: Color::Cyan,
+            text: Color::White,
+            muted: Color::DarkGray,
+            error: Color::Red,
+
+            file_new: Color::Green,
+            file_modified: Color::Yellow,
+            file_deleted: Color::Red,
+            status_staged: Color::Green,
+            status_unstaged: Color::Red,
+            current_branch: Color::Green,
+            commit_id: Color::Yellow,
+
+            addition: Color::Green,
+            addition_background: Color::Indexed(22),
+            deletion: Color::Red,
+            deletion_background: Color::Indexed(52),
+            hunk_header: Color::Cyan,
+            selected_hunk_background: Color::DarkGray,
+            conflict: Color::Magenta,
+            conflict_ours_background: Color::Indexed(17),
+            conflict_theirs_background: Color::Indexed(53),
+
+            syntax_keyword: Color::Magenta,
+            syntax_string: Color::Yellow,
+            syntax_number: Color::Cyan,
+            syntax_comment: Color::DarkGray,
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            border: Color::Black,
+            focused_border: Color::Blue,
+            text: Color::Black,
+            muted: Color::Gray,
+            error: Color::Red,
+
+            file_new: Color::Indexed(28),
+            file_modified: Color::Indexed(130),
+            file_deleted: Color::Indexed(124),
+            status_staged: Color::Indexed(28),
+            status_unstaged: Color::Indexed(124),
+            current_branch: Color::Indexed(28),
+            commit_id: Color::Indexed(130),
+
+            addition: Color::Indexed(28),
+            addition_background: Color::Indexed(194),
+            deletion: Color::Indexed(124),
+            deletion_background: Color::Indexed(224),
+            hunk_header: Color::Blue,
+            selected_hunk_background: Color::Indexed(254),
+            conflict: Color::Indexed(90),
+            conflict_ours_background: Color::Indexed(189),
+            conflict_theirs_background: Color::Indexed(225),
+
+            syntax_keyword: Color::Indexed(90),
+            syntax_string: Color::Indexed(94),
+            syntax_number: Color::Indexed(25),
+            syntax_comment: Color::Gray,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+/// Diff colours that replace the theme's, each only when set. They come from git's
+/// `color.diff.*` settings and from the `[colors]` section of `config.toml`, which wins.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffColors {
+    pub addition: Option<Color>,
+    pub addition_background: Option<Color>,
+    pub deletion: Option<Color>,
+    pub deletion_background: Option<Color>,
+    pub hunk_header: Option<Color>,
+    pub commit_id: Option<Color>,
+}
+
+impl DiffColors {
+    /// Reads `color.diff.new`, `old`, `frag` and `commit`, unless `color.diff` (or else
+    /// `color.ui`) turns git's diff colours off. Values git wouldn't understand are skipped.
+    pub fn from_git(repo: &git2::Repository) -> Self {
+        let Ok(config) = repo.config().and_then(|mut config| config.snapshot()) else {
+            return Self::default();
+        };
+        let enabled = config
+            .get_string("color.diff")
+            .or_else(|_| config.get_string("color.ui"))
+            .map_or(true, |value| {
+                !matches!(
+                    value.to_lowercase().as_str(),
+                    "false" | "never" | "no" | "off" | "0"
+                )
+            });
+        if !enabled {
+            return Self::default();
+        }
+        let slot = |name: &str| {
+            config
+                .get_string(&format!("color.diff.{}", name))
+                .ok()
+                .and_then(|value| parse_git_color(&value))
+                .unwrap_or_default()
+        };
+        let (addition, addition_background) = slot("new");
+        let (deletion, deletion_background) = slot("old");
+        Self {
+            addition,
+            addition_background,
+            deletion,
+            deletion_background,
+            hunk_header: slot("frag").0,
+            commit_id: slot("commit").0,
+        }
+    }
+}
+
+impl Theme {
+    pub fn with_diff_colors(self, colors: &DiffColors) -> Self {
+        Self {
+            addition: colors.addition.unwrap_or(self.addition),
+            addition_background: colors
+                .addition_background
+                .unwrap_or(self.addition_background),
+            deletion: colors.deletion.unwrap_or(self.deletion),
+            deletion_background: colors
+                .deletion_background
+                .unwrap_or(self.deletion_background),
+            hunk_header: colors.hunk_header.unwrap_or(self.hunk_header),
+            commit_id: colors.commit_id.unwrap_or(self.commit_id),
+            ..self
+        }
+    }
+}
+
+/// Parses a git colour setting like `red bold` or `#ffaa00 236`: the first colour is the
+/// foreground and the second the background, while attributes such as `bold` are skipped.
+/// `normal` leaves a colour as it is. `None` when a word is neither a colour nor attribute.
+pub fn parse_git_color(value: &str) -> Option<(Option<Color>, Option<Color>)> {
+    const ATTRIBUTES: [&str; 8] = [
+        "bold", "dim", "ul", "blink", "reverse", "italic", "strike", "reset",
+    ];
+    let mut colors = Vec::new();
+    for word in value.split_whitespace() {
+        let word = word.to_lowercase();
+        let attribute = word
+            .strip_prefix("no-")
+            .or_else(|| word.strip_prefix("no"))
+            .unwrap_or(&word);
+        if ATTRIBUTES.contains(&attribute) {
+            continue;
+        }
+        colors.push(parse_color_word(&word)?);
+    }
+    match colors[..] {
+        [] => Some((None, None)),
+        [fg] => Some((fg, None)),
+        [fg, bg] => Some((fg, bg)),
+        _ => None,
+    }
+}
+
+/// One colour word of a git colour setting, `Some(None)` being `normal`.
+fn parse_color_word(word: &str) -> Option<Option<Color>> {
+    let color = match word {
+        "normal" => return Some(None),
+        "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::Gray,
+        "brightblack" => Color::DarkGray,
+        "brightred" => Color::LightRed,
+        "brightgreen" => Color::LightGreen,
+        "brightyellow" => Color::LightYellow,
+        "brightblue" => Color::LightBlue,
+        "brightmagenta" => Color::LightMagenta,
+        "brightcyan" => Color::LightCyan,
+        "brightwhite" => Color::White,
+        _ => match word.strip_prefix('#') {
+            // Checking the digits first keeps the slicing below on character boundaries.
+            Some(hex) if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+                Color::Rgb(channel(0)?, channel(2)?, channel(4)?)
+            }
+            Some(_) => return None,
+            None => Color::Indexed(word.parse().ok()?),
+        },
+    };
+    Some(Some(color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(
+            parse_color_word("#ff8000"),
+            Some(Some(Color::Rgb(255, 128, 0)))
+        );
+    }
+
+    #[test]
+    fn rejects_hex_colors_that_are_not_six_hex_digits() {
+        assert_eq!(parse_color_word("#ff80"), None);
+        assert_eq!(parse_color_word("#+f+f+f"), None);
+        // Six bytes, but not six characters.
+        assert_eq!(parse_color_word("#ééé"), None);
+    }
+}
@@ -0,0 +1,62 @@
+use git2::Time;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Formats a commit time as `YYYY-MM-DD HH:MM:SS +HHMM` in the commit's own timezone.
+pub fn format_absolute(time: Time) -> String {
+    let offset = time.offset_minutes();
+    let local = time.seconds() + i64::from(offset) * 60;
+    let days = local.div_euclid(86_400);
+    let secs_of_day = local.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    let sign = if offset < 0 { '-' } else { '+' };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} {}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        sign,
+        offset.abs() / 60,
+        offset.abs() % 60
+    )
+}
+
+/// Formats a commit time relative to now, e.g. "3 hours ago".
+pub fn format_relative(time: Time) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let elapsed = now - time.seconds();
+    if elapsed < 0 {
+        return "in the future".to_string();
+    }
+
+    let (value, unit) = match elapsed {
+        0..=59 => return "just now".to_string(),
+        60..=3_599 => (elapsed / 60, "minute"),
+        3_600..=86_399 => (elapsed / 3_600, "hour"),
+        86_400..=2_591_999 => (elapsed / 86_400, "day"),
+        2_592_000..=31_535_999 => (elapsed / 2_592_000, "month"),
+        _ => (elapsed / 31_536_000, "year"),
+    };
+    let plural = if value == 1 { "" } else { "s" };
+    format!("{} {}{} ago", value, unit, plural)
+}
+
+/// Converts days since the Unix epoch to a proleptic Gregorian (year, month, day).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
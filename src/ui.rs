@@ -1,8 +1,9 @@
+use git2::Status;
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
-    text::{Span, Spans},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
     widgets::{
         Block, Borders, Clear, List, ListItem, ListState, Paragraph, Scrollbar,
         ScrollbarOrientation, ScrollbarState,
@@ -10,74 +11,221 @@ use ratatui::{
     Frame,
 };
 use std::io::Stdout;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::app::{App, FocusedPane};
+use crate::app::{App, FocusedPane, Modal, PaneAreas};
+use crate::bisect::BisectProgress;
+use crate::file_system::is_expanded;
+use crate::git_ops::{
+    split_rows, BranchUpstream, DiffLine, Hunk, LineKind, SplitRow, SubmoduleState,
+};
+use crate::highlight::{highlight_line, language_for_path};
+use crate::keymap::Action;
+use crate::rebase::TodoAction;
+use crate::theme::Theme;
+use crate::time_format::{format_absolute, format_relative};
 
 pub fn draw(f: &mut Frame<CrosstermBackend<Stdout>>, app: &mut App) {
+    let theme = app.config.theme;
+    let root_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(f.size());
+    let file_list_width = app.file_list_width;
     let main_chunks = if app.debug_mode {
+        let debug_pane_width = app.debug_pane_width;
         Layout::default()
             .direction(Direction::Horizontal)
             .constraints([
-                Constraint::Percentage(30),
-                Constraint::Percentage(35),
-                Constraint::Percentage(35),
+                Constraint::Percentage(file_list_width),
+                Constraint::Percentage(100 - file_list_width - debug_pane_width),
+                Constraint::Percentage(debug_pane_width),
             ])
-            .split(f.size())
+            .split(root_chunks[0])
     } else {
         Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
-            .split(f.size())
+            .constraints([
+                Constraint::Percentage(file_list_width),
+                Constraint::Percentage(100 - file_list_width),
+            ])
+            .split(root_chunks[0])
     };
 
-    draw_file_list(f, app, main_chunks[0]);
-    draw_right_pane(f, app, main_chunks[1]);
+    app.pane_areas = PaneAreas {
+        file_list: main_chunks[0],
+        details: main_chunks[1],
+        debug: main_chunks.get(2).copied(),
+    };
+
+    if app.history.is_visible {
+        draw_history(f, app, main_chunks[0], &theme);
+    } else {
+        draw_file_list(f, app, main_chunks[0], &theme);
+    }
+    draw_right_pane(f, app, main_chunks[1], &theme);
 
     if app.debug_mode {
-        draw_debug_pane(f, app, main_chunks[2]);
+        draw_debug_pane(f, app, main_chunks[2], &theme);
     }
+    draw_status_bar(f, app, root_chunks[1], &theme);
 
-    if app.commit_modal.is_visible {
-        draw_modal(f, "Commit Message", &app.commit_modal.content, 60, 20);
+    if let Some(confirmation) = &app.confirmation {
+        let modal = Modal {
+            content: format!("{}\n\ny: yes   n/Esc: no", confirmation.prompt),
+            ..Modal::default()
+        };
+        draw_modal(f, "Confirm", &modal, 50, 20, &theme);
+    } else if let Some(menu) = &app.reset_menu {
+        let modal = Modal {
+            content: format!(
+                "Reset the current branch to {}\n\n\
+                 s: soft, keeping the index and working tree\n\
+                 m/Enter: mixed, keeping the working tree\n\
+                 h: hard, discarding all changes\n\
+                 Esc: cancel",
+                menu.label
+            ),
+            ..Modal::default()
+        };
+        draw_modal(f, "Reset", &modal, 50, 30, &theme);
+    } else if app.commit_modal.is_visible {
+        let title = match (app.amend_mode, app.head_status.detached) {
+            (true, _) => "Amend Commit",
+            (false, true) => "Commit Message (detached HEAD, not on any branch)",
+            (false, false) => "Commit Message",
+        };
+        draw_commit_modal(f, app, title, &theme);
     } else if app.help_modal.is_visible {
-        draw_modal(f, "Help", &app.help_modal.content, 60, 80);
+        draw_help(f, app);
+    } else if app.branch_list.is_visible {
+        draw_branch_list(f, app, &theme);
+    } else if app.stash_list.is_visible {
+        draw_stash_list(f, app, &theme);
+    } else if app.reflog.is_visible {
+        draw_reflog(f, app, &theme);
+    } else if app.submodule_list.is_visible {
+        draw_submodule_list(f, app, &theme);
+    } else if app.reword_modal.is_visible {
+        draw_input_modal(
+            f,
+            "Reword (Ctrl+S: save)",
+            &app.reword_modal,
+            60,
+            20,
+            &theme,
+        );
+    } else if app.rebase_todo.is_visible {
+        draw_rebase_todo(f, app, &theme);
+    } else if let Some(input) = &app.input {
+        draw_input_modal(f, &input.title, &input.field, 50, 20, &theme);
     }
 }
 
-fn draw_file_list(f: &mut Frame<CrosstermBackend<Stdout>>, app: &App, area: Rect) {
+fn draw_file_list(
+    f: &mut Frame<CrosstermBackend<Stdout>>,
+    app: &mut App,
+    area: Rect,
+    theme: &Theme,
+) {
+    let filter = app.list_options.status_filter;
     let items: Vec<ListItem> = if app.files.is_empty() {
-        vec![ListItem::new("(no changes)")]
+        vec![ListItem::new(if filter.is_some() {
+            "(no matching files)"
+        } else {
+            "(no changes)"
+        })]
     } else {
         app.files
             .iter()
             .enumerate()
             .map(|(index, file)| {
-                let color = match file.status {
-                    git2::Status::WT_NEW => Color::Green,
-                    git2::Status::WT_MODIFIED => Color::Yellow,
-                    git2::Status::WT_DELETED => Color::Red,
-                    _ => Color::White,
+                let color = if file.status.contains(Status::CONFLICTED) {
+                    theme.conflict
+                } else if file.status.intersects(Status::WT_NEW | Status::INDEX_NEW) {
+                    theme.file_new
+                } else if file
+                    .status
+                    .intersects(Status::WT_DELETED | Status::INDEX_DELETED)
+                {
+                    theme.file_deleted
+                } else if file.status == Status::IGNORED {
+                    theme.muted
+                } else if !file.status.is_empty() {
+                    theme.file_modified
+                } else {
+                    theme.text
                 };
-                let prefix = if file.is_dir { "📁 " } else { "📄 " };
-                let content = format!("{}{}", prefix, file.name);
-                let style = if index == app.selected_index {
-                    Style::default().fg(color).add_modifier(Modifier::REVERSED)
+                let prefix = if file.status.contains(Status::CONFLICTED) && !file.is_dir {
+                    "  ❗ "
+                } else if !file.is_dir {
+                    "  📄 "
+                } else if is_expanded(&app.expanded_dirs, &file.name) {
+                    "▾ 📁 "
                 } else {
-                    Style::default().fg(color)
+                    "▸ 📁 "
                 };
-                ListItem::new(Spans::from(vec![Span::styled(content, style)]))
+                let indent = "  ".repeat(file.depth);
+                let content = format!("{}{}{}", indent, prefix, file.file_name());
+                let mut style = Style::default().fg(color);
+                let mut code_style = Style::default();
+                if app.search.matches(&file.name) {
+                    style = style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+                }
+                if index == app.selected_index {
+                    style = style.add_modifier(Modifier::REVERSED);
+                    code_style = code_style.add_modifier(Modifier::REVERSED);
+                }
+                let [staged, unstaged] = file.status_code;
+                // Untracked files are all unstaged, as their "??" says.
+                let (staged_color, unstaged_color) = match staged {
+                    '?' => (theme.status_unstaged, theme.status_unstaged),
+                    '!' => (theme.muted, theme.muted),
+                    _ => (theme.status_staged, theme.status_unstaged),
+                };
+                let mut spans = vec![
+                    Span::styled(staged.to_string(), code_style.fg(staged_color)),
+                    Span::styled(format!("{} ", unstaged), code_style.fg(unstaged_color)),
+                    Span::styled(content, style),
+                ];
+                if app.list_options.show_file_info && !file.is_dir {
+                    let info = match file.metadata {
+                        Some((size, modified)) => {
+                            format!("  {}, {}", format_size(size), format_mtime(modified))
+                        }
+                        None if file
+                            .status
+                            .intersects(Status::WT_DELETED | Status::INDEX_DELETED) =>
+                        {
+                            "  deleted".to_string()
+                        }
+                        None => String::new(),
+                    };
+                    spans.push(Span::styled(info, Style::default().fg(theme.muted)));
+                }
+                ListItem::new(Line::from(spans))
             })
             .collect()
     };
 
+    let mut title = format!("Files (by {})", app.list_options.sort.name());
+    if let Some(filter) = filter {
+        title.push_str(&format!(" [{}]", filter.name()));
+    }
+    if app.list_options.show_ignored {
+        title.push_str(" [+ignored]");
+    }
+    if !app.search.query().is_empty() {
+        title.push_str(&format!(" (/{})", app.search.query()));
+    }
     let block = Block::default()
-        .title("Files")
+        .title(title)
         .borders(Borders::ALL)
         .border_style(
             Style::default().fg(if matches!(app.focused_pane, FocusedPane::FileList) {
-                Color::Cyan
+                theme.focused_border
             } else {
-                Color::White
+                theme.border
             }),
         );
 
@@ -85,36 +233,216 @@ fn draw_file_list(f: &mut Frame<CrosstermBackend<Stdout>>, app: &App, area: Rect
         .block(block)
         .highlight_style(Style::default().add_modifier(Modifier::BOLD));
 
-    f.render_stateful_widget(
-        file_list,
-        area,
-        &mut ListState::default().with_selected(Some(app.selected_index)),
+    // The state lives on `App` so that ratatui keeps the scroll offset between frames.
+    app.file_list_state.select(Some(app.selected_index));
+    f.render_stateful_widget(file_list, area, &mut app.file_list_state);
+
+    if app.search.prompt.is_visible {
+        draw_search_prompt(f, app, area, theme);
+    }
+}
+
+/// One-line prompt over the bottom of the file list, leaving the matches above it visible.
+fn draw_search_prompt(
+    f: &mut Frame<CrosstermBackend<Stdout>>,
+    app: &App,
+    area: Rect,
+    theme: &Theme,
+) {
+    let height = 3.min(area.height);
+    let prompt_area = Rect::new(area.x, area.y + area.height - height, area.width, height);
+    let no_match = !app.search.query().is_empty()
+        && !app.files.iter().any(|file| app.search.matches(&file.name));
+    let style = if no_match {
+        Style::default().fg(theme.error)
+    } else {
+        Style::default().fg(theme.text)
+    };
+    let prompt = Paragraph::new(Line::styled(format!("/{}", app.search.query()), style)).block(
+        Block::default()
+            .title("Search")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.focused_border)),
+    );
+    f.render_widget(Clear, prompt_area);
+    f.render_widget(prompt, prompt_area);
+    let (_, column) = app.search.prompt.cursor_position();
+    f.set_cursor(
+        (prompt_area.x + 2 + column as u16).min(prompt_area.right().saturating_sub(2)),
+        prompt_area.y + 1,
     );
 }
 
-fn draw_right_pane(f: &mut Frame<CrosstermBackend<Stdout>>, app: &App, area: Rect) {
+fn draw_history(f: &mut Frame<CrosstermBackend<Stdout>>, app: &mut App, area: Rect, theme: &Theme) {
+    let items: Vec<ListItem> = if app.history.commits.is_empty() {
+        vec![ListItem::new("(no commits yet)")]
+    } else {
+        app.history
+            .commits
+            .iter()
+            .map(|commit| {
+                let mut spans = vec![Span::styled(
+                    format!("{} ", commit.short_id),
+                    Style::default().fg(theme.commit_id),
+                )];
+                if let Some(tags) = app.history.tags.get(&commit.id) {
+                    spans.push(Span::styled(
+                        format!("[{}] ", tags.join(", ")),
+                        Style::default()
+                            .fg(theme.current_branch)
+                            .add_modifier(Modifier::BOLD),
+                    ));
+                }
+                spans.push(Span::raw(format!("{} ", commit.summary)));
+                let date = if app.history.absolute_dates {
+                    format_absolute(commit.time)
+                } else {
+                    format_relative(commit.time)
+                };
+                spans.push(Span::styled(
+                    format!("({}, {})", commit.author, date),
+                    Style::default().fg(theme.muted),
+                ));
+                ListItem::new(Line::from(spans))
+            })
+            .collect()
+    };
+
     let block = Block::default()
-        .title("Details")
+        .title("History")
         .borders(Borders::ALL)
         .border_style(
-            Style::default().fg(if matches!(app.focused_pane, FocusedPane::Details) {
-                Color::Cyan
+            Style::default().fg(if matches!(app.focused_pane, FocusedPane::FileList) {
+                theme.focused_border
             } else {
-                Color::White
+                theme.border
             }),
         );
 
-    let content = app.right_pane_content.as_str();
-    let paragraph = Paragraph::new(content)
+    let history = List::new(items)
         .block(block)
-        .wrap(ratatui::widgets::Wrap { trim: true })
-        .scroll((app.details_scroll as u16, 0));
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
 
-    let mut scrollbar_state = ScrollbarState::default()
-        .content_length(content.lines().count() as u16)
-        .position(app.details_scroll as u16);
+    app.history.list_state.select(Some(app.history.selected));
+    f.render_stateful_widget(history, area, &mut app.history.list_state);
+}
+
+/// A file size like `512 B`, `4.2 KB` or `1.0 MB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// A file's modification time relative to now, as for commits.
+fn format_mtime(modified: SystemTime) -> String {
+    let seconds = modified
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as i64);
+    format_relative(git2::Time::new(seconds, 0))
+}
+
+fn draw_right_pane(
+    f: &mut Frame<CrosstermBackend<Stdout>>,
+    app: &mut App,
+    area: Rect,
+    theme: &Theme,
+) {
+    // Commits in the history are diffed with git's defaults.
+    let title = match app.diff_settings.whitespace.label() {
+        Some(label) if !app.history.is_visible => format!("Details ({})", label),
+        _ => "Details".to_string(),
+    };
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(
+            Style::default().fg(if matches!(app.focused_pane, FocusedPane::Details) {
+                theme.focused_border
+            } else {
+                theme.border
+            }),
+        );
 
-    f.render_widget(paragraph, area);
+    if app.files.is_empty() && app.right_pane_lines.is_empty() && !app.history.is_visible {
+        let inner = block.inner(area);
+        let paragraph = Paragraph::new(empty_state_lines(app, inner.height, theme))
+            .alignment(Alignment::Center)
+            .wrap(ratatui::widgets::Wrap { trim: true })
+            .block(block);
+        f.render_widget(paragraph, area);
+        app.details_max_scroll = 0;
+        app.details_scroll = 0;
+        app.details_max_hscroll = 0;
+        app.details_hscroll = 0;
+        app.details_split_shown = false;
+        return;
+    }
+
+    let selected_hunk = app
+        .diff_hunks
+        .get(app.selected_hunk)
+        .filter(|_| matches!(app.focused_pane, FocusedPane::Details));
+    let mut lines = styled_diff_lines(&app.right_pane_lines, selected_hunk, theme);
+    if let Some(selected_lines) = app.selected_lines() {
+        for line in &mut lines[selected_lines] {
+            line.patch_style(Style::default().add_modifier(Modifier::REVERSED));
+        }
+    }
+    let inner = block.inner(area);
+    let split = app.side_by_side && inner.width >= MIN_SPLIT_WIDTH;
+    let mut max_hscroll = 0;
+    let (paragraph, content_height) = if split {
+        let lines = side_by_side_lines(&app.right_pane_lines, lines, inner.width, theme);
+        let content_height = lines.len();
+        (Paragraph::new(lines), content_height)
+    } else if !app.wrap_lines {
+        let content_height = lines.len();
+        let longest = lines.iter().map(Line::width).max().unwrap_or(0);
+        max_hscroll = longest.saturating_sub(inner.width as usize);
+        (Paragraph::new(lines), content_height)
+    } else {
+        let content_height = wrapped_height(&lines, inner.width);
+        // Trimming would eat the line-number gutter's padding and the code's indentation.
+        (
+            Paragraph::new(lines).wrap(ratatui::widgets::Wrap { trim: false }),
+            content_height,
+        )
+    };
+    let (scroll, max_scroll) =
+        clamp_scroll(app.details_scroll, content_height, inner.height as usize);
+    let hscroll = app.details_hscroll.min(max_hscroll);
+    f.render_widget(
+        paragraph
+            .block(block)
+            .scroll((scroll as u16, hscroll as u16)),
+        area,
+    );
+    // Remember the limit so that scrolling stops at the last screenful, and pull the scroll
+    // back if the content got shorter since the last frame.
+    app.details_max_scroll = max_scroll;
+    app.details_scroll = scroll;
+    app.details_max_hscroll = max_hscroll;
+    app.details_hscroll = hscroll;
+    app.details_split_shown = split;
+
+    // The scrollbar places its thumb at position / content_length, so scale the position
+    // to put the thumb at the bottom once the last line is in view.
+    let position = (scroll * content_height)
+        .checked_div(max_scroll)
+        .unwrap_or(0);
+    let mut scrollbar_state = ScrollbarState::default()
+        .content_length(content_height as u16)
+        .viewport_content_length(inner.height)
+        .position(position as u16);
     f.render_stateful_widget(
         Scrollbar::default()
             .orientation(ScrollbarOrientation::VerticalRight)
@@ -125,21 +453,808 @@ fn draw_right_pane(f: &mut Frame<CrosstermBackend<Stdout>>, app: &App, area: Rec
     );
 }
 
+/// What the Details pane says when there are no files to show, a third of the way down.
+fn empty_state_lines(app: &App, height: u16, theme: &Theme) -> Vec<Line<'static>> {
+    let mut lines = vec![Line::from(""); usize::from(height / 3)];
+    if app.list_options.status_filter.is_some() {
+        lines.push(Line::from("No files match the filter"));
+        let keys = app.keymap.labels_for(Action::CycleFilter);
+        if !keys.is_empty() {
+            lines.push(Line::styled(
+                format!("Press {} to change it", keys.join("/")),
+                Style::default().fg(theme.muted),
+            ));
+        }
+        return lines;
+    }
+    lines.push(Line::styled(
+        "Working tree clean — nothing to commit",
+        Style::default().add_modifier(Modifier::BOLD),
+    ));
+    lines.push(Line::from(""));
+    lines.push(Line::styled(
+        match &app.head_status.last_commit {
+            Some(commit) => format!("Last commit: {}", commit),
+            None => "No commits yet".to_string(),
+        },
+        Style::default().fg(theme.muted),
+    ));
+    lines
+}
+
+/// Narrowest Details pane, inside its borders, that the side-by-side view is drawn in.
+const MIN_SPLIT_WIDTH: u16 = 80;
+
+/// Rearranges the unified `styled` lines into two columns, old on the left and new on the
+/// right. Each diff line keeps its own line number only, and content that doesn't fit its
+/// column is cut off rather than wrapped so that the columns stay aligned.
+fn side_by_side_lines<'a>(
+    lines: &[DiffLine],
+    styled: Vec<Line<'a>>,
+    width: u16,
+    theme: &Theme,
+) -> Vec<Line<'a>> {
+    let column_width = usize::from(width.saturating_sub(1) / 2);
+    let gutter_width = gutter_width(lines);
+    let column = |index: Option<usize>, lineno: fn(&DiffLine) -> Option<u32>| -> Vec<Span<'a>> {
+        let mut spans = Vec::new();
+        if let Some(index) = index {
+            let gutter = match lineno(&lines[index]) {
+                Some(lineno) => format!("{:>width$} ", lineno, width = gutter_width),
+                None => " ".repeat(gutter_width + 1),
+            };
+            spans.push(Span::styled(gutter, Style::default().fg(theme.muted)));
+            // Everything after the unified line's two-number gutter.
+            spans.extend(styled[index].spans.iter().skip(1).cloned());
+        }
+        fit_spans(spans, column_width)
+    };
+
+    split_rows(lines)
+        .into_iter()
+        .map(|row| match row {
+            SplitRow::Full(index) => styled[index].clone(),
+            SplitRow::Pair(old, new) => {
+                let mut spans = column(old, |line| line.old_lineno);
+                spans.push(Span::styled("│", Style::default().fg(theme.border)));
+                spans.extend(column(new, |line| line.new_lineno));
+                Line::from(spans)
+            }
+        })
+        .collect()
+}
+
+/// Cuts `spans` down to `width` characters, or pads them with spaces up to it.
+fn fit_spans(spans: Vec<Span>, width: usize) -> Vec<Span> {
+    let mut remaining = width;
+    let mut fitted = Vec::new();
+    for span in spans {
+        let length = span.content.chars().count();
+        if length <= remaining {
+            remaining -= length;
+            fitted.push(span);
+        } else {
+            let cut: String = span.content.chars().take(remaining).collect();
+            fitted.push(Span::styled(cut, span.style));
+            remaining = 0;
+            break;
+        }
+    }
+    fitted.push(Span::raw(" ".repeat(remaining)));
+    fitted
+}
+
+/// Width of the widest line number in `lines`, so that numbers line up in the gutter.
+fn gutter_width(lines: &[DiffLine]) -> usize {
+    lines
+        .iter()
+        .filter_map(|line| line.old_lineno.max(line.new_lineno))
+        .max()
+        .map_or(1, |max| max.to_string().len())
+}
+
+/// `scroll` pulled back to the last screenful of the content, and that furthest useful
+/// scroll, which is zero when it all fits.
+fn clamp_scroll(scroll: usize, content_height: usize, viewport_height: usize) -> (usize, usize) {
+    let max_scroll = content_height.saturating_sub(viewport_height);
+    (scroll.min(max_scroll), max_scroll)
+}
+
+/// Number of rows `lines` take up when wrapped to `width`. This counts characters rather
+/// than replaying ratatui's word wrapping, so a line that breaks early at a word boundary can
+/// take one row more than estimated.
+fn wrapped_height(lines: &[Line], width: u16) -> usize {
+    let width = usize::from(width.max(1));
+    lines
+        .iter()
+        .map(|line| line.width().div_ceil(width).max(1))
+        .sum()
+}
+
+/// Styles the lines shown in the Details pane. Added and removed lines get green and red
+/// backgrounds with their code syntax-highlighted by file extension.
+fn styled_diff_lines<'a>(
+    lines: &'a [DiffLine],
+    selected_hunk: Option<&Hunk>,
+    theme: &Theme,
+) -> Vec<Line<'a>> {
+    let mut language = None;
+    let mut conflict_region = ConflictRegion::None;
+    let gutter_width = gutter_width(lines);
+    let format_lineno = |lineno: Option<u32>| match lineno {
+        Some(lineno) => format!("{:>width$}", lineno, width = gutter_width),
+        None => " ".repeat(gutter_width),
+    };
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| {
+            let in_selected_hunk = selected_hunk.is_some_and(|hunk| {
+                (hunk.start_line..hunk.start_line + hunk.line_count).contains(&index)
+            });
+            let content = line.content.as_str();
+
+            let (prefix, prefix_style, base) = match line.kind {
+                LineKind::Header => {
+                    return Line::styled(content, Style::default().add_modifier(Modifier::BOLD))
+                }
+                LineKind::FileHeader => {
+                    if let Some(paths) = content.strip_prefix("diff --git ") {
+                        language = paths.rsplit(" b/").next().and_then(language_for_path);
+                    } else if let Some(path) = content
+                        .strip_prefix("Blame of ")
+                        .or_else(|| content.strip_prefix("Conflicts in "))
+                    {
+                        language = language_for_path(path);
+                    }
+                    return Line::styled(content, Style::default().add_modifier(Modifier::BOLD));
+                }
+                LineKind::HunkHeader => {
+                    let style = Style::default().fg(theme.hunk_header);
+                    return Line::styled(
+                        content,
+                        if in_selected_hunk {
+                            style.add_modifier(Modifier::REVERSED)
+                        } else {
+                            style
+                        },
+                    );
+                }
+                LineKind::Text => return Line::from(content),
+                LineKind::Blame => {
+                    let mut spans = vec![
+                        Span::styled(
+                            line.annotation.clone().unwrap_or_default(),
+                            Style::default().fg(theme.commit_id),
+                        ),
+                        Span::styled(
+                            format!(" {} ", format_lineno(line.new_lineno)),
+                            Style::default().fg(theme.muted),
+                        ),
+                    ];
+                    spans.extend(highlight_line(content, language, Style::default(), theme));
+                    return Line::from(spans);
+                }
+                LineKind::Conflict => {
+                    let lineno = Span::styled(
+                        format!("{} ", format_lineno(line.new_lineno)),
+                        Style::default().fg(theme.muted),
+                    );
+                    let (next_region, is_marker) = ConflictRegion::after(conflict_region, content);
+                    conflict_region = next_region;
+                    if is_marker {
+                        let style = Style::default()
+                            .fg(theme.conflict)
+                            .add_modifier(Modifier::BOLD);
+                        return Line::from(vec![lineno, Span::styled(content, style)]);
+                    }
+                    let base = match conflict_region {
+                        ConflictRegion::Ours => Style::default().bg(theme.conflict_ours_background),
+                        ConflictRegion::Base => Style::default().fg(theme.muted),
+                        ConflictRegion::Theirs => {
+                            Style::default().bg(theme.conflict_theirs_background)
+                        }
+                        ConflictRegion::None => Style::default(),
+                    };
+                    let mut spans = vec![lineno];
+                    spans.extend(highlight_line(content, language, base, theme));
+                    return Line::from(spans);
+                }
+                LineKind::Addition => (
+                    "+",
+                    Style::default().fg(theme.addition),
+                    Style::default().bg(theme.addition_background),
+                ),
+                LineKind::Deletion => (
+                    "-",
+                    Style::default().fg(theme.deletion),
+                    Style::default().bg(theme.deletion_background),
+                ),
+                LineKind::Context => {
+                    let base = if in_selected_hunk {
+                        Style::default().bg(theme.selected_hunk_background)
+                    } else {
+                        Style::default()
+                    };
+                    (" ", base, base)
+                }
+            };
+
+            let gutter = format!(
+                "{} {} ",
+                format_lineno(line.old_lineno),
+                format_lineno(line.new_lineno)
+            );
+            let mut spans = vec![
+                Span::styled(gutter, Style::default().fg(theme.muted)),
+                Span::styled(prefix, prefix_style.patch(base)),
+            ];
+            spans.extend(highlight_line(content, language, base, theme));
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// Where a line of a conflicted file is relative to git's conflict markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConflictRegion {
+    None,
+    /// Between `<<<<<<<` and `|||||||` or `=======`.
+    Ours,
+    /// The common ancestor, between `|||||||` and `=======` in the diff3 style.
+    Base,
+    /// Between `=======` and `>>>>>>>`.
+    Theirs,
+}
+
+impl ConflictRegion {
+    /// The region the lines after `line` are in, and whether `line` is a marker.
+    fn after(region: Self, line: &str) -> (Self, bool) {
+        let marker = |prefix: &str| line.starts_with(prefix);
+        match region {
+            _ if marker("<<<<<<<") => (ConflictRegion::Ours, true),
+            ConflictRegion::Ours if marker("|||||||") => (ConflictRegion::Base, true),
+            ConflictRegion::Ours | ConflictRegion::Base if marker("=======") => {
+                (ConflictRegion::Theirs, true)
+            }
+            ConflictRegion::Theirs if marker(">>>>>>>") => (ConflictRegion::None, true),
+            region => (region, false),
+        }
+    }
+}
+
+/// Frames of the status bar spinner shown while the worker is busy.
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_FRAME_MILLIS: u128 = 100;
+
+/// Branch, ahead/behind counts and a summary of the working tree, along the bottom.
+fn draw_status_bar(f: &mut Frame<CrosstermBackend<Stdout>>, app: &App, area: Rect, theme: &Theme) {
+    let branch_color = if app.head_status.detached {
+        theme.conflict
+    } else {
+        theme.current_branch
+    };
+    let mut spans = vec![Span::styled(
+        format!(" {}", app.head_status.branch),
+        Style::default()
+            .fg(branch_color)
+            .add_modifier(Modifier::BOLD),
+    )];
+    if !app.head_status.tags.is_empty() {
+        spans.push(Span::styled(
+            format!(" [{}]", app.head_status.tags.join(", ")),
+            Style::default().fg(theme.current_branch),
+        ));
+    }
+    if let Some((ahead, behind)) = app.head_status.ahead_behind {
+        spans.push(Span::styled(
+            format!(" ↑{} ↓{}", ahead, behind),
+            Style::default().fg(theme.text),
+        ));
+    }
+    if let Some(rebase) = &app.rebase {
+        let (step, total) = rebase.position();
+        spans.push(Span::styled(
+            format!(" REBASE {}/{}", step, total),
+            Style::default()
+                .fg(theme.conflict)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(bisect) = &app.bisect {
+        let state = match bisect.progress() {
+            BisectProgress::NeedsGood => "mark a good commit".to_string(),
+            BisectProgress::NeedsBad => "mark a bad commit".to_string(),
+            BisectProgress::Testing { steps: 1, .. } => "~1 step remaining".to_string(),
+            BisectProgress::Testing { steps, .. } => format!("~{} steps remaining", steps),
+            BisectProgress::Found(oid) => format!("first bad commit {:.7}", oid),
+        };
+        spans.push(Span::styled(
+            format!(" BISECT: {}", state),
+            Style::default()
+                .fg(theme.conflict)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(task) = app.worker.current() {
+        let frame = app.worker.busy_since().elapsed().as_millis() / SPINNER_FRAME_MILLIS;
+        spans.push(Span::styled(
+            format!(
+                " {} {}...",
+                SPINNER[frame as usize % SPINNER.len()],
+                task.description()
+            ),
+            Style::default().fg(theme.text),
+        ));
+    }
+    let summary = app.status_summary;
+    let counts = if !summary.is_dirty() {
+        "clean".to_string()
+    } else {
+        format!(
+            "{} modified, {} untracked, {} staged",
+            summary.modified, summary.untracked, summary.staged
+        )
+    };
+    spans.push(Span::styled(
+        format!(" | {}", counts),
+        Style::default().fg(theme.muted),
+    ));
+    f.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
 fn draw_modal(
     f: &mut Frame<CrosstermBackend<Stdout>>,
     title: &str,
-    content: &str,
+    modal: &Modal,
     percent_x: u16,
     percent_y: u16,
+    theme: &Theme,
 ) {
     let modal_area = centered_rect(percent_x, percent_y, f.size());
-    let modal = Paragraph::new(content)
+    let mut lines: Vec<Line> = modal.content.lines().map(Line::from).collect();
+    if let Some(error) = &modal.error {
+        lines.push(Line::from(""));
+        lines.push(Line::styled(
+            error.as_str(),
+            Style::default()
+                .fg(theme.error)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    let modal = Paragraph::new(lines)
         .block(Block::default().title(title).borders(Borders::ALL))
         .wrap(ratatui::widgets::Wrap { trim: true });
     f.render_widget(Clear, modal_area);
     f.render_widget(modal, modal_area);
 }
 
+/// Draws the help modal scrolled to `help_scroll`, which is clamped to the text's length.
+fn draw_help(f: &mut Frame<CrosstermBackend<Stdout>>, app: &mut App) {
+    let modal_area = centered_rect(60, 80, f.size());
+    let block = Block::default()
+        .title("Help (↑/↓ to scroll)")
+        .borders(Borders::ALL);
+    let inner = block.inner(modal_area);
+    let lines: Vec<Line> = app.help_modal.content.lines().map(Line::from).collect();
+    let max_scroll = wrapped_height(&lines, inner.width).saturating_sub(inner.height as usize);
+    app.help_max_scroll = max_scroll;
+    app.help_scroll = app.help_scroll.min(max_scroll);
+    let help = Paragraph::new(lines)
+        .block(block)
+        .wrap(ratatui::widgets::Wrap { trim: true })
+        .scroll((app.help_scroll as u16, 0));
+    f.render_widget(Clear, modal_area);
+    f.render_widget(help, modal_area);
+}
+
+/// Draws a text-entry modal with a visible cursor. Lines are broken at the modal's width by
+/// character rather than by word so that the cursor can be placed exactly, and the text
+/// scrolls to keep the cursor in view.
+fn draw_input_modal(
+    f: &mut Frame<CrosstermBackend<Stdout>>,
+    title: &str,
+    modal: &Modal,
+    percent_x: u16,
+    percent_y: u16,
+    theme: &Theme,
+) {
+    let modal_area = centered_rect(percent_x, percent_y, f.size());
+    draw_input_in(f, modal_area, title, modal, theme);
+}
+
+/// The commit message above the list of files it will be committed with, so that a commit
+/// taking more than meant can be backed out of with Esc and unstaged first.
+fn draw_commit_modal(
+    f: &mut Frame<CrosstermBackend<Stdout>>,
+    app: &App,
+    title: &str,
+    theme: &Theme,
+) {
+    let area = centered_rect(60, 40, f.size());
+    let files = &app.commit_files;
+    // At least a row for "nothing staged", and at most half of the modal.
+    let list_height = (files.len().max(1) as u16 + 2).min(area.height / 2);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(list_height)])
+        .split(area);
+
+    let items: Vec<ListItem> = if files.is_empty() {
+        vec![ListItem::new(Line::styled(
+            "Nothing is staged",
+            Style::default().fg(theme.error),
+        ))]
+    } else {
+        files
+            .iter()
+            .map(|file| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("{} ", file.code),
+                        Style::default().fg(theme.status_staged),
+                    ),
+                    Span::raw(file.path.as_str()),
+                ]))
+            })
+            .collect()
+    };
+    let list_title = match (app.amend_mode, files.len()) {
+        (true, count) => format!(
+            "Staged: {} (added to the last commit) - Ctrl+D: diff, Esc: back",
+            count
+        ),
+        (false, count) => format!("Staged: {} - Ctrl+D: diff, Esc: back to unstage", count),
+    };
+    f.render_widget(Clear, chunks[1]);
+    f.render_widget(
+        List::new(items).block(Block::default().title(list_title).borders(Borders::ALL)),
+        chunks[1],
+    );
+    draw_input_in(f, chunks[0], title, &app.commit_modal, theme);
+}
+
+fn draw_input_in(
+    f: &mut Frame<CrosstermBackend<Stdout>>,
+    modal_area: Rect,
+    title: &str,
+    modal: &Modal,
+    theme: &Theme,
+) {
+    let block = Block::default().title(title).borders(Borders::ALL);
+    let inner = block.inner(modal_area);
+    f.render_widget(Clear, modal_area);
+    f.render_widget(block, modal_area);
+
+    let error_height = if modal.error.is_some() { 2 } else { 0 };
+    let text_area = Rect {
+        height: inner.height.saturating_sub(error_height),
+        ..inner
+    };
+    if let Some(error) = &modal.error {
+        let error_area = Rect {
+            y: text_area.bottom(),
+            height: inner.height - text_area.height,
+            ..inner
+        };
+        let error = Paragraph::new(Line::styled(
+            error.as_str(),
+            Style::default()
+                .fg(theme.error)
+                .add_modifier(Modifier::BOLD),
+        ))
+        .wrap(ratatui::widgets::Wrap { trim: true });
+        f.render_widget(error, error_area);
+    }
+
+    let width = usize::from(text_area.width.max(1));
+    let (cursor_line, cursor_column) = modal.cursor_position();
+    let mut lines = Vec::new();
+    let mut cursor_row = 0;
+    for (index, line) in modal.content.split('\n').enumerate() {
+        let start = lines.len();
+        let chars: Vec<char> = line.chars().collect();
+        lines.extend(
+            chars
+                .chunks(width)
+                .map(|chunk| Line::from(chunk.iter().collect::<String>())),
+        );
+        if index == cursor_line {
+            cursor_row = start + cursor_column / width;
+        }
+        // An empty line, or a cursor just past a full row, still needs a row to sit on.
+        if lines.len() == start || (index == cursor_line && cursor_row == lines.len()) {
+            lines.push(Line::from(""));
+        }
+    }
+
+    let visible_rows = usize::from(text_area.height.max(1));
+    let scroll = (cursor_row + 1).saturating_sub(visible_rows);
+    f.render_widget(Paragraph::new(lines).scroll((scroll as u16, 0)), text_area);
+    f.set_cursor(
+        text_area.x + (cursor_column % width) as u16,
+        text_area.y + (cursor_row - scroll) as u16,
+    );
+}
+
+fn draw_branch_list(f: &mut Frame<CrosstermBackend<Stdout>>, app: &App, theme: &Theme) {
+    let area = centered_rect(50, 60, f.size());
+    let mut items: Vec<ListItem> = app
+        .branch_list
+        .branches
+        .iter()
+        .map(|branch| {
+            let marker = if branch.is_head { "* " } else { "  " };
+            let style = if branch.is_head {
+                Style::default().fg(theme.current_branch)
+            } else {
+                Style::default()
+            };
+            let muted = Style::default().fg(theme.muted);
+            let mut spans = vec![Span::styled(format!("{}{}", marker, branch.name), style)];
+            match &branch.upstream {
+                BranchUpstream::None => spans.push(Span::styled("  (no upstream)", muted)),
+                BranchUpstream::Gone(upstream) => {
+                    spans.push(Span::styled(format!("  → {} (gone)", upstream), muted))
+                }
+                BranchUpstream::Tracking(upstream) => {
+                    spans.push(Span::styled(format!("  → {}", upstream), muted));
+                    match branch.ahead_behind {
+                        Some((0, 0)) => spans.push(Span::styled(" (up to date)", muted)),
+                        Some((ahead, behind)) => {
+                            let mut counts = Vec::new();
+                            if ahead > 0 {
+                                counts.push(format!("↑{}", ahead));
+                            }
+                            if behind > 0 {
+                                counts.push(format!("↓{}", behind));
+                            }
+                            spans.push(Span::styled(
+                                format!(" {}", counts.join(" ")),
+                                Style::default().fg(theme.status_unstaged),
+                            ));
+                        }
+                        None => {}
+                    }
+                }
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    if let Some(error) = &app.branch_list.error {
+        items.push(ListItem::new(Line::styled(
+            error.as_str(),
+            Style::default()
+                .fg(theme.error)
+                .add_modifier(Modifier::BOLD),
+        )));
+    }
+
+    // No branch is marked as current then, so say where HEAD is instead.
+    let title = if app.head_status.detached {
+        format!("Branches — HEAD {}", app.head_status.branch)
+    } else {
+        "Branches".to_string()
+    };
+    let list = List::new(items)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(
+        list,
+        area,
+        &mut ListState::default().with_selected(Some(app.branch_list.selected)),
+    );
+}
+
+fn draw_rebase_todo(f: &mut Frame<CrosstermBackend<Stdout>>, app: &App, theme: &Theme) {
+    let area = centered_rect(60, 60, f.size());
+    let mut items: Vec<ListItem> = app
+        .rebase_todo
+        .items
+        .iter()
+        .map(|item| {
+            let action_style = match item.action {
+                TodoAction::Pick => Style::default(),
+                TodoAction::Reword | TodoAction::Squash => Style::default()
+                    .fg(theme.hunk_header)
+                    .add_modifier(Modifier::BOLD),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:<7}", item.action.name()), action_style),
+                Span::styled(
+                    format!("{} ", &item.id.to_string()[..7]),
+                    Style::default().fg(theme.commit_id),
+                ),
+                Span::raw(item.summary.as_str()),
+            ]))
+        })
+        .collect();
+    if let Some(error) = &app.rebase_todo.error {
+        items.push(ListItem::new(Line::styled(
+            error.as_str(),
+            Style::default()
+                .fg(theme.error)
+                .add_modifier(Modifier::BOLD),
+        )));
+    }
+
+    let title = match &app.rebase {
+        Some(rebase) => {
+            let (step, total) = rebase.position();
+            format!(
+                "Rebase stopped at {}/{} (Enter: continue, a: abort)",
+                step, total
+            )
+        }
+        None => {
+            "Rebase, oldest first (p/r/s: pick/reword/squash, J/K: move, Enter: start)".to_string()
+        }
+    };
+    let list = List::new(items)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(
+        list,
+        area,
+        &mut ListState::default().with_selected(Some(app.rebase_todo.selected)),
+    );
+}
+
+fn draw_stash_list(f: &mut Frame<CrosstermBackend<Stdout>>, app: &App, theme: &Theme) {
+    let area = centered_rect(60, 60, f.size());
+    let mut items: Vec<ListItem> = if app.stash_list.stashes.is_empty() {
+        vec![ListItem::new("(no stashes)")]
+    } else {
+        app.stash_list
+            .stashes
+            .iter()
+            .map(|stash| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("stash@{{{}}} ", stash.index),
+                        Style::default().fg(theme.commit_id),
+                    ),
+                    Span::raw(stash.message.as_str()),
+                ]))
+            })
+            .collect()
+    };
+    if let Some(error) = &app.stash_list.error {
+        items.push(ListItem::new(Line::styled(
+            error.as_str(),
+            Style::default()
+                .fg(theme.error)
+                .add_modifier(Modifier::BOLD),
+        )));
+    }
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Stashes (Enter: pop, a: apply, x: drop)")
+                .borders(Borders::ALL),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(
+        list,
+        area,
+        &mut ListState::default().with_selected(Some(app.stash_list.selected)),
+    );
+}
+
+fn draw_reflog(f: &mut Frame<CrosstermBackend<Stdout>>, app: &App, theme: &Theme) {
+    let area = centered_rect(70, 70, f.size());
+    let mut items: Vec<ListItem> = if app.reflog.entries.is_empty() {
+        vec![ListItem::new("(the reflog is empty)")]
+    } else {
+        app.reflog
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("HEAD@{{{}}} ", index),
+                        Style::default().fg(theme.muted),
+                    ),
+                    Span::styled(
+                        format!("{:.7} ", entry.id),
+                        Style::default().fg(theme.commit_id),
+                    ),
+                    Span::raw(entry.message.as_str()),
+                    Span::styled(
+                        format!("  {}", format_relative(entry.time)),
+                        Style::default().fg(theme.muted),
+                    ),
+                ]))
+            })
+            .collect()
+    };
+    if let Some(error) = &app.reflog.error {
+        items.push(ListItem::new(Line::styled(
+            error.as_str(),
+            Style::default()
+                .fg(theme.error)
+                .add_modifier(Modifier::BOLD),
+        )));
+    }
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Reflog (Enter: reset to the selected entry)")
+                .borders(Borders::ALL),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(
+        list,
+        area,
+        &mut ListState::default().with_selected(Some(app.reflog.selected)),
+    );
+}
+
+fn draw_submodule_list(f: &mut Frame<CrosstermBackend<Stdout>>, app: &App, theme: &Theme) {
+    let area = centered_rect(60, 60, f.size());
+    let mut items: Vec<ListItem> = if app.submodule_list.submodules.is_empty() {
+        vec![ListItem::new("(no submodules)")]
+    } else {
+        app.submodule_list
+            .submodules
+            .iter()
+            .map(|submodule| {
+                let (state, color) = match submodule.state {
+                    SubmoduleState::Uninitialized => ("not initialized".to_string(), theme.muted),
+                    SubmoduleState::OutOfDate => (
+                        match (submodule.checked_out, submodule.recorded) {
+                            (Some(checked_out), Some(recorded)) => format!(
+                                "out of date: {:.7} checked out, {:.7} recorded",
+                                checked_out, recorded
+                            ),
+                            _ => "out of date".to_string(),
+                        },
+                        theme.status_unstaged,
+                    ),
+                    SubmoduleState::Modified => {
+                        ("local changes".to_string(), theme.status_unstaged)
+                    }
+                    SubmoduleState::UpToDate => ("up to date".to_string(), theme.muted),
+                };
+                ListItem::new(Line::from(vec![
+                    Span::raw(submodule.path.as_str()),
+                    Span::styled(format!("  {}", state), Style::default().fg(color)),
+                ]))
+            })
+            .collect()
+    };
+    if let Some(error) = &app.submodule_list.error {
+        items.push(ListItem::new(Line::styled(
+            error.as_str(),
+            Style::default()
+                .fg(theme.error)
+                .add_modifier(Modifier::BOLD),
+        )));
+    }
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Submodules (Enter: initialize/update)")
+                .borders(Borders::ALL),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(
+        list,
+        area,
+        &mut ListState::default().with_selected(Some(app.submodule_list.selected)),
+    );
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -160,14 +1275,81 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         .split(popup_layout[1])[1]
 }
 
-fn draw_debug_pane(f: &mut Frame<CrosstermBackend<Stdout>>, app: &App, area: Rect) {
+/// Draws the debug log, at its end unless it has been scrolled up.
+fn draw_debug_pane(
+    f: &mut Frame<CrosstermBackend<Stdout>>,
+    app: &mut App,
+    area: Rect,
+    theme: &Theme,
+) {
+    // Says why new messages aren't showing up.
+    let title = if app.debug_scroll.is_some() {
+        "Debug (scrolled back)"
+    } else {
+        "Debug"
+    };
     let block = Block::default()
-        .title("Debug")
+        .title(title)
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::White));
+        .border_style(
+            Style::default().fg(if matches!(app.focused_pane, FocusedPane::Debug) {
+                theme.focused_border
+            } else {
+                theme.border
+            }),
+        );
 
-    let debug_pane = Paragraph::new(app.debug_content.as_str())
+    let inner = block.inner(area);
+    let mut lines = Vec::new();
+    if app.debug_trimmed > 0 {
+        lines.push(Line::styled(
+            format!("... ({} older lines trimmed)", app.debug_trimmed),
+            Style::default().fg(theme.muted),
+        ));
+    }
+    lines.extend(app.debug_lines.iter().map(|line| Line::from(line.as_str())));
+    app.debug_max_scroll =
+        wrapped_height(&lines, inner.width).saturating_sub(inner.height as usize);
+    let scroll = app.debug_scroll.map_or(app.debug_max_scroll, |scroll| {
+        scroll.min(app.debug_max_scroll)
+    });
+    let debug_pane = Paragraph::new(lines)
         .block(block)
-        .wrap(ratatui::widgets::Wrap { trim: true });
+        .wrap(ratatui::widgets::Wrap { trim: true })
+        .scroll((scroll as u16, 0));
     f.render_widget(debug_pane, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scroll_stops_at_the_last_screenful() {
+        assert_eq!(clamp_scroll(0, 100, 20), (0, 80));
+        assert_eq!(clamp_scroll(50, 100, 20), (50, 80));
+        assert_eq!(clamp_scroll(80, 100, 20), (80, 80));
+        assert_eq!(clamp_scroll(500, 100, 20), (80, 80));
+    }
+
+    #[test]
+    fn content_that_fits_does_not_scroll() {
+        assert_eq!(clamp_scroll(0, 5, 20), (0, 0));
+        assert_eq!(clamp_scroll(10, 5, 20), (0, 0));
+        assert_eq!(clamp_scroll(10, 20, 20), (0, 0));
+        assert_eq!(clamp_scroll(3, 0, 20), (0, 0));
+    }
+
+    #[test]
+    fn scroll_limit_counts_wrapped_rows() {
+        let lines = vec![
+            Line::from("x".repeat(25)),
+            Line::from(""),
+            Line::from("short"),
+        ];
+        // 3 rows for the long line at a width of 10, one each for the others.
+        let height = wrapped_height(&lines, 10);
+        assert_eq!(height, 5);
+        assert_eq!(clamp_scroll(9, height, 2), (3, 3));
+    }
+}
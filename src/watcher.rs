@@ -0,0 +1,195 @@
+use git2::Repository;
+use notify::event::{Event, EventKind};
+use notify::{RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the tree has to be quiet after a change before it is reported.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// The longest a change waits to be reported while others keep coming, as during a build
+/// writing somewhere that isn't ignored.
+const MAX_DELAY: Duration = Duration::from_secs(1);
+
+/// The files in the git directory whose changes show in the file list or the status bar:
+/// commits, checkouts, staging and fetches done outside gitui. Refs under `refs/` count too.
+const WATCHED_GIT_FILES: &[&str] = &["HEAD", "index", "packed-refs", "FETCH_HEAD"];
+
+/// Watches the working tree from a background thread and sends a message when it changed,
+/// so that the file list is only re-read when there is something new to show.
+///
+/// The operating system reports changes as they happen. Those to ignored files, and inside
+/// `.git` to anything but HEAD, the index and the refs, are left out. A burst of changes,
+/// like an editor writing a temporary file and renaming it, is reported once the tree has
+/// been quiet for a moment.
+pub fn watch(repo: &Repository) -> Receiver<()> {
+    let (sender, receiver) = channel();
+    let git_dir = repo.path().to_path_buf();
+    thread::spawn(move || {
+        // The thread has its own handle for checking ignore rules.
+        let Ok(repo) = Repository::open(&git_dir) else {
+            return;
+        };
+        let Some(workdir) = repo.workdir().map(Path::to_path_buf) else {
+            return;
+        };
+        // Events may name paths with symbolic links resolved, as on macOS, where the
+        // temporary directory is a link.
+        let workdir = workdir.canonicalize().unwrap_or(workdir);
+        let git_dir = git_dir.canonicalize().unwrap_or(git_dir);
+        let (event_sender, events) = channel();
+        let Ok(mut watcher) = notify::recommended_watcher(event_sender) else {
+            return;
+        };
+        if watcher.watch(&workdir, RecursiveMode::Recursive).is_err() {
+            return;
+        }
+        // The git directory of a linked worktree lives in the main repository.
+        if !git_dir.starts_with(&workdir) {
+            let _ = watcher.watch(&git_dir, RecursiveMode::Recursive);
+        }
+        let relevant = |event: notify::Result<Event>| {
+            event.is_ok_and(|event| is_relevant(&repo, &workdir, &git_dir, &event))
+        };
+        for event in &events {
+            if !relevant(event) {
+                continue;
+            }
+            let first = Instant::now();
+            let mut last = first;
+            loop {
+                let quiet_at = (last + DEBOUNCE).min(first + MAX_DELAY);
+                let Some(timeout) = quiet_at.checked_duration_since(Instant::now()) else {
+                    break;
+                };
+                match events.recv_timeout(timeout) {
+                    // Changes to ignored files don't hold the report back.
+                    Ok(event) => {
+                        if relevant(event) {
+                            last = Instant::now();
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            if sender.send(()).is_err() {
+                return;
+            }
+        }
+    });
+    receiver
+}
+
+fn is_relevant(repo: &Repository, workdir: &Path, git_dir: &Path, event: &Event) -> bool {
+    // Reads don't change anything; gitui reads the tree itself when refreshing.
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+    event.paths.iter().any(|path| {
+        if let Ok(relative) = path.strip_prefix(git_dir) {
+            return is_watched_git_file(relative);
+        }
+        let Ok(relative) = path.strip_prefix(workdir) else {
+            return false;
+        };
+        // The git directories of submodules, and of repositories nested without being
+        // submodules, change with every command run in them.
+        if relative
+            .components()
+            .any(|component| component.as_os_str() == ".git")
+        {
+            return false;
+        }
+        !repo.is_path_ignored(relative).unwrap_or(false)
+    })
+}
+
+/// Whether `relative`, a path in the git directory, is one of the files whose changes are
+/// reported. Lock files are left out, as git renames them into place once it is done.
+fn is_watched_git_file(relative: &Path) -> bool {
+    if relative
+        .extension()
+        .is_some_and(|extension| extension == "lock")
+    {
+        return false;
+    }
+    relative.starts_with("refs")
+        || WATCHED_GIT_FILES
+            .iter()
+            .any(|name| relative == Path::new(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, ModifyKind};
+
+    fn modified(path: &Path) -> Event {
+        Event::new(EventKind::Modify(ModifyKind::Any)).add_path(path.to_path_buf())
+    }
+
+    #[test]
+    fn only_head_the_index_and_refs_count_inside_git() {
+        for path in [
+            "HEAD",
+            "index",
+            "packed-refs",
+            "FETCH_HEAD",
+            "refs/heads/main",
+            "refs/remotes/origin/main",
+        ] {
+            assert!(is_watched_git_file(Path::new(path)), "{}", path);
+        }
+        for path in [
+            "index.lock",
+            "refs/heads/main.lock",
+            "objects/ab/cdef",
+            "logs/HEAD",
+            "config",
+        ] {
+            assert!(!is_watched_git_file(Path::new(path)), "{}", path);
+        }
+    }
+
+    #[test]
+    fn changes_to_ignored_files_and_reads_are_left_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        std::fs::write(workdir.join(".gitignore"), "target/\n").unwrap();
+        let relevant = |event: &Event| is_relevant(&repo, &workdir, repo.path(), event);
+
+        assert!(relevant(&modified(&workdir.join("src/main.rs"))));
+        assert!(relevant(&modified(&repo.path().join("index"))));
+        assert!(!relevant(&modified(&workdir.join("target/debug/gitui"))));
+        assert!(!relevant(&modified(&repo.path().join("objects/ab/cdef"))));
+        assert!(!relevant(&modified(&workdir.join("vendor/lib/.git/index"))));
+        let read =
+            Event::new(EventKind::Access(AccessKind::Any)).add_path(workdir.join("src/main.rs"));
+        assert!(!relevant(&read));
+    }
+
+    #[test]
+    fn reports_a_burst_of_changes_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        std::fs::write(workdir.join(".gitignore"), "target/\n").unwrap();
+        std::fs::create_dir(workdir.join("target")).unwrap();
+        let changes = watch(&repo);
+        // Give the thread time to start watching.
+        thread::sleep(Duration::from_millis(300));
+
+        std::fs::write(workdir.join("target/output"), "built").unwrap();
+        assert!(changes.recv_timeout(DEBOUNCE * 3).is_err());
+
+        for i in 0..5 {
+            std::fs::write(workdir.join("file.txt"), format!("change {}", i)).unwrap();
+        }
+        assert!(changes.recv_timeout(Duration::from_secs(5)).is_ok());
+        assert!(changes.recv_timeout(DEBOUNCE * 3).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+use git2::Repository;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Instant;
+
+use crate::debug;
+use crate::file_system::{get_file_list, FileEntry, FileListOptions, StatusSummary};
+use crate::git_ops::{
+    fetch_remote, file_details, pull_current_branch, push_current_branch, update_submodule,
+    DiffLine, DiffRequest, Hunk,
+};
+
+/// Slow git operations that run on the worker thread rather than blocking the UI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitTask {
+    Fetch,
+    Pull,
+    Push,
+    /// Initializes the submodule with this name if needed and checks out its recorded commit,
+    /// cloning or fetching it first when that commit isn't there.
+    UpdateSubmodule(String),
+    /// Reads the file list, which takes a while in a big working tree.
+    Status(StatusRequest),
+    /// Works out what the Details pane shows for a file, which takes a while for big diffs.
+    Diff(DiffRequest),
+}
+
+/// The options and directory states a file list is read with, given back with the list so
+/// that one read for a state the App has since moved on from can be told apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusRequest {
+    pub options: FileListOptions,
+    pub expanded_dirs: HashMap<String, bool>,
+}
+
+/// A file list read on the worker thread, with its counts and the directory states, pruned
+/// of directories that are gone.
+pub type FileList = (Vec<FileEntry>, StatusSummary, HashMap<String, bool>);
+
+impl GitTask {
+    fn name(&self) -> &'static str {
+        match self {
+            GitTask::Fetch => "Fetch",
+            GitTask::Pull => "Pull",
+            GitTask::Push => "Push",
+            GitTask::UpdateSubmodule(_) => "Submodule update",
+            GitTask::Status(_) => "Status",
+            GitTask::Diff(_) => "Diff",
+        }
+    }
+
+    /// What the status bar says while the task runs.
+    pub fn description(&self) -> &'static str {
+        match self {
+            GitTask::Fetch => "Fetching",
+            GitTask::Pull => "Pulling",
+            GitTask::Push => "Pushing",
+            GitTask::UpdateSubmodule(_) => "Updating submodule",
+            GitTask::Status(_) => "Reading status",
+            GitTask::Diff(_) => "Reading diff",
+        }
+    }
+
+    /// Reads don't change the repository, so they run beside the other tasks rather than
+    /// waiting behind a push, and without a word in the debug log or the status bar.
+    fn is_read(&self) -> bool {
+        matches!(self, GitTask::Status(_) | GitTask::Diff(_))
+    }
+}
+
+/// Sent back once a task is done. The summary or error of a task that changes the
+/// repository has already gone to the debug channel, after any progress messages, so that
+/// the log stays in order. Reads come back with what was read, for the App to show.
+#[derive(Debug)]
+pub enum GitResult {
+    Succeeded(GitTask),
+    Failed(GitTask),
+    Status(StatusRequest, Result<FileList, git2::Error>),
+    Diff(DiffRequest, Result<(Vec<DiffLine>, Vec<Hunk>), git2::Error>),
+}
+
+/// Hands tasks to a thread with its own handle on the repository and keeps track of the
+/// ones still running. Tasks run one at a time, in the order they were given. Reads go to
+/// a second thread, where only the latest of each kind is carried out.
+pub struct Worker {
+    tasks: Sender<GitTask>,
+    reads: Sender<GitTask>,
+    results: Receiver<GitResult>,
+    pending: Vec<GitTask>,
+    /// When the worker last went from idle to busy, to animate the spinner.
+    busy_since: Instant,
+}
+
+impl Worker {
+    pub fn spawn(repo_path: &Path) -> Self {
+        let (tasks, task_receiver) = channel::<GitTask>();
+        let (reads, read_receiver) = channel::<GitTask>();
+        let (result_sender, results) = channel();
+        let read_result_sender = result_sender.clone();
+        let repo_path = repo_path.to_path_buf();
+        let read_repo_path = repo_path.clone();
+        thread::spawn(move || {
+            let repo = Repository::open(&repo_path);
+            for task in task_receiver {
+                let outcome = match &repo {
+                    Ok(repo) => run_task(repo, &task),
+                    Err(e) => Err(git2::Error::from_str(e.message())),
+                };
+                let result = match outcome {
+                    Ok(summary) => {
+                        debug::debug_log(&summary);
+                        GitResult::Succeeded(task)
+                    }
+                    Err(e) => {
+                        debug::debug_log(&format!("{} failed: {}", task.name(), e.message()));
+                        GitResult::Failed(task)
+                    }
+                };
+                if result_sender.send(result).is_err() {
+                    break;
+                }
+            }
+        });
+        thread::spawn(move || read_loop(&read_repo_path, read_receiver, read_result_sender));
+        Self {
+            tasks,
+            reads,
+            results,
+            pending: Vec::new(),
+            busy_since: Instant::now(),
+        }
+    }
+
+    pub fn run(&mut self, task: GitTask) {
+        if task.is_read() {
+            // A stopped reader leaves the App waiting for nothing, which does no harm.
+            let _ = self.reads.send(task);
+            return;
+        }
+        debug::debug_log(&format!("{}...", task.description()));
+        if self.pending.is_empty() {
+            self.busy_since = Instant::now();
+        }
+        if self.tasks.send(task.clone()).is_ok() {
+            self.pending.push(task);
+        } else {
+            debug::debug_log(&format!("{} failed: the worker has stopped", task.name()));
+        }
+    }
+
+    /// The next finished task, if any, without waiting.
+    pub fn try_result(&mut self) -> Option<GitResult> {
+        let result = self.results.try_recv().ok()?;
+        if let GitResult::Succeeded(task) | GitResult::Failed(task) = &result {
+            if let Some(index) = self.pending.iter().position(|pending| pending == task) {
+                self.pending.remove(index);
+            }
+        }
+        Some(result)
+    }
+
+    /// The task running right now, if any.
+    pub fn current(&self) -> Option<&GitTask> {
+        self.pending.first()
+    }
+
+    pub fn busy_since(&self) -> Instant {
+        self.busy_since
+    }
+}
+
+/// Carries out reads until the Worker is dropped. Reads asked for while another was running
+/// are superseded by the last of them: the file list or the selection has moved on since.
+fn read_loop(repo_path: &Path, tasks: Receiver<GitTask>, results: Sender<GitResult>) {
+    let repo = Repository::open(repo_path);
+    while let Ok(task) = tasks.recv() {
+        let mut status = None;
+        let mut diff = None;
+        for task in std::iter::once(task).chain(tasks.try_iter()) {
+            match task {
+                GitTask::Status(request) => status = Some(request),
+                GitTask::Diff(request) => diff = Some(request),
+                _ => {}
+            }
+        }
+        let mut finished = Vec::new();
+        if let Some(request) = status {
+            let listed = with_repo(&repo, |repo| {
+                let mut expanded_dirs = request.expanded_dirs.clone();
+                let (files, summary) = get_file_list(repo, request.options, &mut expanded_dirs)?;
+                Ok((files, summary, expanded_dirs))
+            });
+            finished.push(GitResult::Status(request, listed));
+        }
+        if let Some(request) = diff {
+            let details = with_repo(&repo, |repo| file_details(repo, &request));
+            finished.push(GitResult::Diff(request, details));
+        }
+        for result in finished {
+            if results.send(result).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn with_repo<T>(
+    repo: &Result<Repository, git2::Error>,
+    read: impl FnOnce(&Repository) -> Result<T, git2::Error>,
+) -> Result<T, git2::Error> {
+    match repo {
+        Ok(repo) => read(repo),
+        Err(e) => Err(git2::Error::from_str(e.message())),
+    }
+}
+
+fn run_task(repo: &Repository, task: &GitTask) -> Result<String, git2::Error> {
+    match task {
+        GitTask::Fetch => fetch_remote(repo),
+        GitTask::Pull => pull_current_branch(repo),
+        GitTask::Push => push_current_branch(repo),
+        GitTask::UpdateSubmodule(name) => update_submodule(repo, name),
+        GitTask::Status(_) | GitTask::Diff(_) => Err(git2::Error::from_str(
+            "reads are carried out by the reader thread",
+        )),
+    }
+}